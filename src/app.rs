@@ -1,11 +1,12 @@
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 
 use crate::replay::AppMode;
-use crate::{cgroup, guard, health, proc, recording, supervisor};
+use crate::{cgroup, control, guard, health, proc, process, recording, supervisor};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ProcessSnapshot {
@@ -25,11 +26,52 @@ pub struct ProcessSnapshot {
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PodMemorySnapshot {
     pub cgroup_usage: u64,
+    /// Usage minus reclaimable page cache (`inactive_file`), as kubelet
+    /// computes it for eviction.
+    pub working_set: u64,
     pub cgroup_limit: Option<u64>,
+    /// cgroup v2 `memory.pressure`; `None` on cgroup v1 or without PSI.
+    pub pressure: Option<MemoryPressure>,
     pub rss_sum: u64,
     pub terminator_threshold_percent: u8,
 }
 
+/// Percent of wall time tasks were stalled on memory, from `memory.pressure`.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct MemoryPressure {
+    pub some_avg10: f64,
+    pub some_avg60: f64,
+    pub full_avg10: f64,
+    pub full_avg60: f64,
+}
+
+impl MemoryPressure {
+    pub fn value(&self, metric: guard::PressureMetric) -> f64 {
+        match metric {
+            guard::PressureMetric::SomeAvg10 => self.some_avg10,
+            guard::PressureMetric::SomeAvg60 => self.some_avg60,
+            guard::PressureMetric::FullAvg10 => self.full_avg10,
+            guard::PressureMetric::FullAvg60 => self.full_avg60,
+        }
+    }
+}
+
+impl PodMemorySnapshot {
+    pub fn bytes_for(&self, signal: guard::MemorySignal) -> u64 {
+        match signal {
+            guard::MemorySignal::Usage => self.cgroup_usage,
+            guard::MemorySignal::WorkingSet => self.working_set,
+        }
+    }
+
+    pub fn percent_for(&self, signal: guard::MemorySignal) -> Option<f64> {
+        match self.cgroup_limit {
+            Some(limit) if limit > 0 => Some(self.bytes_for(signal) as f64 / limit as f64 * 100.0),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ViewState {
     pub sort_column: SortColumn,
@@ -76,6 +118,8 @@ pub enum KillTarget {
         command: String,
         pid: Option<u32>,
         pgid: Option<u32>,
+        cgroup: Option<std::path::PathBuf>,
+        owner: Option<supervisor::RemoteOwner>,
     },
 }
 
@@ -100,7 +144,9 @@ pub struct App {
     pub watched_pids: HashSet<u32>,
     pub show_cmdline: Option<(u32, String, String)>,
     pub restart_requested: Option<usize>,
-    pub show_log: Option<(String, String)>,
+    pub stop_requested: Option<usize>,
+    pub adopt_requested: Option<u32>,
+    pub log_view: Option<crate::logview::LogView>,
     pub all_pids: HashSet<u32>,
     pub managed_children: Vec<crate::supervisor::ManagedChild>,
     pub guard: Option<crate::guard::Guard>,
@@ -110,6 +156,11 @@ pub struct App {
     pub dark_mode: bool,
     pub focus: FocusPane,
     pub selected_managed: usize,
+    /// Replies to requests sent to other supervisors, posted by the threads
+    /// that wait for them.
+    pub owner_replies: Arc<Mutex<Vec<String>>>,
+    /// Kills started from this UI, until they report how they went.
+    pub watched_kills: Vec<process::KillHandle>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -129,7 +180,9 @@ impl App {
             processes: Vec::new(),
             pod_memory: PodMemorySnapshot {
                 cgroup_usage: 0,
+                working_set: 0,
                 cgroup_limit: None,
+                pressure: None,
                 rss_sum: 0,
                 terminator_threshold_percent: threshold,
             },
@@ -150,7 +203,9 @@ impl App {
             watched_pids: HashSet::new(),
             show_cmdline: None,
             restart_requested: None,
-            show_log: None,
+            stop_requested: None,
+            adopt_requested: None,
+            log_view: None,
             all_pids: HashSet::new(),
             managed_children: Vec::new(),
             guard: None,
@@ -160,6 +215,8 @@ impl App {
             dark_mode: true,
             focus: FocusPane::Processes,
             selected_managed: 0,
+            owner_replies: Arc::new(Mutex::new(Vec::new())),
+            watched_kills: Vec::new(),
         }
     }
 
@@ -272,10 +329,10 @@ impl App {
                 })
                 .collect();
             for (pid, name) in exited_watched {
-                if let Some(count) = self.recording_manager.save_recording(pid, name.clone()) {
+                if let Some(saved) = self.recording_manager.save_recording(pid, name.clone()) {
                     self.set_status_message(format!(
                         "Recording saved: {} ({} snapshots)",
-                        name, count
+                        name, saved.snapshot_count
                     ));
                 }
                 self.watched_pids.remove(&pid);
@@ -336,15 +393,19 @@ impl App {
                 continue;
             }
 
+            // Shown after the previous supervisor's children; requests still
+            // go to the owner under the child's own index.
             let children = parse_child_snapshots(&content);
-            let child_offset = all_children.len();
+            let child_offset = index_after(&all_children);
             for mut child in children {
                 child.index += child_offset;
                 all_children.push(child);
             }
 
-            if best_guard.is_none() {
-                best_guard = parse_guard_snapshot(&content);
+            if !matches!(best_guard, Some(ref guard) if guard.leader) {
+                if let Some(guard) = parse_guard_snapshot(&content) {
+                    best_guard = Some(guard);
+                }
             }
         }
 
@@ -354,60 +415,131 @@ impl App {
     }
 
     pub fn read_other_instances_state(&mut self) {
-        let my_pid = std::process::id();
-        let my_file = format!("spm-state-{my_pid}.json");
+        let first_remote = index_after(&self.managed_children);
+        let mut remote_children = read_instance_children(std::process::id(), false);
+        for (offset, child) in remote_children.iter_mut().enumerate() {
+            child.index = first_remote + offset;
+        }
+        self.managed_children.extend(remote_children);
+    }
 
-        let entries = match std::fs::read_dir("/tmp") {
-            Ok(e) => e,
-            Err(_) => return,
-        };
+    pub fn set_status_message(&mut self, text: String) {
+        self.set_status_message_with_duration(text, Duration::from_secs(3));
+    }
 
-        let local_count = self.managed_children.len();
-        let mut remote_children = Vec::new();
+    pub fn set_status_message_with_duration(&mut self, text: String, duration: Duration) {
+        let expires_at = Instant::now() + duration;
+        self.status_message = Some(StatusMessage { text, expires_at });
+    }
 
-        for entry in entries.flatten() {
-            let name = entry.file_name();
-            let name_str = name.to_string_lossy();
-            if !name_str.starts_with("spm-state-") || !name_str.ends_with(".json") {
-                continue;
-            }
-            if name_str == my_file {
-                continue;
-            }
+    /// Send `request` for the selected managed child to the supervisor that
+    /// owns it. Returns false for children of this instance.
+    pub fn request_from_owner(&mut self, request: fn(String) -> control::ControlRequest) -> bool {
+        let Some(owner) = self
+            .managed_children
+            .get(self.selected_managed)
+            .and_then(|child| child.owner)
+        else {
+            return false;
+        };
+        let message = self.send_to_owner(owner, request(owner.index.to_string()));
+        self.set_status_message(message);
+        true
+    }
 
-            let content = match std::fs::read_to_string(entry.path()) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
+    /// Send `request` to the supervisor that owns a child without blocking
+    /// the UI; the reply shows up as a status message once it arrives.
+    pub fn send_to_owner(
+        &self,
+        owner: supervisor::RemoteOwner,
+        request: control::ControlRequest,
+    ) -> String {
+        let pending = format!(
+            "spm {}: sent '{}', waiting for reply",
+            owner.spm_pid,
+            control::format_request(&request)
+        );
+        let replies = self.owner_replies.clone();
+        std::thread::spawn(move || {
+            let message = control::request_summary(owner.spm_pid, &request);
+            if let Ok(mut replies) = replies.lock() {
+                replies.push(message);
+            }
+        });
+        pending
+    }
 
-            let timestamp = match extract_json_string(&content, "timestamp") {
-                Some(v) => v,
-                None => continue,
-            };
+    /// Show the latest reply from another supervisor, if one came in.
+    pub fn take_owner_replies(&mut self) {
+        let latest = match self.owner_replies.lock() {
+            Ok(mut replies) => replies.drain(..).next_back(),
+            Err(_) => None,
+        };
+        if let Some(message) = latest {
+            self.set_status_message(message);
+        }
+    }
 
-            if !is_timestamp_fresh(&timestamp, 5) {
-                let _ = std::fs::remove_file(entry.path());
-                continue;
+    /// Show how the latest kill started from this UI went, once it is done.
+    pub fn take_kill_outcomes(&mut self) {
+        let mut latest = None;
+        self.watched_kills.retain(|kill| {
+            if !process::is_finished(kill) {
+                return true;
             }
-
-            let children = parse_child_snapshots(&content);
-            let offset = local_count + remote_children.len();
-            for mut child in children {
-                child.index = offset + child.index;
-                remote_children.push(child);
+            if let Some(outcome) = process::take_outcome(kill) {
+                latest = Some(outcome);
             }
+            false
+        });
+        match latest {
+            Some(Ok(message)) => self.set_status_message(message),
+            Some(Err(message)) => self.set_status_message(format!("⚠ {message}")),
+            None => {}
         }
-
-        self.managed_children.extend(remote_children);
     }
 
-    pub fn set_status_message(&mut self, text: String) {
-        self.set_status_message_with_duration(text, Duration::from_secs(3));
+    /// Open the log pane on the selected managed child: its live output when
+    /// this instance runs it, else the log file its supervisor writes.
+    pub fn open_log_view(&mut self) {
+        let Some(child) = self.managed_children.get(self.selected_managed) else {
+            return;
+        };
+        let (title, source) = match (child.owner, &child.log_path) {
+            (None, _) => (
+                format!("{} — live output", child.name),
+                crate::logview::LogSource::Tail {
+                    tail: child.output_tail.clone(),
+                    pushed: 0,
+                },
+            ),
+            (Some(_), Some(path)) => (
+                format!("{} — {}", child.name, path.display()),
+                crate::logview::LogSource::File {
+                    path: path.clone(),
+                    seen: None,
+                },
+            ),
+            (Some(_), None) => {
+                self.set_status_message("No log file (headless mode)".to_string());
+                return;
+            }
+        };
+        self.log_view = Some(crate::logview::LogView::new(title, source));
     }
 
-    pub fn set_status_message_with_duration(&mut self, text: String, duration: Duration) {
-        let expires_at = Instant::now() + duration;
-        self.status_message = Some(StatusMessage { text, expires_at });
+    /// Hand the selected process to the only running supervisor.
+    pub fn request_adopt(&mut self) {
+        let Some(pid) = self.selected_process().map(|process| process.pid) else {
+            self.set_status_message("No process selected".to_string());
+            return;
+        };
+        let message = match control::discover_supervisors().as_slice() {
+            [spm_pid] => control::request_summary(*spm_pid, &control::ControlRequest::Adopt(pid)),
+            [] => format!("No supervisor running — start one with `spm adopt {pid}`"),
+            _ => format!("Several supervisors running — use `spm adopt {pid} --to <spm pid>`"),
+        };
+        self.set_status_message(message);
     }
 
     pub fn selected_process(&self) -> Option<&ProcessSnapshot> {
@@ -418,6 +550,60 @@ impl App {
     }
 }
 
+/// First index free for display after `children`.
+fn index_after(children: &[supervisor::ManagedChild]) -> usize {
+    children
+        .iter()
+        .map(|child| child.index + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Children from every fresh state file except `skip_pid`'s, each tagged with
+/// the supervisor that owns it. With `guarded_only`, supervisors that run
+/// with the guard disabled are left out.
+pub fn read_instance_children(skip_pid: u32, guarded_only: bool) -> Vec<supervisor::ManagedChild> {
+    let skip_file = format!("spm-state-{skip_pid}.json");
+    let entries = match std::fs::read_dir("/tmp") {
+        Ok(e) => e,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut children = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+        if !name_str.starts_with("spm-state-") || !name_str.ends_with(".json") {
+            continue;
+        }
+        if name_str == skip_file {
+            continue;
+        }
+
+        let content = match std::fs::read_to_string(entry.path()) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+
+        let timestamp = match extract_json_string(&content, "timestamp") {
+            Some(v) => v,
+            None => continue,
+        };
+
+        if !is_timestamp_fresh(&timestamp, 5) {
+            let _ = std::fs::remove_file(entry.path());
+            continue;
+        }
+        if guarded_only && !parse_guard_snapshot(&content).is_some_and(|guard| guard.config.enabled)
+        {
+            continue;
+        }
+
+        children.extend(parse_child_snapshots(&content));
+    }
+    children
+}
+
 fn is_timestamp_fresh(timestamp: &str, max_age_seconds: i64) -> bool {
     let ts_epoch = match parse_iso8601_epoch(timestamp) {
         Some(value) => value,
@@ -467,9 +653,17 @@ fn parse_child_snapshots(content: &str) -> Vec<supervisor::ManagedChild> {
         None => return Vec::new(),
     };
 
+    let spm_pid = extract_json_number::<u32>(content, "spm_pid");
     split_json_objects(&children)
         .into_iter()
         .filter_map(|object| parse_child_snapshot(&object))
+        .map(|mut child| {
+            child.owner = spm_pid.map(|spm_pid| supervisor::RemoteOwner {
+                spm_pid,
+                index: child.index,
+            });
+            child
+        })
         .collect()
 }
 
@@ -485,13 +679,58 @@ fn parse_child_snapshot(content: &str) -> Option<supervisor::ManagedChild> {
     let restart_count = extract_json_number::<u32>(content, "restart_count").unwrap_or(0);
 
     let mut child = supervisor::ManagedChild::new(index, command);
+    if let Some(name) = extract_json_string(content, "name") {
+        child.name = name;
+    }
     child.pid = pid;
     child.pgid = pid;
     child.state = parse_child_state(&state_raw);
     child.total_uss = total_uss;
+    child.total_pss = extract_json_number::<u64>(content, "total_pss").unwrap_or(0);
+    child.total_rss = extract_json_number::<u64>(content, "total_rss").unwrap_or(0);
+    child.process_count = extract_json_number::<u32>(content, "processes").unwrap_or(0);
     child.restart_count = restart_count;
     child.health.status = parse_health_status(&health_raw);
     child.health.port = port;
+    child.growth_rate = extract_json_number::<f64>(content, "growth_rate");
+    child.started_at = extract_json_number::<u64>(content, "uptime_secs")
+        .and_then(|secs| Instant::now().checked_sub(Duration::from_secs(secs)));
+    child.spec.priority = extract_json_number::<i32>(content, "priority").unwrap_or(0);
+    child.spec.protected = extract_json_bool(content, "protected").unwrap_or(false);
+    child.spec.existing_pid = extract_json_optional_u32(content, "adopted_pid");
+    if child.spec.existing_pid.is_some() {
+        child.origin = supervisor::Origin::Existing;
+    }
+    child.log_path = extract_json_string(content, "log_path").map(std::path::PathBuf::from);
+    child.quarantine =
+        extract_json_string(content, "quarantine_reason").map(|reason| supervisor::Quarantine {
+            reason,
+            bundle: extract_json_string(content, "quarantine_bundle").map(std::path::PathBuf::from),
+        });
+    if let Some(stage) =
+        extract_json_string(content, "stage").and_then(|stage| process::KillStage::parse(&stage))
+    {
+        let kill = process::KillProgress::start(process::StopSignal {
+            signal: extract_json_number::<i32>(content, "signal").unwrap_or(libc::SIGTERM),
+            timeout: Duration::ZERO,
+        });
+        if let Ok(mut progress) = kill.lock() {
+            progress.stage = stage;
+            progress.deadline = extract_json_number::<f64>(content, "remaining_secs")
+                .map(|secs| Instant::now() + Duration::from_secs_f64(secs.max(0.0)));
+        }
+        child.kill = Some(kill);
+    }
+    child.mitigation =
+        extract_json_string(content, "mitigation").and_then(|step| guard::GuardStep::parse(&step));
+    child.cpu_percent = extract_json_number::<f64>(content, "cpu_percent");
+    if let Some(pids) = extract_json_number::<u64>(content, "pids") {
+        child.cgroup_stats = Some(crate::cgroup::CgroupStats {
+            memory_current: extract_json_number::<u64>(content, "cgroup_memory"),
+            pids,
+            ..Default::default()
+        });
+    }
     Some(child)
 }
 
@@ -503,14 +742,24 @@ fn parse_guard_snapshot(content: &str) -> Option<guard::Guard> {
         extract_json_number::<u8>(&guard_content, "consecutive_ticks_above").unwrap_or(0);
     let total_kills = extract_json_number::<u32>(&guard_content, "total_kills").unwrap_or(0);
     let enabled = extract_json_bool(&guard_content, "enabled").unwrap_or(false);
+    let memory_signal = extract_json_string(&guard_content, "memory_signal")
+        .and_then(|value| guard::MemorySignal::parse(&value))
+        .unwrap_or_default();
+    let leader = extract_json_bool(&guard_content, "leader").unwrap_or(true);
+    let leader_pid = extract_json_optional_u32(&guard_content, "leader_pid");
+    let time_to_limit = extract_json_number::<f64>(&guard_content, "time_to_limit_secs");
 
     let mut config = guard::GuardConfig::default();
     config.kill_threshold_percent = kill_threshold_percent;
     config.enabled = enabled;
+    config.memory_signal = memory_signal;
 
     let mut guard = guard::Guard::new(config);
     guard.consecutive_ticks_above = consecutive_ticks_above;
     guard.total_kills = total_kills;
+    guard.leader = leader;
+    guard.leader_pid = leader_pid;
+    guard.time_to_limit = time_to_limit;
     Some(guard)
 }
 
@@ -522,10 +771,14 @@ fn parse_child_state(value: &str) -> supervisor::ChildState {
         supervisor::ChildState::Stopping { emergency }
     } else if value.starts_with("Restarting") {
         supervisor::ChildState::Restarting
+    } else if value.starts_with("Waiting") {
+        supervisor::ChildState::Waiting
     } else if value.starts_with("Completed") {
         supervisor::ChildState::Completed
     } else if value.starts_with("Failed") {
         supervisor::ChildState::Failed
+    } else if value.starts_with("Quarantined") {
+        supervisor::ChildState::Quarantined
     } else {
         supervisor::ChildState::Stopped
     }
@@ -553,6 +806,7 @@ fn extract_json_string(content: &str, key: &str) -> Option<String> {
             match ch {
                 'n' => out.push('\n'),
                 'r' => out.push('\r'),
+                't' => out.push('\t'),
                 '"' => out.push('"'),
                 '\\' => out.push('\\'),
                 other => out.push(other),
@@ -722,7 +976,8 @@ fn split_json_objects(content: &str) -> Vec<String> {
     objects
 }
 
-fn compute_growth_rate(samples: &VecDeque<(Instant, u64)>) -> Option<f64> {
+/// Growth in MB/min between the first and last of at least three samples.
+pub fn compute_growth_rate(samples: &VecDeque<(Instant, u64)>) -> Option<f64> {
     if samples.len() < 3 {
         return None;
     }
@@ -1,23 +1,31 @@
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-use crate::app::PodMemorySnapshot;
+use crate::app::{MemoryPressure, PodMemorySnapshot};
 
 pub fn read_pod_memory() -> PodMemorySnapshot {
     let threshold = read_threshold_percent();
 
-    let (usage, limit) = if Path::new("/sys/fs/cgroup/memory.max").exists() {
-        read_cgroup_v2()
+    let (usage, limit, inactive_file) = if Path::new("/sys/fs/cgroup/memory.max").exists() {
+        let (usage, limit) = read_cgroup_v2();
+        let inactive_file = read_memory_stat("/sys/fs/cgroup/memory.stat", "inactive_file");
+        (usage, limit, inactive_file)
     } else if Path::new("/sys/fs/cgroup/memory/memory.limit_in_bytes").exists() {
-        read_cgroup_v1()
+        let (usage, limit) = read_cgroup_v1();
+        let inactive_file =
+            read_memory_stat("/sys/fs/cgroup/memory/memory.stat", "total_inactive_file");
+        (usage, limit, inactive_file)
     } else {
-        (0, None)
+        (0, None, 0)
     };
 
     PodMemorySnapshot {
         cgroup_usage: usage,
+        working_set: usage.saturating_sub(inactive_file),
         cgroup_limit: limit,
+        pressure: read_memory_pressure("/sys/fs/cgroup/memory.pressure"),
         rss_sum: 0,
         terminator_threshold_percent: threshold,
     }
@@ -131,6 +139,299 @@ fn read_cgroup_v1() -> (u64, Option<u64>) {
     (usage, limit)
 }
 
+/// The cgroup v2 directory of `pid`, if it is not the one spm itself runs
+/// in or one above it — throttling or freezing a shared cgroup would hit spm
+/// and every other child too.
+pub fn dedicated_cgroup(pid: u32) -> Option<PathBuf> {
+    let own = read_cgroup_v2_path("/proc/self/cgroup")?;
+    let theirs = read_cgroup_v2_path(&format!("/proc/{pid}/cgroup"))?;
+    if Path::new(&own).starts_with(&theirs) {
+        return None;
+    }
+    let path = Path::new("/sys/fs/cgroup").join(theirs.trim_start_matches('/'));
+    path.join("cgroup.procs").exists().then_some(path)
+}
+
+const CONTROLLERS: [&str; 3] = ["memory", "cpu", "pids"];
+
+/// The parent of every child cgroup, created on first use.
+struct ChildCgroupRoot {
+    path: PathBuf,
+    /// The cgroup spm runs in, unless it had to move into
+    /// `<path>/supervisor` to enable controllers.
+    home: PathBuf,
+}
+
+static CHILD_CGROUP_ROOT: OnceLock<Option<ChildCgroupRoot>> = OnceLock::new();
+
+/// What enabling controllers for child cgroups did, the first time a child
+/// had limits to set.
+struct ControllerSetup {
+    /// The leaf spm moved itself into, if it had to.
+    moved_to: Option<PathBuf>,
+    /// Why some of `CONTROLLERS` are not enabled for child cgroups.
+    error: Option<String>,
+}
+
+static CONTROLLER_SETUP: OnceLock<ControllerSetup> = OnceLock::new();
+
+/// Per-child accounting read from a child's own cgroup.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CgroupStats {
+    pub memory_current: Option<u64>,
+    pub cpu_usage_usec: Option<u64>,
+    pub pids: u64,
+    pub sampled_at: Option<std::time::Instant>,
+}
+
+/// `<spm's cgroup>/spm-<pid>`, the parent of every child cgroup. Created on
+/// first use; `None` unless the pod runs on a pure cgroup v2 hierarchy that
+/// has been delegated to us.
+pub fn child_cgroup_root() -> Option<&'static Path> {
+    CHILD_CGROUP_ROOT
+        .get_or_init(create_child_cgroup_root)
+        .as_ref()
+        .map(|root| root.path.as_path())
+}
+
+/// Why child cgroups lack a memory, cpu or pids controller, if they do.
+pub fn controller_error() -> Option<&'static str> {
+    CONTROLLER_SETUP.get()?.error.as_deref()
+}
+
+fn create_child_cgroup_root() -> Option<ChildCgroupRoot> {
+    if !Path::new("/sys/fs/cgroup/cgroup.controllers").exists() {
+        return None;
+    }
+    let own = read_cgroup_v2_path("/proc/self/cgroup")?;
+    let own = Path::new("/sys/fs/cgroup").join(own.trim_start_matches('/'));
+    let root = own.join(format!("spm-{}", std::process::id()));
+    create_dir(&root).ok()?;
+    Some(ChildCgroupRoot {
+        path: root,
+        home: own,
+    })
+}
+
+/// Enable the memory, cpu and pids controllers for child cgroups, once,
+/// for the first child that has limits to set. Returns a note for that
+/// caller when spm had to move itself into another cgroup to do so.
+pub fn enable_child_controllers() -> Option<String> {
+    let root = CHILD_CGROUP_ROOT.get()?.as_ref()?;
+    let mut note = None;
+    CONTROLLER_SETUP.get_or_init(|| {
+        let setup = setup_controllers(root);
+        note = setup.moved_to.as_ref().map(|leaf| {
+            format!(
+                "moved spm from {} into {} to enable cgroup controllers for child limits",
+                root.home.display(),
+                leaf.display()
+            )
+        });
+        setup
+    });
+    note
+}
+
+fn setup_controllers(root: &ChildCgroupRoot) -> ControllerSetup {
+    let own = &root.home;
+    let mut moved_to = None;
+    let mut errors = enable_controllers(own);
+    if !errors.is_empty() {
+        // A cgroup that holds processes cannot hand controllers down to
+        // child cgroups (EBUSY), so spm moves itself into a leaf and tries
+        // again. Other processes left in `own` still block it; that is
+        // reported below.
+        let leaf = root.path.join("supervisor");
+        match create_dir(&leaf).and_then(|()| write_cgroup_file(&leaf, "cgroup.procs", "0")) {
+            Ok(()) => {
+                moved_to = Some(leaf);
+                errors = enable_controllers(own);
+            }
+            Err(error) => errors.push(error),
+        }
+    }
+    errors.extend(enable_controllers(&root.path));
+
+    let enabled = fs::read_to_string(root.path.join("cgroup.subtree_control")).unwrap_or_default();
+    let missing: Vec<&str> = CONTROLLERS
+        .into_iter()
+        .filter(|controller| !enabled.split_whitespace().any(|name| name == *controller))
+        .collect();
+    let controller_error = (!missing.is_empty()).then(|| {
+        let cause = if errors.is_empty() {
+            format!("not delegated to {}", own.display())
+        } else {
+            errors.join("; ")
+        };
+        format!(
+            "{} controller not enabled for child cgroups: {cause}",
+            missing.join("/")
+        )
+    });
+    ControllerSetup {
+        moved_to,
+        error: controller_error,
+    }
+}
+
+fn create_dir(path: &Path) -> Result<(), String> {
+    match fs::create_dir(path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+        Err(error) => Err(format!("Failed to create {}: {error}", path.display())),
+    }
+}
+
+/// Enable every available controller of `CONTROLLERS` for the cgroup's
+/// children; returns the writes that failed.
+fn enable_controllers(cgroup: &Path) -> Vec<String> {
+    let Ok(available) = fs::read_to_string(cgroup.join("cgroup.controllers")) else {
+        return Vec::new();
+    };
+    CONTROLLERS
+        .into_iter()
+        .filter(|controller| available.split_whitespace().any(|name| name == *controller))
+        .filter_map(|controller| {
+            write_cgroup_file(cgroup, "cgroup.subtree_control", &format!("+{controller}")).err()
+        })
+        .collect()
+}
+
+/// Create (or reuse) the cgroup a managed child is spawned into.
+pub fn create_child_cgroup(name: &str) -> Option<PathBuf> {
+    let path = child_cgroup_root()?.join(name);
+    match fs::create_dir(&path) {
+        Ok(()) => Some(path),
+        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => Some(path),
+        Err(_) => None,
+    }
+}
+
+/// Remove a child cgroup. Fails while it still has members.
+pub fn remove_child_cgroup(cgroup: &Path) {
+    let _ = fs::remove_dir(cgroup);
+}
+
+/// Remove every child cgroup and the root, if one was created. If spm left
+/// the cgroup it started in, it moves back so its own leaf can go too.
+pub fn remove_child_cgroups() {
+    let Some(Some(root)) = CHILD_CGROUP_ROOT.get() else {
+        return;
+    };
+    let ChildCgroupRoot { path: root, home } = root;
+    if CONTROLLER_SETUP
+        .get()
+        .is_some_and(|setup| setup.moved_to.is_some())
+    {
+        let _ = write_cgroup_file(home, "cgroup.procs", "0");
+    }
+    if let Ok(entries) = fs::read_dir(root) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                let _ = fs::remove_dir(entry.path());
+            }
+        }
+    }
+    let _ = fs::remove_dir(root);
+}
+
+/// Every process in a cgroup, including ones that called setsid.
+pub fn cgroup_pids(cgroup: &Path) -> Vec<u32> {
+    fs::read_to_string(cgroup.join("cgroup.procs"))
+        .map(|content| {
+            content
+                .lines()
+                .filter_map(|line| line.trim().parse::<u32>().ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn read_cgroup_stats(cgroup: &Path) -> CgroupStats {
+    let cpu_usage_usec = fs::read_to_string(cgroup.join("cpu.stat"))
+        .ok()
+        .and_then(|content| {
+            content.lines().find_map(|line| {
+                line.strip_prefix("usage_usec ")
+                    .and_then(|value| value.trim().parse::<u64>().ok())
+            })
+        });
+    CgroupStats {
+        memory_current: read_cgroup_u64(cgroup, "memory.current"),
+        cpu_usage_usec,
+        pids: read_cgroup_u64(cgroup, "pids.current")
+            .unwrap_or_else(|| cgroup_pids(cgroup).len() as u64),
+        sampled_at: Some(std::time::Instant::now()),
+    }
+}
+
+/// Write `value` to `file` in a cgroup directory.
+pub fn write_cgroup_file(cgroup: &Path, file: &str, value: &str) -> Result<(), String> {
+    let path = cgroup.join(file);
+    fs::write(&path, value).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+pub fn read_cgroup_u64(cgroup: &Path, file: &str) -> Option<u64> {
+    read_u64(cgroup.join(file).to_str()?)
+}
+
+/// The `0::<path>` entry of a `/proc/<pid>/cgroup` file.
+fn read_cgroup_v2_path(path: &str) -> Option<String> {
+    read_string(path)?
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .map(str::to_string)
+}
+
+/// Parse the `some` and `full` lines of a PSI file:
+/// `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`.
+fn read_memory_pressure(path: &str) -> Option<MemoryPressure> {
+    let content = read_string(path)?;
+    let mut pressure = MemoryPressure::default();
+    for line in content.lines() {
+        let mut fields = line.split_whitespace();
+        let Some(kind) = fields.next() else {
+            continue;
+        };
+        for field in fields {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+            let Ok(value) = value.parse::<f64>() else {
+                continue;
+            };
+            match (kind, key) {
+                ("some", "avg10") => pressure.some_avg10 = value,
+                ("some", "avg60") => pressure.some_avg60 = value,
+                ("full", "avg10") => pressure.full_avg10 = value,
+                ("full", "avg60") => pressure.full_avg60 = value,
+                _ => {}
+            }
+        }
+    }
+    Some(pressure)
+}
+
+/// A single counter from a `memory.stat` file, 0 if missing.
+fn read_memory_stat(path: &str, key: &str) -> u64 {
+    let content = match read_string(path) {
+        Some(content) => content,
+        None => return 0,
+    };
+    content
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(' ')?;
+            if name == key {
+                value.trim().parse::<u64>().ok()
+            } else {
+                None
+            }
+        })
+        .unwrap_or(0)
+}
+
 fn read_string(path: &str) -> Option<String> {
     match fs::read_to_string(path) {
         Ok(content) => Some(content),
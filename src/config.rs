@@ -0,0 +1,425 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::guard::{GuardStep, MemorySignal, PressureMetric};
+use crate::health::HealthConfig;
+use crate::policy::VictimStrategy;
+use crate::supervisor::{ChildSpec, CrashLoopConfig, PreStopConfig, RestartPolicy};
+
+/// Top-level layout of an `spm.toml` file.
+///
+/// ```toml
+/// [guard]
+/// kill_threshold = 80
+///
+/// [[child]]
+/// name = "api"
+/// command = ["python", "-m", "uvicorn", "app:main", "--port", "8000"]
+/// cwd = "/workspace/api"
+/// env = { LOG_LEVEL = "debug" }
+/// max_restarts = 5
+/// restart = "always"
+/// restart_window_secs = 600
+/// backoff_base_secs = 0.5
+/// backoff_max_secs = 60
+/// backoff_jitter = 0.2
+/// crash_loop = { restarts = 5, window_secs = 60, log_lines = 50 }
+/// priority = 100
+/// memory_max = "2G"
+/// cpu_max = 1.5
+/// health = { port = 8000, path = "/healthz" }
+/// stop_signal = "INT"
+/// stop_timeout_secs = 30
+/// pre_stop = { http = "/drain", timeout_secs = 10 }
+/// log = { dir = "/var/log/spm", max_size = "50M", max_files = 3, keep_runs = 5, timestamps = true }
+/// depends_on = ["migrate"]
+/// ready_timeout_secs = 120
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SupervisorConfig {
+    #[serde(default)]
+    pub guard: GuardSection,
+    #[serde(default, rename = "child")]
+    pub children: Vec<ChildConfig>,
+}
+
+/// Guard settings from the file. Anything left unset falls back to the CLI
+/// flag / env var, then to the built-in default.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GuardSection {
+    pub enabled: Option<bool>,
+    pub kill_threshold: Option<u8>,
+    pub grace_ticks: Option<u8>,
+    pub max_restarts: Option<u32>,
+    pub memory_signal: Option<MemorySignal>,
+    pub pressure_threshold: Option<f64>,
+    pub pressure_metric: Option<PressureMetric>,
+    pub forecast_horizon_secs: Option<u64>,
+    pub victim_strategy: Option<VictimStrategy>,
+    pub actions: Option<Vec<GuardStep>>,
+    pub resume_threshold: Option<u8>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChildConfig {
+    pub name: Option<String>,
+    pub command: CommandConfig,
+    pub cwd: Option<PathBuf>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub max_restarts: Option<u32>,
+    pub restart: Option<RestartPolicy>,
+    /// Limit `max_restarts` to this many seconds, e.g. 5 in 600.
+    pub restart_window_secs: Option<u64>,
+    pub backoff_base_secs: Option<f64>,
+    pub backoff_max_secs: Option<f64>,
+    /// Fraction of each delay, 0 to 1.
+    pub backoff_jitter: Option<f64>,
+    #[serde(default)]
+    pub crash_loop: CrashLoopConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub protected: bool,
+    /// Only enforced when the child gets a cgroup of its own.
+    pub memory_max: Option<MemorySize>,
+    /// In cores, e.g. `0.5`.
+    pub cpu_max: Option<f64>,
+    #[serde(default)]
+    pub primary: bool,
+    /// Signal name or number sent to the child if spm dies.
+    pub parent_death_signal: Option<String>,
+    /// Signal name or number that asks the child to stop.
+    pub stop_signal: Option<String>,
+    pub stop_timeout_secs: Option<f64>,
+    #[serde(default)]
+    pub pre_stop: PreStopConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+    /// Names of other children to wait for.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// How long to wait for `depends_on` before giving up (default: 300s).
+    pub ready_timeout_secs: Option<f64>,
+}
+
+/// Log rotation and retention. Anything left unset falls back to the CLI
+/// flag / env var, then to the built-in default.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LogConfig {
+    pub dir: Option<PathBuf>,
+    /// Size at which the log rolls over; `0` never rolls it.
+    pub max_size: Option<MemorySize>,
+    pub max_files: Option<usize>,
+    /// Earlier runs whose logs are kept.
+    pub keep_runs: Option<usize>,
+    pub timestamps: Option<bool>,
+}
+
+/// A byte count, either as an integer or with a `K`/`M`/`G`/`T` suffix.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MemorySize {
+    Bytes(u64),
+    Text(String),
+}
+
+/// A command is either a shell string (run through `sh -c`) or an argv list
+/// that is exec'd as-is.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum CommandConfig {
+    Shell(String),
+    Argv(Vec<String>),
+}
+
+pub fn load(path: &Path) -> Result<SupervisorConfig, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    parse(&content).map_err(|e| format!("Invalid config {}: {e}", path.display()))
+}
+
+pub fn parse(content: &str) -> Result<SupervisorConfig, String> {
+    let config: SupervisorConfig = toml::from_str(content).map_err(|e| e.to_string())?;
+    for (position, child) in config.children.iter().enumerate() {
+        child.validate(position)?;
+    }
+    validate_dependencies(&config.children)?;
+    Ok(config)
+}
+
+/// A child name from the file or `ctl add`. Names end up in log, bundle
+/// and cgroup paths, so only `[A-Za-z0-9._-]` is allowed.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(format!("invalid child name '{name}'"));
+    }
+    match name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+    {
+        Some(c) => Err(format!(
+            "child name '{name}' contains '{c}'; use letters, digits, '.', '_' and '-'"
+        )),
+        None => Ok(()),
+    }
+}
+
+/// A stop or pre-stop timeout from the file or the command line.
+pub fn validate_stop_timeout(secs: Option<f64>) -> Result<(), String> {
+    match secs {
+        Some(secs) if secs.is_nan() || secs <= 0.0 || secs > 86_400.0 => {
+            Err("must be between 0 and 86400 seconds".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Backoff settings from the file or the command line: a positive base, a
+/// cap no lower than it, and a jitter fraction between 0 and 1.
+pub fn validate_backoff(
+    base_secs: Option<f64>,
+    max_secs: Option<f64>,
+    jitter: Option<f64>,
+) -> Result<(), String> {
+    if let Some(base) = base_secs {
+        if base.is_nan() || base <= 0.0 || base > 86_400.0 {
+            return Err("backoff base must be between 0 and 86400 seconds".to_string());
+        }
+    }
+    if let Some(max) = max_secs {
+        if max.is_nan() || max < base_secs.unwrap_or(1.0) || max > 86_400.0 {
+            return Err(
+                "backoff max must be at least the base and at most 86400 seconds".to_string(),
+            );
+        }
+    }
+    match jitter {
+        Some(jitter) if !(0.0..=1.0).contains(&jitter) => {
+            Err("backoff jitter must be between 0 and 1".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Every `depends_on` entry names exactly one other child, and no child
+/// ends up waiting on itself.
+fn validate_dependencies(children: &[ChildConfig]) -> Result<(), String> {
+    for (position, child) in children.iter().enumerate() {
+        if let Some(ref name) = child.name {
+            if children[..position]
+                .iter()
+                .any(|other| other.name.as_ref() == Some(name))
+            {
+                return Err(format!("{name}: more than one child has this name"));
+            }
+        }
+    }
+    for (position, child) in children.iter().enumerate() {
+        let label = child
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("child #{position}"));
+        for dependency in &child.depends_on {
+            if !children
+                .iter()
+                .any(|other| other.name.as_ref() == Some(dependency))
+            {
+                return Err(format!("{label}: depends_on unknown child '{dependency}'"));
+            }
+        }
+
+        if depends_on(children, position, position, &mut Vec::new()) {
+            return Err(format!("{label}: depends_on forms a cycle"));
+        }
+    }
+    Ok(())
+}
+
+/// Whether child `from` waits on child `target`, directly or indirectly.
+fn depends_on(children: &[ChildConfig], from: usize, target: usize, seen: &mut Vec<usize>) -> bool {
+    for dependency in dependencies_of(children, from) {
+        if dependency == target {
+            return true;
+        }
+        if !seen.contains(&dependency) {
+            seen.push(dependency);
+            if depends_on(children, dependency, target, seen) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn dependencies_of(children: &[ChildConfig], position: usize) -> Vec<usize> {
+    children[position]
+        .depends_on
+        .iter()
+        .filter_map(|name| {
+            children
+                .iter()
+                .position(|child| child.name.as_deref() == Some(name.as_str()))
+        })
+        .collect()
+}
+
+impl ChildConfig {
+    fn validate(&self, position: usize) -> Result<(), String> {
+        let label = self
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("child #{position}"));
+        if let Some(ref name) = self.name {
+            validate_name(name)?;
+        }
+        match &self.command {
+            CommandConfig::Shell(command) if command.trim().is_empty() => {
+                Err(format!("{label}: command is empty"))
+            }
+            CommandConfig::Argv(argv) if argv.is_empty() || argv[0].trim().is_empty() => {
+                Err(format!("{label}: command is empty"))
+            }
+            _ => Ok(()),
+        }?;
+        if let Some(ref size) = self.memory_max {
+            size.bytes()
+                .map_err(|error| format!("{label}: memory_max: {error}"))?;
+        }
+        if let Some(ref signal) = self.parent_death_signal {
+            if crate::control::parse_signal(signal).is_none() {
+                return Err(format!("{label}: unknown parent_death_signal '{signal}'"));
+            }
+        }
+        if let Some(ref signal) = self.stop_signal {
+            if crate::control::parse_signal(signal).is_none() {
+                return Err(format!("{label}: unknown stop_signal '{signal}'"));
+            }
+        }
+        validate_stop_timeout(self.stop_timeout_secs)
+            .map_err(|error| format!("{label}: stop_timeout_secs {error}"))?;
+        if self.pre_stop.command.is_some() && self.pre_stop.http.is_some() {
+            return Err(format!(
+                "{label}: pre_stop takes either command or http, not both"
+            ));
+        }
+        validate_stop_timeout(self.pre_stop.timeout_secs)
+            .map_err(|error| format!("{label}: pre_stop.timeout_secs {error}"))?;
+        validate_stop_timeout(self.ready_timeout_secs)
+            .map_err(|error| format!("{label}: ready_timeout_secs {error}"))?;
+        if let Some(ref size) = self.log.max_size {
+            size.bytes()
+                .map_err(|error| format!("{label}: log.max_size: {error}"))?;
+        }
+        if self.restart_window_secs == Some(0) {
+            return Err(format!("{label}: restart_window_secs must be positive"));
+        }
+        validate_backoff(
+            self.backoff_base_secs,
+            self.backoff_max_secs,
+            self.backoff_jitter,
+        )
+        .map_err(|error| format!("{label}: {error}"))?;
+        if self.crash_loop.restarts > 0 && self.crash_loop.window_secs == 0 {
+            return Err(format!("{label}: crash_loop.window_secs must be positive"));
+        }
+        if self.crash_loop.log_lines > 1000 {
+            return Err(format!(
+                "{label}: crash_loop.log_lines must be at most 1000"
+            ));
+        }
+        match self.cpu_max {
+            Some(cores) if cores.is_nan() || cores <= 0.0 => {
+                Err(format!("{label}: cpu_max must be positive"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn into_spec(self) -> ChildSpec {
+        let (command, argv) = match self.command {
+            CommandConfig::Shell(command) => (command, None),
+            CommandConfig::Argv(argv) => (argv.join(" "), Some(argv)),
+        };
+        ChildSpec {
+            name: self.name,
+            command,
+            argv,
+            cwd: self.cwd,
+            env: self.env.into_iter().collect(),
+            max_restarts: self.max_restarts,
+            restart: self.restart,
+            restart_window: self.restart_window_secs.map(Duration::from_secs),
+            backoff_base: self.backoff_base_secs.map(Duration::from_secs_f64),
+            backoff_max: self.backoff_max_secs.map(Duration::from_secs_f64),
+            backoff_jitter: self.backoff_jitter,
+            crash_loop: self.crash_loop,
+            health: self.health,
+            priority: self.priority,
+            protected: self.protected,
+            memory_max: self.memory_max.and_then(|size| size.bytes().ok()),
+            cpu_max: self.cpu_max,
+            primary: self.primary,
+            parent_death_signal: self
+                .parent_death_signal
+                .as_deref()
+                .and_then(crate::control::parse_signal),
+            stop_signal: self
+                .stop_signal
+                .as_deref()
+                .and_then(crate::control::parse_signal),
+            stop_timeout: self.stop_timeout_secs.map(Duration::from_secs_f64),
+            pre_stop: self.pre_stop,
+            log: crate::logs::LogSettings {
+                dir: self.log.dir,
+                max_bytes: self.log.max_size.and_then(|size| size.bytes().ok()),
+                max_files: self.log.max_files,
+                keep_runs: self.log.keep_runs,
+                timestamps: self.log.timestamps,
+            },
+            existing_pid: None,
+            depends_on: self.depends_on,
+            ready_timeout: self.ready_timeout_secs.map(Duration::from_secs_f64),
+        }
+    }
+}
+
+impl MemorySize {
+    pub fn bytes(&self) -> Result<u64, String> {
+        match self {
+            MemorySize::Bytes(bytes) => Ok(*bytes),
+            MemorySize::Text(text) => parse_memory_size(text),
+        }
+    }
+}
+
+/// `"512M"`, `"2G"`, `"1.5Gi"` or a plain byte count. Suffixes are powers of
+/// 1024, as in cgroup files.
+pub fn parse_memory_size(text: &str) -> Result<u64, String> {
+    let trimmed = text.trim();
+    let number_end = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(number_end);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{text}'"))?;
+    let multiplier: u64 = match suffix.trim().trim_end_matches(['i', 'B']) {
+        "" => 1,
+        "K" | "k" => 1 << 10,
+        "M" | "m" => 1 << 20,
+        "G" | "g" => 1 << 30,
+        "T" | "t" => 1 << 40,
+        _ => return Err(format!("invalid size '{text}'")),
+    };
+    Ok((value * multiplier as f64) as u64)
+}
@@ -0,0 +1,504 @@
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How long a client has to send its request line before it is dropped.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// One request per connection, sent as a single text line:
+///
+/// ```text
+/// list
+/// start <target>
+/// stop <target>
+/// restart <target>
+/// kill <target>
+/// guard-kill <target> [emergency]
+/// guard-throttle <target>
+/// guard-freeze <target>
+/// signal <target> <signal>
+/// add <name|-> <shell command...>
+/// adopt <pid>
+/// remove <target>
+/// ```
+///
+/// `<target>` is a child name or index. The reply is a single JSON line with
+/// an `ok` field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlRequest {
+    List,
+    Start(String),
+    Stop(String),
+    Restart(String),
+    /// Terminate the process group as if it had crashed; the restart policy
+    /// decides what happens next.
+    Kill(String),
+    /// Sent by the guard leader when it picks one of our children as the
+    /// victim; handled exactly like a kill by our own guard. Without a
+    /// reason, the kill is reported against our own pod memory reading.
+    GuardKill {
+        target: String,
+        emergency: bool,
+        reason: Option<crate::guard::KillReason>,
+    },
+    /// Sent by the guard leader to take a step short of killing. The reply
+    /// carries the step actually taken, which may be further up the ladder.
+    GuardMitigate {
+        target: String,
+        step: crate::guard::GuardStep,
+        reason: Option<crate::guard::KillReason>,
+    },
+    Signal(String, i32),
+    /// A shell command, or a program and its arguments exec'd directly.
+    Add {
+        name: Option<String>,
+        command: String,
+        argv: Option<Vec<String>>,
+    },
+    /// Take over an already-running process as a new child.
+    Adopt(u32),
+    Remove(String),
+}
+
+pub struct ControlServer {
+    listener: UnixListener,
+    /// Connections whose request line has not fully arrived yet.
+    pending: Vec<PendingRequest>,
+}
+
+struct PendingRequest {
+    stream: UnixStream,
+    received: Vec<u8>,
+    accepted_at: Instant,
+}
+
+pub fn socket_path(pid: u32) -> PathBuf {
+    PathBuf::from(format!("/tmp/spm-ctl-{pid}.sock"))
+}
+
+pub fn remove_socket() {
+    let _ = std::fs::remove_file(socket_path(std::process::id()));
+}
+
+impl ControlServer {
+    pub fn bind() -> Result<Self, String> {
+        let path = socket_path(std::process::id());
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path)
+            .map_err(|e| format!("Failed to bind {}: {e}", path.display()))?;
+        // `add` runs commands as us, so only our own user may connect.
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("Failed to restrict {}: {e}", path.display()))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure control socket: {e}"))?;
+        Ok(Self {
+            listener,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Accept new connections and answer those whose request line has
+    /// arrived, without blocking on clients that are slow to send it.
+    pub fn poll(&mut self, mut handle: impl FnMut(ControlRequest) -> String) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if !peer_is_us(&stream) || stream.set_nonblocking(true).is_err() {
+                continue;
+            }
+            self.pending.push(PendingRequest {
+                stream,
+                received: Vec::new(),
+                accepted_at: Instant::now(),
+            });
+        }
+
+        self.pending.retain_mut(|pending| {
+            let mut buffer = [0u8; 4096];
+            let mut closed = false;
+            loop {
+                match pending.stream.read(&mut buffer) {
+                    Ok(0) => {
+                        closed = true;
+                        break;
+                    }
+                    Ok(read) => pending.received.extend_from_slice(&buffer[..read]),
+                    Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                    Err(_) => return false,
+                }
+            }
+            // A client may close its end instead of ending the line.
+            if pending.received.contains(&b'\n') || (closed && !pending.received.is_empty()) {
+                answer(pending, &mut handle);
+                return false;
+            }
+            !closed && pending.accepted_at.elapsed() < REQUEST_TIMEOUT
+        });
+    }
+}
+
+/// Answer the request line `pending` has received.
+fn answer(pending: &mut PendingRequest, handle: &mut impl FnMut(ControlRequest) -> String) {
+    let end = pending
+        .received
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .unwrap_or(pending.received.len());
+    let line = String::from_utf8_lossy(&pending.received[..end]);
+    let response = match parse_request(&line) {
+        Ok(request) => handle(request),
+        Err(error) => error_response(&error),
+    };
+    // The reply may not fit the socket buffer; give the client a moment.
+    let _ = pending.stream.set_nonblocking(false);
+    let _ = pending.stream.set_write_timeout(Some(REQUEST_TIMEOUT));
+    let _ = writeln!(pending.stream, "{response}");
+}
+
+/// Whether the process at the other end runs as our user (or root).
+fn peer_is_us(stream: &UnixStream) -> bool {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: u32::MAX,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    result == 0 && (cred.uid == 0 || cred.uid == unsafe { libc::geteuid() })
+}
+
+pub fn ok_response() -> String {
+    "{\"ok\":true}".to_string()
+}
+
+pub fn error_response(message: &str) -> String {
+    format!(
+        "{{\"ok\":false,\"error\":\"{}\"}}",
+        crate::monitor::escape_json(message)
+    )
+}
+
+pub fn parse_request(line: &str) -> Result<ControlRequest, String> {
+    let line = line.trim();
+    let (op, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let target = || {
+        if rest.is_empty() || rest.contains(char::is_whitespace) {
+            Err(format!("'{op}' takes exactly one target"))
+        } else {
+            Ok(rest.to_string())
+        }
+    };
+
+    match op {
+        "list" => Ok(ControlRequest::List),
+        "start" => Ok(ControlRequest::Start(target()?)),
+        "stop" => Ok(ControlRequest::Stop(target()?)),
+        "restart" => Ok(ControlRequest::Restart(target()?)),
+        "kill" => Ok(ControlRequest::Kill(target()?)),
+        "guard-kill" => {
+            let mut parts = rest.split_whitespace();
+            let usage = || "usage: guard-kill <target> [emergency] [reason]".to_string();
+            let target = parts.next().ok_or_else(usage)?.to_string();
+            let mut emergency = false;
+            let mut reason = None;
+            for part in parts {
+                if part == "emergency" && !emergency && reason.is_none() {
+                    emergency = true;
+                } else if reason.is_none() {
+                    reason = Some(crate::guard::KillReason::parse_token(part).ok_or_else(usage)?);
+                } else {
+                    return Err(usage());
+                }
+            }
+            Ok(ControlRequest::GuardKill {
+                target,
+                emergency,
+                reason,
+            })
+        }
+        "guard-throttle" | "guard-freeze" => {
+            let step = if op == "guard-throttle" {
+                crate::guard::GuardStep::Throttle
+            } else {
+                crate::guard::GuardStep::Freeze
+            };
+            let mut parts = rest.split_whitespace();
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(target), reason, None) => Ok(ControlRequest::GuardMitigate {
+                    target: target.to_string(),
+                    step,
+                    reason: match reason {
+                        Some(token) => Some(
+                            crate::guard::KillReason::parse_token(token)
+                                .ok_or_else(|| format!("usage: {op} <target> [reason]"))?,
+                        ),
+                        None => None,
+                    },
+                }),
+                _ => Err(format!("usage: {op} <target> [reason]")),
+            }
+        }
+        "remove" => Ok(ControlRequest::Remove(target()?)),
+        "adopt" => target()?
+            .parse::<u32>()
+            .map(ControlRequest::Adopt)
+            .map_err(|_| "usage: adopt <pid>".to_string()),
+        "signal" => {
+            let mut parts = rest.split_whitespace();
+            let (target, signal) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(target), Some(signal), None) => (target, signal),
+                _ => return Err("usage: signal <target> <signal>".to_string()),
+            };
+            let signal =
+                parse_signal(signal).ok_or_else(|| format!("unknown signal '{signal}'"))?;
+            Ok(ControlRequest::Signal(target.to_string(), signal))
+        }
+        "add" => {
+            let (name, command) = rest
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| "usage: add <name|-> <command>".to_string())?;
+            let command = command.trim();
+            if command.is_empty() {
+                return Err("usage: add <name|-> <command>".to_string());
+            }
+            Ok(ControlRequest::Add {
+                name: Some(name.to_string()).filter(|name| name != "-"),
+                command: command.to_string(),
+                argv: None,
+            })
+        }
+        "add-argv" => {
+            let usage = || "usage: add-argv <name|-> [\"program\", \"arg\", ...]".to_string();
+            let (name, argv) = rest.split_once(char::is_whitespace).ok_or_else(usage)?;
+            let argv = parse_string_array(argv.trim()).map_err(|_| usage())?;
+            if argv.first().is_none_or(|program| program.is_empty()) {
+                return Err(usage());
+            }
+            Ok(ControlRequest::Add {
+                name: Some(name.to_string()).filter(|name| name != "-"),
+                command: argv.join(" "),
+                argv: Some(argv),
+            })
+        }
+        "" => Err("empty request".to_string()),
+        other => Err(format!("unknown operation '{other}'")),
+    }
+}
+
+pub fn format_request(request: &ControlRequest) -> String {
+    match request {
+        ControlRequest::List => "list".to_string(),
+        ControlRequest::Start(target) => format!("start {target}"),
+        ControlRequest::Stop(target) => format!("stop {target}"),
+        ControlRequest::Restart(target) => format!("restart {target}"),
+        ControlRequest::Kill(target) => format!("kill {target}"),
+        ControlRequest::GuardKill {
+            target,
+            emergency,
+            reason,
+        } => {
+            let mut line = format!("guard-kill {target}");
+            if *emergency {
+                line.push_str(" emergency");
+            }
+            if let Some(reason) = reason {
+                line.push(' ');
+                line.push_str(&reason.to_token());
+            }
+            line
+        }
+        ControlRequest::GuardMitigate {
+            target,
+            step,
+            reason,
+        } => match reason {
+            Some(reason) => format!("guard-{} {target} {}", step.as_str(), reason.to_token()),
+            None => format!("guard-{} {target}", step.as_str()),
+        },
+        ControlRequest::Signal(target, signal) => format!("signal {target} {signal}"),
+        ControlRequest::Add {
+            name,
+            argv: Some(argv),
+            ..
+        } => {
+            let argv: Vec<String> = argv
+                .iter()
+                .map(|arg| format!("\"{}\"", crate::monitor::escape_json(arg)))
+                .collect();
+            format!(
+                "add-argv {} [{}]",
+                name.as_deref().unwrap_or("-"),
+                argv.join(",")
+            )
+        }
+        ControlRequest::Add {
+            name,
+            command,
+            argv: None,
+        } => {
+            format!("add {} {command}", name.as_deref().unwrap_or("-"))
+        }
+        ControlRequest::Adopt(pid) => format!("adopt {pid}"),
+        ControlRequest::Remove(target) => format!("remove {target}"),
+    }
+}
+
+/// A JSON array of strings, as `format_request` writes argv lists.
+fn parse_string_array(text: &str) -> Result<Vec<String>, ()> {
+    let inner = text
+        .strip_prefix('[')
+        .and_then(|text| text.strip_suffix(']'))
+        .ok_or(())?;
+    let mut items = Vec::new();
+    let mut chars = inner.trim().chars().peekable();
+    while chars.peek().is_some() {
+        if chars.next() != Some('"') {
+            return Err(());
+        }
+        let mut item = String::new();
+        loop {
+            match chars.next().ok_or(())? {
+                '"' => break,
+                '\\' => match chars.next().ok_or(())? {
+                    'n' => item.push('\n'),
+                    'r' => item.push('\r'),
+                    't' => item.push('\t'),
+                    'u' => {
+                        let code: String = chars.by_ref().take(4).collect();
+                        let code = u32::from_str_radix(&code, 16).map_err(|_| ())?;
+                        item.push(char::from_u32(code).ok_or(())?);
+                    }
+                    other => item.push(other),
+                },
+                other => item.push(other),
+            }
+        }
+        items.push(item);
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        match chars.next() {
+            Some(',') => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            None => break,
+            Some(_) => return Err(()),
+        }
+    }
+    Ok(items)
+}
+
+pub fn parse_signal(value: &str) -> Option<i32> {
+    if let Ok(number) = value.parse::<i32>() {
+        return Some(number).filter(|number| *number > 0 && *number < 65);
+    }
+    let upper = value.to_ascii_uppercase();
+    let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+    let signal = match name {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        "TERM" => libc::SIGTERM,
+        "CONT" => libc::SIGCONT,
+        "STOP" => libc::SIGSTOP,
+        "TSTP" => libc::SIGTSTP,
+        "WINCH" => libc::SIGWINCH,
+        _ => return None,
+    };
+    Some(signal)
+}
+
+/// `SIGTERM` for 15, the number for signals `parse_signal` has no name for.
+pub fn signal_name(signal: i32) -> String {
+    let name = match signal {
+        libc::SIGHUP => "SIGHUP",
+        libc::SIGINT => "SIGINT",
+        libc::SIGQUIT => "SIGQUIT",
+        libc::SIGKILL => "SIGKILL",
+        libc::SIGUSR1 => "SIGUSR1",
+        libc::SIGUSR2 => "SIGUSR2",
+        libc::SIGTERM => "SIGTERM",
+        libc::SIGCONT => "SIGCONT",
+        libc::SIGSTOP => "SIGSTOP",
+        libc::SIGTSTP => "SIGTSTP",
+        libc::SIGWINCH => "SIGWINCH",
+        _ => return format!("signal {signal}"),
+    };
+    name.to_string()
+}
+
+/// Send one request to the supervisor with the given pid and return its raw
+/// JSON reply.
+pub fn send_request(pid: u32, request: &ControlRequest) -> Result<String, String> {
+    let path = socket_path(pid);
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("Cannot connect to {}: {e}", path.display()))?;
+    // Kills run in the background, so replies come within a monitor poll;
+    // the slack covers a guard request the leader forwards to another spm.
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+    writeln!(stream, "{}", format_request(request))
+        .map_err(|e| format!("Failed to send request: {e}"))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read reply: {e}"))?;
+    if line.trim().is_empty() {
+        return Err("Supervisor closed the connection without replying".to_string());
+    }
+    Ok(line.trim().to_string())
+}
+
+/// Send a request and turn the reply into a one-line status message.
+pub fn request_summary(pid: u32, request: &ControlRequest) -> String {
+    match send_request(pid, request) {
+        Ok(reply) if reply.starts_with("{\"ok\":true") => {
+            format!("spm {pid}: {} ok", format_request(request))
+        }
+        Ok(reply) => {
+            let error = reply
+                .split_once("\"error\":\"")
+                .and_then(|(_, rest)| rest.strip_suffix("\"}"))
+                .unwrap_or(&reply);
+            format!("spm {pid}: {error}")
+        }
+        Err(error) => error,
+    }
+}
+
+/// Pids of supervisors with a live control socket in /tmp.
+pub fn discover_supervisors() -> Vec<u32> {
+    let mut pids: Vec<u32> = match std::fs::read_dir("/tmp") {
+        Ok(entries) => entries
+            .flatten()
+            .filter_map(|entry| {
+                let name = entry.file_name();
+                let name = name.to_str()?;
+                name.strip_prefix("spm-ctl-")?
+                    .strip_suffix(".sock")?
+                    .parse::<u32>()
+                    .ok()
+            })
+            .filter(|pid| unsafe { libc::kill(*pid as i32, 0) } == 0)
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    pids.sort_unstable();
+    pids
+}
@@ -1,5 +1,21 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
 use std::time::{Duration, Instant};
 
+use serde::Deserialize;
+
+/// Whoever holds an exclusive flock on this file runs the guard for the whole
+/// pod; other supervisors defer to it.
+pub const LEADER_LOCK_PATH: &str = "/tmp/spm-guard.lock";
+
+/// Pod memory samples (one per tick) the forecast fits its growth rate to.
+const FORECAST_WINDOW: usize = 5;
+
+/// A forecast shorter than the SIGTERM grace period gets SIGKILL right away.
+const TERM_GRACE_SECS: f64 = 3.0;
+
 #[derive(Clone, Debug)]
 pub struct GuardConfig {
     pub kill_threshold_percent: u8,
@@ -8,6 +24,43 @@ pub struct GuardConfig {
     pub max_restarts: u32,
     pub enabled: bool,
     pub post_kill_cooldown: Duration,
+    pub memory_signal: MemorySignal,
+    /// Stall percentage from `memory.pressure` that triggers the guard on its
+    /// own, even below `kill_threshold_percent`. `None` disables PSI.
+    pub pressure_threshold: Option<f64>,
+    pub pressure_metric: PressureMetric,
+    /// Kill as soon as pod memory, projected forward at its recent growth
+    /// rate, would reach the terminator threshold within this many seconds.
+    /// `None` disables the forecast.
+    pub forecast_horizon: Option<Duration>,
+    /// Steps tried on a victim, one per grace period, before it is killed.
+    /// Always ends in `Kill`.
+    pub actions: Vec<GuardStep>,
+    /// Throttled and frozen children are released once pod memory drops
+    /// below this percentage.
+    pub resume_threshold_percent: u8,
+}
+
+/// One rung of the escalation ladder the guard walks for a victim.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum GuardStep {
+    /// Cap `memory.high` at the child's current usage.
+    Throttle,
+    /// `cgroup.freeze`, or SIGSTOP when the child has no cgroup of its own.
+    Freeze,
+    Kill,
+}
+
+/// Which pod memory figure the guard compares against the threshold.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum MemorySignal {
+    /// Raw `memory.current` / `memory.usage_in_bytes`, page cache included.
+    #[default]
+    Usage,
+    /// Usage minus `inactive_file`, which the kernel reclaims before OOM.
+    WorkingSet,
 }
 
 #[derive(Clone, Debug)]
@@ -17,6 +70,20 @@ pub struct Guard {
     pub total_kills: u32,
     pub last_kill: Option<KillEvent>,
     pub logged_unlimited: bool,
+    pub leader: bool,
+    pub leader_pid: Option<u32>,
+    pub last_mitigation: Option<Instant>,
+    pub memory_samples: VecDeque<(Instant, u64)>,
+    /// Seconds until the terminator threshold at the current growth rate;
+    /// `None` while memory is flat or shrinking.
+    pub time_to_limit: Option<f64>,
+}
+
+/// Leader election for the pod-wide guard. The kernel drops the flock when the
+/// holder exits, so a follower takes over on its next tick.
+#[derive(Debug, Default)]
+pub struct LeaderLock {
+    file: Option<File>,
 }
 
 #[derive(Clone, Debug)]
@@ -38,14 +105,22 @@ pub enum GuardAction {
         reason: KillReason,
         emergency: bool,
     },
+    /// A step short of killing, taken because the ladder says so.
+    Mitigate {
+        victim_index: usize,
+        step: GuardStep,
+        reason: KillReason,
+    },
     Exhausted {
         percent: f64,
     },
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum KillReason {
     ThresholdExceeded { pod_percent: f64 },
+    MemoryPressure { stall_percent: f64 },
+    Forecast { seconds_to_limit: f64 },
     HealthCheckFailed,
 }
 
@@ -58,6 +133,90 @@ impl GuardConfig {
             max_restarts: 10,
             enabled: true,
             post_kill_cooldown: Duration::from_secs(5),
+            memory_signal: MemorySignal::default(),
+            pressure_threshold: None,
+            pressure_metric: PressureMetric::default(),
+            forecast_horizon: None,
+            actions: vec![GuardStep::Kill],
+            resume_threshold_percent: 65,
+        }
+    }
+}
+
+/// Which `memory.pressure` average is compared with `pressure_threshold`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PressureMetric {
+    SomeAvg10,
+    SomeAvg60,
+    /// All non-idle tasks stalled on memory at once — thrashing.
+    #[default]
+    FullAvg10,
+    FullAvg60,
+}
+
+impl GuardStep {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GuardStep::Throttle => "throttle",
+            GuardStep::Freeze => "freeze",
+            GuardStep::Kill => "kill",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "throttle" => Some(GuardStep::Throttle),
+            "freeze" => Some(GuardStep::Freeze),
+            "kill" => Some(GuardStep::Kill),
+            _ => None,
+        }
+    }
+}
+
+impl KillReason {
+    /// The form sent to another supervisor in `guard-*` requests.
+    pub fn to_token(&self) -> String {
+        match self {
+            KillReason::ThresholdExceeded { pod_percent } => format!("threshold={pod_percent:.1}"),
+            KillReason::MemoryPressure { stall_percent } => format!("pressure={stall_percent:.1}"),
+            KillReason::Forecast { seconds_to_limit } => format!("forecast={seconds_to_limit:.1}"),
+            KillReason::HealthCheckFailed => "health".to_string(),
+        }
+    }
+
+    pub fn parse_token(token: &str) -> Option<Self> {
+        if token == "health" {
+            return Some(KillReason::HealthCheckFailed);
+        }
+        let (kind, value) = token.split_once('=')?;
+        let value: f64 = value.parse().ok()?;
+        match kind {
+            "threshold" => Some(KillReason::ThresholdExceeded { pod_percent: value }),
+            "pressure" => Some(KillReason::MemoryPressure {
+                stall_percent: value,
+            }),
+            "forecast" => Some(KillReason::Forecast {
+                seconds_to_limit: value,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl MemorySignal {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            MemorySignal::Usage => "usage",
+            MemorySignal::WorkingSet => "working-set",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "usage" => Some(MemorySignal::Usage),
+            "working-set" => Some(MemorySignal::WorkingSet),
+            _ => None,
         }
     }
 }
@@ -70,6 +229,11 @@ impl Guard {
             total_kills: 0,
             last_kill: None,
             logged_unlimited: false,
+            leader: false,
+            leader_pid: None,
+            last_mitigation: None,
+            memory_samples: VecDeque::new(),
+            time_to_limit: None,
         }
     }
 
@@ -83,26 +247,47 @@ impl Guard {
             return GuardAction::None;
         }
 
-        let limit = match pod_memory.cgroup_limit {
-            Some(limit) if limit > 0 => limit,
-            Some(_) => return GuardAction::None,
+        let usage_percent = match pod_memory.cgroup_limit {
+            Some(limit) if limit > 0 => Some(
+                (pod_memory.bytes_for(self.config.memory_signal) as f64 / limit as f64) * 100.0,
+            ),
+            Some(_) => None,
             None => {
-                if !self.logged_unlimited {
+                if !self.logged_unlimited && self.config.pressure_threshold.is_none() {
                     eprintln!("[spm] Guard disabled: cgroup memory limit is unlimited");
                     self.logged_unlimited = true;
                 }
-                return GuardAction::None;
+                None
             }
         };
+        self.time_to_limit = self.forecast(pod_memory);
+        let stall_percent = pod_memory
+            .pressure
+            .map(|pressure| pressure.value(self.config.pressure_metric));
 
-        let percent = (pod_memory.cgroup_usage as f64 / limit as f64) * 100.0;
-        if percent < self.config.kill_threshold_percent as f64 {
+        let over_usage = usage_percent
+            .is_some_and(|percent| percent >= self.config.kill_threshold_percent as f64);
+        let over_pressure = match (stall_percent, self.config.pressure_threshold) {
+            (Some(stall), Some(threshold)) => stall >= threshold,
+            _ => false,
+        };
+        let over_forecast = match (self.time_to_limit, self.config.forecast_horizon) {
+            (Some(seconds), Some(horizon)) => seconds <= horizon.as_secs_f64(),
+            _ => false,
+        };
+        if !over_usage && !over_pressure && !over_forecast {
             self.consecutive_ticks_above = 0;
             return GuardAction::None;
         }
+        let percent = usage_percent.unwrap_or(0.0);
 
-        if let Some(ref last_kill) = self.last_kill {
-            if last_kill.at.elapsed() < self.config.post_kill_cooldown {
+        let last_action = self
+            .last_kill
+            .as_ref()
+            .map(|kill| kill.at)
+            .max(self.last_mitigation);
+        if let Some(at) = last_action {
+            if at.elapsed() < self.config.post_kill_cooldown {
                 return GuardAction::Warning {
                     percent,
                     ticks_remaining: 0,
@@ -110,24 +295,139 @@ impl Guard {
             }
         }
 
+        // Waiting out the grace window is pointless when the forecast says
+        // the pod will be gone before it ends.
         self.consecutive_ticks_above = self.consecutive_ticks_above.saturating_add(1);
-        if self.consecutive_ticks_above < self.config.grace_ticks {
+        if self.consecutive_ticks_above < self.config.grace_ticks && !over_forecast {
             return GuardAction::Warning {
                 percent,
                 ticks_remaining: self.config.grace_ticks - self.consecutive_ticks_above,
             };
         }
 
-        let emergency = percent >= self.config.emergency_threshold_percent as f64;
-        match policy.select_victim(managed) {
-            Some(victim_index) => GuardAction::Kill {
+        let emergency = percent >= self.config.emergency_threshold_percent as f64
+            || (over_forecast && self.time_to_limit.is_some_and(|s| s < TERM_GRACE_SECS));
+        let reason = if over_usage {
+            KillReason::ThresholdExceeded {
+                pod_percent: percent,
+            }
+        } else if over_forecast {
+            KillReason::Forecast {
+                seconds_to_limit: self.time_to_limit.unwrap_or(0.0),
+            }
+        } else {
+            KillReason::MemoryPressure {
+                stall_percent: stall_percent.unwrap_or(0.0),
+            }
+        };
+        let Some(victim_index) = policy.select_victim(managed) else {
+            return GuardAction::Exhausted { percent };
+        };
+        let step = if emergency {
+            GuardStep::Kill
+        } else {
+            self.next_step(managed[victim_index].mitigation)
+        };
+        match step {
+            GuardStep::Kill => GuardAction::Kill {
                 victim_index,
-                reason: KillReason::ThresholdExceeded {
-                    pod_percent: percent,
-                },
+                reason,
                 emergency,
             },
-            None => GuardAction::Exhausted { percent },
+            step => GuardAction::Mitigate {
+                victim_index,
+                step,
+                reason,
+            },
         }
     }
+
+    /// Whether pod memory has eased enough to release throttled and frozen
+    /// children.
+    pub fn should_resume(&self, pod_memory: &crate::app::PodMemorySnapshot) -> bool {
+        let usage_ok = pod_memory
+            .percent_for(self.config.memory_signal)
+            .is_none_or(|percent| percent < self.config.resume_threshold_percent as f64);
+        let pressure_ok = match (pod_memory.pressure, self.config.pressure_threshold) {
+            (Some(pressure), Some(threshold)) => {
+                pressure.value(self.config.pressure_metric) < threshold
+            }
+            _ => true,
+        };
+        usage_ok && pressure_ok
+    }
+
+    /// The rung after `current` on the configured ladder; `Kill` once the
+    /// ladder runs out.
+    pub fn next_step(&self, current: Option<GuardStep>) -> GuardStep {
+        let position = match current {
+            Some(current) => match self.config.actions.iter().position(|step| *step == current) {
+                Some(position) => position + 1,
+                None => 0,
+            },
+            None => 0,
+        };
+        self.config
+            .actions
+            .get(position)
+            .copied()
+            .unwrap_or(GuardStep::Kill)
+    }
+
+    /// Record this tick's pod memory and project when it will reach the
+    /// terminator threshold.
+    fn forecast(&mut self, pod_memory: &crate::app::PodMemorySnapshot) -> Option<f64> {
+        let bytes = pod_memory.bytes_for(self.config.memory_signal);
+        self.memory_samples.push_back((Instant::now(), bytes));
+        while self.memory_samples.len() > FORECAST_WINDOW {
+            self.memory_samples.pop_front();
+        }
+
+        let limit = pod_memory.cgroup_limit.filter(|limit| *limit > 0)?;
+        let mb_per_minute = crate::app::compute_growth_rate(&self.memory_samples)?;
+        if mb_per_minute <= 0.0 {
+            return None;
+        }
+        let bytes_per_second = mb_per_minute * 1024.0 * 1024.0 / 60.0;
+        let target = limit as f64 * pod_memory.terminator_threshold_percent as f64 / 100.0;
+        Some(((target - bytes as f64) / bytes_per_second).max(0.0))
+    }
+}
+
+impl LeaderLock {
+    pub fn try_acquire(&mut self) -> bool {
+        if self.file.is_some() {
+            return true;
+        }
+        let file = match OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(LEADER_LOCK_PATH)
+        {
+            Ok(file) => file,
+            Err(_) => return false,
+        };
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            return false;
+        }
+        let _ = file.set_len(0);
+        let _ = (&file).write_all(format!("{}\n", std::process::id()).as_bytes());
+        self.file = Some(file);
+        true
+    }
+
+    pub fn release(&mut self) {
+        self.file = None;
+    }
+}
+
+/// Pid written by the current guard leader, if it is still alive.
+pub fn read_leader_pid() -> Option<u32> {
+    let pid = std::fs::read_to_string(LEADER_LOCK_PATH)
+        .ok()?
+        .trim()
+        .parse::<u32>()
+        .ok()?;
+    (unsafe { libc::kill(pid as i32, 0) } == 0).then_some(pid)
 }
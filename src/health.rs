@@ -4,8 +4,11 @@ use std::net::{SocketAddr, TcpStream};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 
+use serde::Deserialize;
+
 #[derive(Clone, Debug)]
 pub struct HealthState {
+    pub config: HealthConfig,
     pub status: HealthStatus,
     pub port: Option<u16>,
     pub endpoint: Option<String>,
@@ -16,6 +19,32 @@ pub struct HealthState {
     pub baseline_ports: HashSet<u16>,
 }
 
+/// Per-child health settings. Defaults reproduce the auto-discovery behaviour:
+/// find the first new LISTEN port and probe `HEALTH_PATHS` on it.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HealthConfig {
+    pub enabled: bool,
+    /// Skip port discovery and probe this port directly.
+    pub port: Option<u16>,
+    /// Probe only this path instead of trying `HEALTH_PATHS`.
+    pub path: Option<String>,
+    pub interval_secs: u64,
+    pub failure_threshold: u8,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            port: None,
+            path: None,
+            interval_secs: 5,
+            failure_threshold: 3,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum HealthStatus {
     Discovering,
@@ -202,31 +231,43 @@ pub fn discover_health_endpoint(port: u16) -> Option<String> {
 }
 
 impl HealthState {
-    pub fn new() -> Self {
+    pub fn with_config(config: HealthConfig) -> Self {
         Self {
+            failure_threshold: config.failure_threshold.max(1),
+            config,
             status: HealthStatus::Discovering,
             port: None,
             endpoint: None,
             last_check: None,
             consecutive_failures: 0,
-            failure_threshold: 3,
             discovering_since: None,
             baseline_ports: HashSet::new(),
         }
     }
 
-    pub fn new_with_baseline(pid: u32) -> Self {
+    pub fn new_with_baseline(pid: u32, config: HealthConfig) -> Self {
         let baseline: HashSet<u16> = get_all_listen_ports(pid).into_iter().collect();
         Self {
             baseline_ports: baseline,
-            ..Self::new()
+            ..Self::with_config(config)
         }
     }
 
     /// Called every tick. Handles state transitions.
     pub fn tick(&mut self, pid: Option<u32>) {
+        if !self.config.enabled {
+            self.status = HealthStatus::NotApplicable;
+            return;
+        }
+
         match self.status {
             HealthStatus::Discovering => {
+                if let Some(port) = self.config.port {
+                    self.port = Some(port);
+                    self.status = HealthStatus::Probing;
+                    return;
+                }
+
                 if self.discovering_since.is_none() {
                     self.discovering_since = Some(Instant::now());
                 }
@@ -252,7 +293,13 @@ impl HealthState {
             }
             HealthStatus::Probing => {
                 if let Some(port) = self.port {
-                    if let Some(endpoint) = discover_health_endpoint(port) {
+                    let endpoint = match self.config.path {
+                        Some(ref path) => {
+                            Some(path.clone()).filter(|path| probe_health(port, path))
+                        }
+                        None => discover_health_endpoint(port),
+                    };
+                    if let Some(endpoint) = endpoint {
                         self.endpoint = Some(endpoint);
                         self.status = HealthStatus::Healthy;
                         self.consecutive_failures = 0;
@@ -263,7 +310,7 @@ impl HealthState {
             }
             HealthStatus::Healthy => {
                 let should_check = match self.last_check {
-                    Some(last) => last.elapsed() >= Duration::from_secs(5),
+                    Some(last) => last.elapsed() >= Duration::from_secs(self.config.interval_secs),
                     None => true,
                 };
 
@@ -285,8 +332,17 @@ impl HealthState {
         }
     }
 
+    /// Passed its health check, or has none to pass: what a dependent
+    /// child waits for.
+    pub fn is_ready(&self) -> bool {
+        matches!(
+            self.status,
+            HealthStatus::Healthy | HealthStatus::NotApplicable
+        )
+    }
+
     /// Reset for restart (re-discover port)
     pub fn reset(&mut self) {
-        *self = HealthState::new();
+        *self = HealthState::with_config(self.config.clone());
     }
 }
@@ -0,0 +1,743 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::supervisor::{OutputLine, OutputStream, SPM_NOTE_PREFIX};
+
+/// Where a child's output goes and how much of it is kept. Anything unset
+/// falls back to the CLI flag / env var, then to the defaults below.
+#[derive(Clone, Debug, Default)]
+pub struct LogSettings {
+    pub dir: Option<PathBuf>,
+    /// Size at which the log rolls over to `.log.1`.
+    pub max_bytes: Option<u64>,
+    /// Rolled-over segments kept per run.
+    pub max_files: Option<usize>,
+    /// Logs of earlier runs kept next to the current one.
+    pub keep_runs: Option<usize>,
+    /// Prefix each line with a UTC timestamp.
+    pub timestamps: Option<bool>,
+}
+
+pub const DEFAULT_LOG_DIR: &str = "/tmp";
+const DEFAULT_MAX_BYTES: u64 = 10 << 20;
+const DEFAULT_MAX_FILES: usize = 3;
+const DEFAULT_KEEP_RUNS: usize = 5;
+
+impl LogSettings {
+    pub fn dir(&self) -> &Path {
+        self.dir
+            .as_deref()
+            .unwrap_or_else(|| Path::new(DEFAULT_LOG_DIR))
+    }
+
+    /// `0` turns rotation off.
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes.unwrap_or(DEFAULT_MAX_BYTES)
+    }
+
+    pub fn max_files(&self) -> usize {
+        self.max_files.unwrap_or(DEFAULT_MAX_FILES)
+    }
+
+    pub fn keep_runs(&self) -> usize {
+        self.keep_runs.unwrap_or(DEFAULT_KEEP_RUNS)
+    }
+
+    pub fn timestamps(&self) -> bool {
+        self.timestamps.unwrap_or(false)
+    }
+}
+
+/// The log of a child's current run: `<dir>/spm-<index>-<name>.log`.
+pub fn log_path(settings: &LogSettings, index: usize, name: &str) -> PathBuf {
+    settings.dir().join(format!("spm-{index}-{name}.log"))
+}
+
+/// Appends a child's output to its log, rolling it over to `.log.1`,
+/// `.log.2`, ... once it outgrows `max_bytes`.
+#[derive(Debug)]
+pub struct LogWriter {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    max_files: usize,
+    timestamps: bool,
+    /// Set when the child writes to the file itself.
+    direct: Option<DirectLog>,
+}
+
+/// A log the child writes itself, and what spm needs to read it back.
+#[derive(Debug)]
+struct DirectLog {
+    /// How far spm has read the log.
+    offset: u64,
+    /// The child's stderr, `<log>.stderr`. spm moves its lines into the log
+    /// so it knows which lines they are.
+    stderr: File,
+    /// How far spm has moved `stderr`.
+    stderr_offset: u64,
+    /// The log opened once more, for the stderr lines: writes through the
+    /// child's copies would not tell where they landed.
+    appender: File,
+    /// Where stderr lines spm has not read back yet sit in the log.
+    stderr_ranges: VecDeque<(u64, u64)>,
+    /// The filesystem can cut the start off the log.
+    collapses: bool,
+}
+
+/// Longest line `take_new_lines` waits on for its newline.
+const MAX_PARTIAL_LINE: u64 = 64 << 10;
+/// Most `take_new_lines` reads at once; older output is skipped, as the
+/// output tail would drop it anyway.
+const MAX_READ: u64 = 1 << 20;
+
+impl LogWriter {
+    /// Start a fresh log at `path`; whatever was there has been kept by
+    /// `preserve_previous_run`.
+    pub fn create(path: &Path, settings: &LogSettings) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .and_then(|file| file.set_len(0).map(|()| file))
+            .map_err(|e| format!("Failed to create log: {e}"))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            written: 0,
+            max_bytes: settings.max_bytes(),
+            max_files: settings.max_files(),
+            timestamps: settings.timestamps(),
+            direct: None,
+        })
+    }
+
+    /// Hand the log to the child as its stdout, and `<log>.stderr` as its
+    /// stderr. Output no longer passes through spm, so the child is
+    /// unaffected if spm dies; spm reads it back with `take_new_lines`,
+    /// which also moves stderr into the log and rotates the file.
+    pub fn child_output(&mut self) -> Result<(File, File), String> {
+        let share = |e| format!("Failed to share log: {e}");
+        let stderr = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(stderr_path(&self.path))
+            .and_then(|file| file.set_len(0).map(|()| file))
+            .map_err(share)?;
+        let appender = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(share)?;
+        let output = (
+            self.file.try_clone().map_err(share)?,
+            stderr.try_clone().map_err(share)?,
+        );
+        self.direct = Some(DirectLog {
+            offset: 0,
+            stderr,
+            stderr_offset: 0,
+            appender,
+            stderr_ranges: VecDeque::new(),
+            collapses: can_collapse(self.path.parent().unwrap_or(Path::new("."))),
+        });
+        Ok(output)
+    }
+
+    pub fn is_direct(&self) -> bool {
+        self.direct.is_some()
+    }
+
+    /// Lines the child wrote to a log it was handed since the last call.
+    pub fn take_new_lines(&mut self) -> Vec<OutputLine> {
+        self.move_stderr(false);
+        let Some(ref mut direct) = self.direct else {
+            return Vec::new();
+        };
+        let len = file_len(&self.file);
+        if len < direct.offset {
+            // Truncated by someone else: start over.
+            direct.offset = 0;
+            direct.stderr_ranges.clear();
+        }
+        let skipped = len - direct.offset > MAX_READ;
+        let start = if skipped {
+            len - MAX_READ
+        } else {
+            direct.offset
+        };
+        let mut buf = vec![0; (len - start) as usize];
+        let read = self.file.read_at(&mut buf, start).unwrap_or(0);
+        buf.truncate(read);
+        let complete = match buf.iter().rposition(|byte| *byte == b'\n') {
+            Some(end) => end + 1,
+            None if buf.len() as u64 >= MAX_PARTIAL_LINE => buf.len(),
+            None => 0,
+        };
+        direct.offset = start + complete as u64;
+
+        let mut lines = Vec::new();
+        let mut at = start;
+        for line in buf[..complete].split_inclusive(|byte| *byte == b'\n') {
+            let line_start = at;
+            at += line.len() as u64;
+            if skipped && line_start == start {
+                continue;
+            }
+            let ranges = &mut direct.stderr_ranges;
+            while ranges.front().is_some_and(|(_, end)| *end <= line_start) {
+                ranges.pop_front();
+            }
+            let stream = if ranges.front().is_some_and(|(from, _)| *from <= line_start) {
+                OutputStream::Stderr
+            } else {
+                OutputStream::Stdout
+            };
+            let text = line.strip_suffix(b"\n").unwrap_or(line);
+            lines.push(OutputLine {
+                stream,
+                text: String::from_utf8_lossy(text).into_owned(),
+            });
+        }
+        if self.max_bytes > 0 && len > self.max_bytes {
+            self.rotate_direct(len);
+        }
+        lines
+    }
+
+    /// Move what the child wrote to `<log>.stderr` into the log, a line at a
+    /// time, noting where it landed. With `all`, an unfinished last line
+    /// goes too.
+    fn move_stderr(&mut self, all: bool) {
+        let Some(ref mut direct) = self.direct else {
+            return;
+        };
+        loop {
+            let len = file_len(&direct.stderr);
+            let end = len.min(direct.stderr_offset + MAX_READ);
+            let mut buf = vec![0; end.saturating_sub(direct.stderr_offset) as usize];
+            let read = direct
+                .stderr
+                .read_at(&mut buf, direct.stderr_offset)
+                .unwrap_or(0);
+            buf.truncate(read);
+            let complete = match buf.iter().rposition(|byte| *byte == b'\n') {
+                Some(end) => end + 1,
+                None if all || buf.len() as u64 >= MAX_PARTIAL_LINE => buf.len(),
+                None => 0,
+            };
+            if complete == 0 {
+                break;
+            }
+            direct.stderr_offset += complete as u64;
+            buf.truncate(complete);
+            if buf.last() != Some(&b'\n') {
+                buf.push(b'\n');
+            }
+            if direct.appender.write_all(&buf).is_ok() {
+                if let Ok(end) = direct.appender.stream_position() {
+                    direct
+                        .stderr_ranges
+                        .push_back((end - buf.len() as u64, end));
+                }
+            }
+            if end == len {
+                break;
+            }
+        }
+        // Moved lines need no room in the stderr file any more; what is
+        // left there is recovered by `preserve_previous_run` if spm dies.
+        unsafe {
+            libc::fallocate(
+                direct.stderr.as_raw_fd(),
+                libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                0,
+                direct.stderr_offset as libc::off_t,
+            );
+        }
+    }
+
+    /// Move the last of the child's stderr into the log once it has exited,
+    /// and remove `<log>.stderr`.
+    pub fn finish_stderr(&mut self) {
+        if self.direct.is_none() {
+            return;
+        }
+        self.move_stderr(true);
+        let _ = std::fs::remove_file(stderr_path(&self.path));
+    }
+
+    /// Roll a log the child writes itself, `len` bytes long when last read,
+    /// over to `.log.1`. The part spm has read is copied there and cut off
+    /// the start of the log, in whole blocks, so the child can go on writing
+    /// meanwhile; a line may be split between `.log.1` and the log. Where
+    /// the filesystem cannot cut a file, the log is copied and truncated
+    /// once spm has read all of it, and a note in the log says lines
+    /// written meanwhile may be missing.
+    fn rotate_direct(&mut self, len: u64) {
+        let Some((offset, collapses)) = self
+            .direct
+            .as_ref()
+            .map(|direct| (direct.offset, direct.collapses))
+        else {
+            return;
+        };
+        let segment = segment_path(&self.path, 1);
+        if collapses {
+            let block = self
+                .file
+                .metadata()
+                .map(|m| m.blksize())
+                .unwrap_or(4096)
+                .max(1);
+            // The cut has to leave part of the file in place.
+            let cut = offset.min(len.saturating_sub(1)) / block * block;
+            if cut == 0 {
+                return;
+            }
+            self.shift_segments();
+            if self.max_files > 0 {
+                let _ = copy_range(&self.file, 0, cut, &segment);
+            }
+            let collapsed = unsafe {
+                libc::fallocate(
+                    self.file.as_raw_fd(),
+                    libc::FALLOC_FL_COLLAPSE_RANGE,
+                    0,
+                    cut as libc::off_t,
+                ) == 0
+            };
+            let Some(ref mut direct) = self.direct else {
+                return;
+            };
+            if collapsed {
+                direct.offset -= cut;
+                for range in direct.stderr_ranges.iter_mut() {
+                    *range = (range.0.saturating_sub(cut), range.1.saturating_sub(cut));
+                }
+                direct.stderr_ranges.retain(|(_, end)| *end > 0);
+                return;
+            }
+            direct.collapses = false;
+            // `.log.1` holds the start of the log already; add the rest.
+            if self.max_files > 0 {
+                let _ = copy_range(&self.file, cut, len, &segment);
+            }
+        } else {
+            if offset < len {
+                return;
+            }
+            self.shift_segments();
+            if self.max_files > 0 {
+                let _ = copy_range(&self.file, 0, len, &segment);
+            }
+        }
+        let _ = self.file.set_len(0);
+        if let Some(ref mut direct) = self.direct {
+            direct.offset = 0;
+            direct.stderr_ranges.clear();
+        }
+        self.write_line(&format!(
+            "{SPM_NOTE_PREFIX}log rotated by copying and truncating it, as this filesystem cannot cut the start off a file; lines written meanwhile may be missing"
+        ));
+    }
+
+    pub fn write_line(&mut self, line: &str) {
+        let line = if self.timestamps {
+            format!("{} {line}\n", crate::monitor::chrono_like_timestamp())
+        } else {
+            format!("{line}\n")
+        };
+        let len = line.len() as u64;
+        if self.direct.is_none()
+            && self.max_bytes > 0
+            && self.written > 0
+            && self.written + len > self.max_bytes
+        {
+            self.rotate();
+        }
+        if self.file.write_all(line.as_bytes()).is_ok() {
+            self.written += len;
+        }
+    }
+
+    fn rotate(&mut self) {
+        self.shift_segments();
+        if self.max_files > 0 {
+            let _ = std::fs::rename(&self.path, segment_path(&self.path, 1));
+        }
+        if let Ok(file) = File::create(&self.path) {
+            self.file = file;
+        }
+        self.written = 0;
+    }
+
+    /// Make room for a new `.log.1`, dropping the oldest segment.
+    fn shift_segments(&self) {
+        if self.max_files == 0 {
+            return;
+        }
+        let _ = std::fs::remove_file(segment_path(&self.path, self.max_files));
+        for segment in (1..self.max_files).rev() {
+            let _ = std::fs::rename(
+                segment_path(&self.path, segment),
+                segment_path(&self.path, segment + 1),
+            );
+        }
+    }
+}
+
+fn segment_path(path: &Path, segment: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{segment}"));
+    PathBuf::from(name)
+}
+
+/// Where a child writing its log itself writes its stderr.
+fn stderr_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".stderr");
+    PathBuf::from(name)
+}
+
+/// Whether files in `dir` can have their start cut off, which rotating a log
+/// the child writes itself takes to lose nothing. Found out on a scratch
+/// file, as the log has nothing to cut yet.
+fn can_collapse(dir: &Path) -> bool {
+    let probe = dir.join(format!(".spm-collapse-{}", std::process::id()));
+    let collapses = (|| {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&probe)?;
+        let block = file.metadata()?.blksize().max(1);
+        file.write_all_at(&vec![b'\n'; 2 * block as usize], 0)?;
+        let result = unsafe {
+            libc::fallocate(
+                file.as_raw_fd(),
+                libc::FALLOC_FL_COLLAPSE_RANGE,
+                0,
+                block as libc::off_t,
+            )
+        };
+        Ok::<_, std::io::Error>(result == 0)
+    })();
+    let _ = std::fs::remove_file(&probe);
+    collapses.unwrap_or(false)
+}
+
+fn file_len(file: &File) -> u64 {
+    file.metadata().map(|m| m.len()).unwrap_or(0)
+}
+
+/// Append bytes `from..to` of `file` to the file at `target`.
+fn copy_range(file: &File, from: u64, to: u64, target: &Path) -> std::io::Result<()> {
+    let mut target = OpenOptions::new().create(true).append(true).open(target)?;
+    let mut buf = vec![0; MAX_READ as usize];
+    let mut at = from;
+    while at < to {
+        let want = (to - at).min(MAX_READ) as usize;
+        let read = file.read_at(&mut buf[..want], at)?;
+        if read == 0 {
+            break;
+        }
+        target.write_all(&buf[..read])?;
+        at += read as u64;
+    }
+    Ok(())
+}
+
+/// Append to the log at `path` what the child wrote to `<log>.stderr` that
+/// no spm moved into the log, e.g. after spm was killed, and remove it.
+fn recover_stderr(path: &Path) {
+    let side = stderr_path(path);
+    let Ok(mut file) = File::open(&side) else {
+        return;
+    };
+    // What spm moved was punched out of the file: start at what is left,
+    // past the zeros a moved line ending mid-block leaves.
+    let mut rest = Vec::new();
+    let start = unsafe { libc::lseek(file.as_raw_fd(), 0, libc::SEEK_DATA) };
+    if start >= 0 && file.read_to_end(&mut rest).is_ok() {
+        let from = rest
+            .iter()
+            .position(|byte| *byte != 0)
+            .unwrap_or(rest.len());
+        let mut text = rest.split_off(from);
+        if !text.is_empty() {
+            if text.last() != Some(&b'\n') {
+                text.push(b'\n');
+            }
+            if let Ok(mut log) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = log.write_all(&text);
+            }
+        }
+    }
+    let _ = std::fs::remove_file(side);
+}
+
+/// Move the log the last run left at `path`, with its rolled-over segments
+/// and any stderr no spm moved into it, to `spm-<index>-<name>.<time>.<reason>.log`, then drop all but the newest
+/// `keep_runs` of them. `reason` is how that run ended, e.g. `exit-1` or
+/// `signal-9`; the time is that of its last write.
+pub fn preserve_previous_run(path: &Path, reason: &str, keep_runs: usize) {
+    recover_stderr(path);
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return;
+    };
+    let (Some(dir), Some(stem)) = (path.parent(), path.file_stem().and_then(|s| s.to_str()))
+    else {
+        return;
+    };
+    if keep_runs == 0 {
+        remove_run(path);
+        return;
+    }
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .map(|elapsed| elapsed.as_secs() as libc::time_t)
+        .unwrap_or(0);
+    let time: String = crate::monitor::format_timestamp(modified)
+        .chars()
+        .filter(|c| *c != '-' && *c != ':')
+        .collect();
+
+    let mut target = dir.join(format!("{stem}.{time}.{reason}.log"));
+    let mut attempt = 1;
+    while target.exists() {
+        attempt += 1;
+        target = dir.join(format!("{stem}.{time}.{reason}-{attempt}.log"));
+    }
+    let _ = std::fs::rename(path, &target);
+    for segment in 1.. {
+        let from = segment_path(path, segment);
+        if !from.exists() {
+            break;
+        }
+        let _ = std::fs::rename(from, segment_path(&target, segment));
+    }
+
+    let mut runs: Vec<(SystemTime, PathBuf)> = previous_runs(path)
+        .into_iter()
+        .filter_map(|run| {
+            let modified = std::fs::metadata(&run).and_then(|m| m.modified()).ok()?;
+            Some((modified, run))
+        })
+        .collect();
+    runs.sort_by(|left, right| right.cmp(left));
+    for (_, run) in runs.into_iter().skip(keep_runs) {
+        remove_run(&run);
+    }
+}
+
+/// Logs kept from earlier runs of the child logging to `path`, in no
+/// particular order.
+pub fn previous_runs(path: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(stem)) = (path.parent(), path.file_stem().and_then(|s| s.to_str()))
+    else {
+        return Vec::new();
+    };
+    let prefix = format!("{stem}.");
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| {
+            name.strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(".log"))
+                .is_some_and(|middle| !middle.is_empty())
+        })
+        .map(|name| dir.join(name))
+        .collect()
+}
+
+fn remove_run(path: &Path) {
+    let _ = std::fs::remove_file(path);
+    for segment in 1.. {
+        if std::fs::remove_file(segment_path(path, segment)).is_err() {
+            break;
+        }
+    }
+}
+
+/// How headless runs repeat their children's output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// `[name] line`, each stream on spm's matching stream.
+    #[default]
+    Text,
+    /// One JSON record per line, on stdout unless `--output` says otherwise.
+    Json,
+}
+
+/// Where headless child output goes: `stdout`, `stderr` or a file path.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputTarget {
+    Stdout,
+    Stderr,
+    File(PathBuf),
+}
+
+impl OutputTarget {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "" => Err("expected stdout, stderr or a file path".to_string()),
+            "stdout" | "-" => Ok(Self::Stdout),
+            "stderr" => Ok(Self::Stderr),
+            path => Ok(Self::File(PathBuf::from(path))),
+        }
+    }
+}
+
+struct OutputEcho {
+    format: OutputFormat,
+    file: Option<std::sync::Mutex<File>>,
+    target: Option<OutputTarget>,
+}
+
+static OUTPUT_ECHO: std::sync::OnceLock<OutputEcho> = std::sync::OnceLock::new();
+
+/// Choose the format and sink of headless child output, once, before any
+/// child is spawned. Without a target, text keeps stdout and stderr apart
+/// and JSON goes to stdout, away from the events on stderr.
+pub fn set_output_echo(format: OutputFormat, target: Option<OutputTarget>) -> Result<(), String> {
+    let file = match target {
+        Some(OutputTarget::File(ref path)) => Some(std::sync::Mutex::new(
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Failed to open {}: {e}", path.display()))?,
+        )),
+        _ => None,
+    };
+    let _ = OUTPUT_ECHO.set(OutputEcho {
+        format,
+        file,
+        target,
+    });
+    Ok(())
+}
+
+/// A line a child wrote, as headless spm repeats it.
+pub struct EchoedLine<'a> {
+    pub index: usize,
+    pub name: &'a str,
+    pub pid: u32,
+    pub is_stderr: bool,
+    pub line: &'a str,
+}
+
+pub fn echo_line(echoed: &EchoedLine) {
+    let echo = OUTPUT_ECHO.get();
+    let format = echo.map(|echo| echo.format).unwrap_or_default();
+    let target = echo.and_then(|echo| echo.target.as_ref());
+    let text = match format {
+        OutputFormat::Text => format!("[{}] {}\n", echoed.name, echoed.line),
+        OutputFormat::Json => format!("{}\n", line_to_json(echoed)),
+    };
+    // One write per line, so lines of different children never interleave.
+    match (target, format) {
+        (Some(OutputTarget::File(_)), _) => {
+            if let Some(Ok(mut file)) = echo.and_then(|echo| echo.file.as_ref()).map(|f| f.lock())
+            {
+                let _ = file.write_all(text.as_bytes());
+            }
+        }
+        (Some(OutputTarget::Stderr), _) => {
+            let _ = std::io::stderr().lock().write_all(text.as_bytes());
+        }
+        (None, OutputFormat::Text) if echoed.is_stderr => {
+            let _ = std::io::stderr().lock().write_all(text.as_bytes());
+        }
+        _ => {
+            let _ = std::io::stdout().lock().write_all(text.as_bytes());
+        }
+    }
+}
+
+fn line_to_json(echoed: &EchoedLine) -> String {
+    let level = detect_level(echoed.line)
+        .map(|level| format!("\"{level}\""))
+        .unwrap_or("null".into());
+    format!(
+        "{{\"ts\":\"{}\",\"index\":{},\"name\":\"{}\",\"pid\":{},\"stream\":\"{}\",\"level\":{},\"line\":\"{}\"}}",
+        crate::monitor::chrono_like_timestamp(),
+        echoed.index,
+        crate::monitor::escape_json(echoed.name),
+        echoed.pid,
+        if echoed.is_stderr { "stderr" } else { "stdout" },
+        level,
+        crate::monitor::escape_json(echoed.line)
+    )
+}
+
+/// The level of a log line in one of the common shapes: a JSON object with
+/// a `level`/`severity` field, logfmt `level=...`, or an upper-case level word
+/// among the first few tokens (`INFO`, `[WARN]`, `ERROR:root:...`).
+pub fn detect_level(line: &str) -> Option<&'static str> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with('{') {
+        for key in ["\"level\"", "\"severity\"", "\"lvl\"", "\"levelname\""] {
+            if let Some(at) = trimmed.find(key) {
+                let value = trimmed[at + key.len()..]
+                    .trim_start()
+                    .strip_prefix(':')?
+                    .trim_start()
+                    .strip_prefix('"')?;
+                let end = value.find('"')?;
+                return normalize_level(&value[..end]);
+            }
+        }
+        return None;
+    }
+    for token in trimmed.split_whitespace().take(4) {
+        if let Some(value) = token
+            .strip_prefix("level=")
+            .or_else(|| token.strip_prefix("lvl="))
+        {
+            return normalize_level(value.trim_matches('"'));
+        }
+        let word = token
+            .trim_matches(|c: char| matches!(c, '[' | ']' | '(' | ')' | '<' | '>' | ','))
+            .split(':')
+            .next()
+            .unwrap_or("");
+        // Only shouted words count: "error" in a sentence is not a level.
+        if word.len() > 2 && word.chars().all(|c| c.is_ascii_uppercase()) {
+            if let Some(level) = normalize_level(word) {
+                return Some(level);
+            }
+        }
+    }
+    None
+}
+
+fn normalize_level(word: &str) -> Option<&'static str> {
+    match word.to_ascii_lowercase().as_str() {
+        "trace" => Some("trace"),
+        "debug" | "dbug" => Some("debug"),
+        "info" | "information" | "notice" => Some("info"),
+        "warn" | "warning" => Some("warn"),
+        "error" | "err" | "eror" => Some("error"),
+        "fatal" | "critical" | "crit" | "panic" | "emerg" | "alert" => Some("fatal"),
+        _ => None,
+    }
+}
@@ -0,0 +1,294 @@
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crossterm::event::KeyCode;
+
+use crate::supervisor::{OutputLine, OutputStream, OutputTail, SPM_NOTE_PREFIX};
+
+/// Lines the pane holds, like a child's output tail; older ones are dropped.
+const VIEW_LINES: usize = 5000;
+
+/// Where the log pane gets a child's output from.
+#[derive(Clone, Debug)]
+pub enum LogSource {
+    /// Output of a child this spm runs, as its readers see it. Lines keep
+    /// their stream; only those pushed since the last refresh are copied.
+    Tail { tail: OutputTail, pushed: u64 },
+    /// The log file of a child another supervisor runs, re-read whenever it
+    /// changes. Every line counts as stdout.
+    File {
+        path: PathBuf,
+        seen: Option<(u64, SystemTime)>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamFilter {
+    All,
+    Stdout,
+    Stderr,
+}
+
+impl StreamFilter {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Self::All => Self::Stdout,
+            Self::Stdout => Self::Stderr,
+            Self::Stderr => Self::All,
+        }
+    }
+
+    /// spm's own notes show whatever the filter.
+    fn allows(&self, line: &OutputLine) -> bool {
+        match self {
+            Self::All => true,
+            Self::Stdout => line.stream != OutputStream::Stderr,
+            Self::Stderr => line.stream != OutputStream::Stdout,
+        }
+    }
+}
+
+/// The log pane of one managed child.
+#[derive(Clone, Debug)]
+pub struct LogView {
+    pub title: String,
+    pub source: LogSource,
+    pub lines: Vec<OutputLine>,
+    pub filter: StreamFilter,
+    /// Keep the newest line in view as output arrives.
+    pub follow: bool,
+    /// First line shown, counted among the lines the filter lets through.
+    pub scroll: usize,
+    pub search: String,
+    /// The search is being typed.
+    pub search_input: bool,
+    /// Line of the match last jumped to, counted like `scroll`.
+    pub current_match: Option<usize>,
+}
+
+impl LogView {
+    pub fn new(title: String, source: LogSource) -> Self {
+        let mut view = Self {
+            title,
+            source,
+            lines: Vec::new(),
+            filter: StreamFilter::All,
+            follow: true,
+            scroll: 0,
+            search: String::new(),
+            search_input: false,
+            current_match: None,
+        };
+        view.refresh();
+        view
+    }
+
+    pub fn refresh(&mut self) {
+        match self.source {
+            LogSource::Tail {
+                ref tail,
+                ref mut pushed,
+            } => {
+                let Ok(tail) = tail.lock() else {
+                    return;
+                };
+                let new = (tail.pushed - *pushed).min(tail.lines.len() as u64) as usize;
+                *pushed = tail.pushed;
+                if new == 0 {
+                    return;
+                }
+                self.lines
+                    .extend(tail.lines.iter().skip(tail.lines.len() - new).cloned());
+                let dropped = self.lines.len().saturating_sub(VIEW_LINES);
+                self.lines.drain(..dropped);
+            }
+            LogSource::File {
+                ref path,
+                ref mut seen,
+            } => {
+                let Ok(metadata) = std::fs::metadata(path) else {
+                    return;
+                };
+                let stamp = metadata
+                    .modified()
+                    .ok()
+                    .map(|modified| (metadata.len(), modified));
+                if stamp.is_some() && stamp == *seen {
+                    return;
+                }
+                *seen = stamp;
+                let content = std::fs::read(path).unwrap_or_default();
+                let content = String::from_utf8_lossy(&content);
+                let lines: Vec<&str> = content.lines().collect();
+                self.lines = lines[lines.len().saturating_sub(VIEW_LINES)..]
+                    .iter()
+                    .map(|line| OutputLine {
+                        stream: if is_spm_note(line) {
+                            OutputStream::Spm
+                        } else {
+                            OutputStream::Stdout
+                        },
+                        text: line.to_string(),
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    pub fn visible(&self) -> Vec<&OutputLine> {
+        self.lines
+            .iter()
+            .filter(|line| self.filter.allows(line))
+            .collect()
+    }
+
+    /// First line shown on a page of `page` lines.
+    pub fn top(&self, page: usize) -> usize {
+        let last_top = self.visible().len().saturating_sub(page);
+        if self.follow {
+            last_top
+        } else {
+            self.scroll.min(last_top)
+        }
+    }
+
+    /// Returns false once the pane should close.
+    pub fn handle_key(&mut self, code: KeyCode, page: usize) -> bool {
+        if self.search_input {
+            match code {
+                KeyCode::Char(c) => self.search.push(c),
+                KeyCode::Backspace => {
+                    self.search.pop();
+                }
+                KeyCode::Enter => {
+                    self.search_input = false;
+                    self.current_match = None;
+                    self.next_match(page, true);
+                }
+                KeyCode::Esc => {
+                    self.search_input = false;
+                    self.search.clear();
+                    self.current_match = None;
+                }
+                _ => {}
+            }
+            return true;
+        }
+        match code {
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('l') => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1, page),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1, page),
+            KeyCode::PageUp => self.scroll_by(-(page as isize), page),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.scroll_by(page as isize, page),
+            KeyCode::Home | KeyCode::Char('g') => {
+                self.follow = false;
+                self.scroll = 0;
+            }
+            KeyCode::End | KeyCode::Char('G') => self.follow = true,
+            KeyCode::Char('f') => {
+                if !self.follow {
+                    self.follow = true;
+                } else {
+                    self.scroll = self.top(page);
+                    self.follow = false;
+                }
+            }
+            KeyCode::Char('/') => {
+                self.search_input = true;
+                self.search.clear();
+            }
+            KeyCode::Char('n') => self.next_match(page, true),
+            KeyCode::Char('N') => self.next_match(page, false),
+            KeyCode::Char('s') => {
+                self.filter = self.filter.next();
+                self.current_match = None;
+            }
+            KeyCode::Char('K') => self.jump_to_last_kill(page),
+            _ => {}
+        }
+        true
+    }
+
+    fn scroll_by(&mut self, delta: isize, page: usize) {
+        let last_top = self.visible().len().saturating_sub(page);
+        let top = (self.top(page) as isize + delta).clamp(0, last_top as isize) as usize;
+        self.scroll = top;
+        // Paging down onto the last page picks following up again.
+        self.follow = delta > 0 && top == last_top;
+    }
+
+    /// Show `line` a third of the way down the page.
+    fn show_line(&mut self, line: usize, page: usize) {
+        self.follow = false;
+        self.scroll = line.saturating_sub(page / 3);
+    }
+
+    fn next_match(&mut self, page: usize, forward: bool) {
+        if self.search.is_empty() {
+            return;
+        }
+        let needle = self.search.to_lowercase();
+        let matches: Vec<usize> = self
+            .visible()
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.text.to_lowercase().contains(&needle))
+            .map(|(position, _)| position)
+            .collect();
+        let (Some(&first), Some(&last)) = (matches.first(), matches.last()) else {
+            self.current_match = None;
+            return;
+        };
+        let from = self.current_match.unwrap_or_else(|| self.top(page));
+        let found = if forward {
+            let after = if self.current_match.is_some() {
+                from + 1
+            } else {
+                from
+            };
+            matches
+                .iter()
+                .copied()
+                .find(|position| *position >= after)
+                .unwrap_or(first)
+        } else {
+            matches
+                .iter()
+                .copied()
+                .rev()
+                .find(|position| *position < from)
+                .unwrap_or(last)
+        };
+        self.current_match = Some(found);
+        self.show_line(found, page);
+    }
+
+    /// Jump to the note spm left when the guard or a health check last
+    /// killed the child.
+    fn jump_to_last_kill(&mut self, page: usize) {
+        let last = self
+            .visible()
+            .iter()
+            .rposition(|line| line.stream == OutputStream::Spm);
+        if let Some(position) = last {
+            self.current_match = Some(position);
+            self.show_line(position, page);
+        }
+    }
+}
+
+/// A note spm wrote into the log, possibly after a timestamp.
+fn is_spm_note(line: &str) -> bool {
+    line.starts_with(SPM_NOTE_PREFIX)
+        || line
+            .split_once(' ')
+            .is_some_and(|(_, rest)| rest.starts_with(SPM_NOTE_PREFIX))
+}
@@ -1,11 +1,16 @@
 mod app;
 mod cgroup;
+mod config;
+mod control;
 mod guard;
 mod health;
+mod logs;
+mod logview;
 mod monitor;
 mod policy;
 mod proc;
 mod process;
+mod profile;
 mod recording;
 mod replay;
 mod supervisor;
@@ -50,33 +55,213 @@ struct Cli {
 #[derive(Subcommand, Debug, Clone)]
 enum Commands {
     /// Launch and supervise processes
-    Run {
-        /// Commands to run (quoted strings)
+    Run(Box<RunArgs>),
+    /// Supervise already-running processes
+    Adopt {
+        /// PIDs to take over
         #[arg(required = true)]
-        commands: Vec<String>,
+        pids: Vec<u32>,
 
-        /// Run without TUI, emit JSON events
+        /// Supervisor pid to hand them to (default: the only running supervisor, or a new one)
+        #[arg(long)]
+        to: Option<u32>,
+
+        /// When starting a new supervisor, run it without TUI
         #[arg(long)]
         headless: bool,
+    },
+    /// Run one command and report the memory, CPU and disk I/O of its process tree
+    Profile {
+        /// Command to profile: one shell string, or a program and its arguments
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
 
-        /// Pod memory % to trigger kill (default: 75)
-        #[arg(long, env = "SPM_GUARD_KILL_THRESHOLD", default_value = "75")]
-        kill_threshold: u8,
+        /// Seconds between samples
+        #[arg(long, default_value_t = 1.0)]
+        interval: f64,
 
-        /// Consecutive seconds above threshold before kill
-        #[arg(long, env = "SPM_GUARD_GRACE_TICKS", default_value = "3")]
-        grace_ticks: u8,
+        /// Print the summary as JSON
+        #[arg(long)]
+        json: bool,
 
-        /// Max restarts before marking Failed
-        #[arg(long, env = "SPM_GUARD_MAX_RESTARTS", default_value = "10")]
-        max_restarts: u32,
+        /// Write the summary to this file instead of stderr
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+    /// Control a running supervisor over its socket
+    Ctl {
+        /// Supervisor pid (default: the only running supervisor)
+        #[arg(long)]
+        pid: Option<u32>,
 
-        /// Path for JSON event log file
-        #[arg(long, env = "SPM_GUARD_LOG")]
-        log: Option<PathBuf>,
+        #[command(subcommand)]
+        action: CtlAction,
     },
 }
 
+#[derive(clap::Args, Debug, Clone)]
+struct RunArgs {
+    /// Commands to run (quoted strings)
+    #[arg(required_unless_present_any = ["config", "adopt"])]
+    commands: Vec<String>,
+
+    /// Supervisor config file (spm.toml) declaring children and guard settings
+    #[arg(long, short = 'c', env = "SPM_CONFIG")]
+    config: Option<PathBuf>,
+
+    /// Run without TUI, emit JSON events
+    #[arg(long)]
+    headless: bool,
+
+    /// Pod memory % to trigger kill (default: 75)
+    #[arg(long, env = "SPM_GUARD_KILL_THRESHOLD")]
+    kill_threshold: Option<u8>,
+
+    /// Consecutive seconds above threshold before kill (default: 3)
+    #[arg(long, env = "SPM_GUARD_GRACE_TICKS")]
+    grace_ticks: Option<u8>,
+
+    /// Max restarts before marking Failed (default: 10)
+    #[arg(long, env = "SPM_GUARD_MAX_RESTARTS")]
+    max_restarts: Option<u32>,
+
+    /// When children are restarted, unless set per child (default: on-failure)
+    #[arg(long, value_enum, env = "SPM_RESTART")]
+    restart: Option<supervisor::RestartPolicy>,
+
+    /// Count --max-restarts over this many seconds instead of in total
+    #[arg(long, env = "SPM_RESTART_WINDOW")]
+    restart_window: Option<u64>,
+
+    /// First restart delay in seconds, doubled each restart (default: 1)
+    #[arg(long, env = "SPM_BACKOFF_BASE")]
+    backoff_base: Option<f64>,
+
+    /// Longest restart delay in seconds (default: 30)
+    #[arg(long, env = "SPM_BACKOFF_MAX")]
+    backoff_max: Option<f64>,
+
+    /// Randomize each delay by up to this fraction, 0 to 1 (default: 0)
+    #[arg(long, env = "SPM_BACKOFF_JITTER")]
+    backoff_jitter: Option<f64>,
+
+    /// Signal that asks children to stop, unless set per child (default: TERM)
+    #[arg(long, env = "SPM_STOP_SIGNAL")]
+    stop_signal: Option<String>,
+
+    /// Seconds between the stop signal and SIGKILL (default: 3, 5 at shutdown)
+    #[arg(long, env = "SPM_STOP_TIMEOUT")]
+    stop_timeout: Option<f64>,
+
+    /// Directory for child logs, unless set per child (default: /tmp)
+    #[arg(long, env = "SPM_LOG_DIR")]
+    log_dir: Option<PathBuf>,
+
+    /// Roll a child's log over at this size, e.g. 50M; 0 never rolls it (default: 10M)
+    #[arg(long, env = "SPM_LOG_MAX_SIZE")]
+    log_max_size: Option<String>,
+
+    /// Rolled-over segments kept per run (default: 3)
+    #[arg(long, env = "SPM_LOG_MAX_FILES")]
+    log_max_files: Option<usize>,
+
+    /// Logs of earlier runs kept per child (default: 5)
+    #[arg(long, env = "SPM_LOG_KEEP_RUNS")]
+    log_keep_runs: Option<usize>,
+
+    /// Prefix each line of child logs with a UTC timestamp
+    #[arg(long, env = "SPM_LOG_TIMESTAMPS")]
+    log_timestamps: bool,
+
+    /// How headless runs repeat child output: text or json (default: text)
+    #[arg(long, value_enum, env = "SPM_OUTPUT_FORMAT")]
+    output_format: Option<logs::OutputFormat>,
+
+    /// Where headless child output goes: stdout, stderr or a file path
+    #[arg(long, value_parser = logs::OutputTarget::parse, env = "SPM_OUTPUT")]
+    output: Option<logs::OutputTarget>,
+
+    /// Pod memory figure the guard watches (default: usage)
+    #[arg(long, value_enum, env = "SPM_GUARD_MEMORY_SIGNAL")]
+    memory_signal: Option<guard::MemorySignal>,
+
+    /// Memory stall % (PSI) that triggers the guard regardless of usage
+    #[arg(long, env = "SPM_GUARD_PRESSURE_THRESHOLD")]
+    pressure_threshold: Option<f64>,
+
+    /// PSI average compared with --pressure-threshold (default: full-avg10)
+    #[arg(long, value_enum, env = "SPM_GUARD_PRESSURE_METRIC")]
+    pressure_metric: Option<guard::PressureMetric>,
+
+    /// Kill when projected pod memory would hit the terminator within N seconds
+    #[arg(long, env = "SPM_GUARD_FORECAST_HORIZON")]
+    forecast_horizon: Option<u64>,
+
+    /// How the guard picks which child to kill (default: largest-uss)
+    #[arg(long, value_enum, env = "SPM_GUARD_VICTIM_STRATEGY")]
+    victim_strategy: Option<policy::VictimStrategy>,
+
+    /// Escalation ladder, comma-separated (default: kill)
+    #[arg(long, value_enum, value_delimiter = ',', env = "SPM_GUARD_ACTIONS")]
+    actions: Option<Vec<guard::GuardStep>>,
+
+    /// Pod memory % below which throttled/frozen children resume (default: kill threshold - 10)
+    #[arg(long, env = "SPM_GUARD_RESUME_THRESHOLD")]
+    resume_threshold: Option<u8>,
+
+    /// Path for JSON event log file
+    #[arg(long, env = "SPM_GUARD_LOG")]
+    log: Option<PathBuf>,
+
+    /// Run as a container entrypoint (PID 1): reap all zombies, forward signals, exit with the primary child's code
+    #[arg(long, env = "SPM_INIT")]
+    init: bool,
+
+    /// Child (name or index) whose exit ends --init mode (default: the first)
+    #[arg(long, env = "SPM_PRIMARY", requires = "init")]
+    primary: Option<String>,
+
+    /// Signal children receive if spm dies (e.g. TERM, KILL)
+    #[arg(long, env = "SPM_PARENT_DEATH_SIGNAL")]
+    parent_death_signal: Option<String>,
+
+    /// What to do with children a crashed spm left running (default: adopt)
+    #[arg(long, value_enum, env = "SPM_LEFTOVERS")]
+    leftovers: Option<supervisor::LeftoverPolicy>,
+
+    /// Already-running PIDs to supervise alongside the commands, comma-separated
+    #[arg(long, value_delimiter = ',', env = "SPM_ADOPT")]
+    adopt: Vec<u32>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum CtlAction {
+    /// Print the supervisor state as JSON
+    List,
+    /// Start a stopped, completed or failed child
+    Start { target: String },
+    /// Stop a child without restarting it
+    Stop { target: String },
+    /// Restart a child immediately
+    Restart { target: String },
+    /// Terminate a child as if it had crashed (restart policy applies)
+    Kill { target: String },
+    /// Send a signal (name or number) to a child's process group
+    Signal { target: String, signal: String },
+    /// Add and start a new child
+    Add {
+        /// Name for the new child
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Command to run: one shell string, or a program and its arguments
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Stop a child and remove it from supervision
+    Remove { target: String },
+}
+
 #[derive(Debug, Clone)]
 struct TuiConfig {
     record: bool,
@@ -150,17 +335,54 @@ fn run_app(
                         let outcome = match confirm.target {
                             KillTarget::Process { pid, .. } => {
                                 match process::terminate_process(pid) {
-                                    Ok(message) => message,
+                                    Ok(kill) => {
+                                        app.watched_kills.push(kill);
+                                        format!("Terminating process {pid} (SIGTERM)")
+                                    }
                                     Err(message) => message,
                                 }
                             }
-                            KillTarget::Managed { pgid, .. } => match pgid {
-                                Some(pgid) => match process::kill_process_group(pgid, false) {
-                                    Ok(message) => message,
-                                    Err(message) => message,
-                                },
-                                None => "Managed process missing pgid".to_string(),
-                            },
+                            KillTarget::Managed {
+                                owner: Some(owner), ..
+                            } => app.send_to_owner(
+                                owner,
+                                control::ControlRequest::Kill(owner.index.to_string()),
+                            ),
+                            KillTarget::Managed {
+                                pid, pgid, cgroup, ..
+                            } => {
+                                let stop = process::StopSignal::default();
+                                let key = pid.or(pgid).unwrap_or(0);
+                                match (cgroup, pgid) {
+                                    (Some(cgroup), _) => {
+                                        let kill = process::kill_in_background(
+                                            key,
+                                            stop,
+                                            false,
+                                            move |progress| {
+                                                process::kill_cgroup(&cgroup, stop, false, progress)
+                                            },
+                                        );
+                                        app.watched_kills.push(kill);
+                                        format!("Terminating process {key} (SIGTERM)")
+                                    }
+                                    (None, Some(pgid)) => {
+                                        let kill = process::kill_in_background(
+                                            key,
+                                            stop,
+                                            false,
+                                            move |progress| {
+                                                process::kill_process_group(
+                                                    pgid, stop, false, progress,
+                                                )
+                                            },
+                                        );
+                                        app.watched_kills.push(kill);
+                                        format!("Terminating process {key} (SIGTERM)")
+                                    }
+                                    (None, None) => "Managed process missing pgid".to_string(),
+                                }
+                            }
                         };
                         app.set_status_message(outcome);
                         app.confirm_kill = None;
@@ -172,6 +394,10 @@ fn run_app(
                 }
             } else if app.show_cmdline.is_some() {
                 app.show_cmdline = None;
+            } else if let Some(view) = app.log_view.as_mut() {
+                if !view.handle_key(key_event.code, ui::log_page_height(terminal.size()?)) {
+                    app.log_view = None;
+                }
             } else {
                 let mut recording_to_load: Option<String> = None;
                 let mut recording_to_delete: Option<String> = None;
@@ -316,30 +542,82 @@ fn run_app(
                                         selected: 0,
                                     });
                                 }
-                                KeyCode::Up => {
-                                    if !app.processes.is_empty() {
-                                        app.view_state.selected =
-                                            app.view_state.selected.saturating_sub(1);
+                                KeyCode::Tab | KeyCode::BackTab => {
+                                    if app.supervisor_mode && !app.managed_children.is_empty() {
+                                        app.focus = match app.focus {
+                                            app::FocusPane::Processes => app::FocusPane::Managed,
+                                            app::FocusPane::Managed => app::FocusPane::Processes,
+                                        };
                                     }
                                 }
-                                KeyCode::Down => {
-                                    if !app.processes.is_empty() {
-                                        let max_index = app.processes.len().saturating_sub(1);
-                                        app.view_state.selected =
-                                            (app.view_state.selected + 1).min(max_index);
+                                KeyCode::Up => match app.focus {
+                                    app::FocusPane::Processes => {
+                                        if !app.processes.is_empty() {
+                                            app.view_state.selected =
+                                                app.view_state.selected.saturating_sub(1);
+                                        }
                                     }
-                                }
+                                    app::FocusPane::Managed => {
+                                        app.selected_managed =
+                                            app.selected_managed.saturating_sub(1);
+                                    }
+                                },
+                                KeyCode::Down => match app.focus {
+                                    app::FocusPane::Processes => {
+                                        if !app.processes.is_empty() {
+                                            let max_index = app.processes.len().saturating_sub(1);
+                                            app.view_state.selected =
+                                                (app.view_state.selected + 1).min(max_index);
+                                        }
+                                    }
+                                    app::FocusPane::Managed => {
+                                        if !app.managed_children.is_empty() {
+                                            let max_index =
+                                                app.managed_children.len().saturating_sub(1);
+                                            app.selected_managed =
+                                                (app.selected_managed + 1).min(max_index);
+                                        }
+                                    }
+                                },
                                 KeyCode::Char('k') => {
-                                    if let Some(process) = app.selected_process() {
+                                    let managed_target = match app.focus {
+                                        app::FocusPane::Managed => {
+                                            app.managed_children.get(app.selected_managed)
+                                        }
+                                        app::FocusPane::Processes => {
+                                            app.selected_process().and_then(|process| {
+                                                app.managed_children
+                                                    .iter()
+                                                    .find(|child| child.pid == Some(process.pid))
+                                            })
+                                        }
+                                    };
+
+                                    if let Some(child) = managed_target {
                                         app.confirm_kill = Some(KillConfirmation {
-                                            target: KillTarget::Process {
-                                                pid: process.pid,
-                                                name: process.name.clone(),
-                                                is_system: process.is_system,
+                                            target: KillTarget::Managed {
+                                                index: child.index,
+                                                command: child.command.clone(),
+                                                pid: child.pid,
+                                                pgid: child.pgid,
+                                                cgroup: child.cgroup.clone(),
+                                                owner: child.owner,
                                             },
                                         });
-                                    } else {
-                                        app.set_status_message("No process selected".to_string());
+                                    } else if app.focus == app::FocusPane::Processes {
+                                        if let Some(process) = app.selected_process() {
+                                            app.confirm_kill = Some(KillConfirmation {
+                                                target: KillTarget::Process {
+                                                    pid: process.pid,
+                                                    name: process.name.clone(),
+                                                    is_system: process.is_system,
+                                                },
+                                            });
+                                        } else {
+                                            app.set_status_message(
+                                                "No process selected".to_string(),
+                                            );
+                                        }
                                     }
                                 }
                                 KeyCode::Char('w') => {
@@ -349,9 +627,26 @@ fn run_app(
                                     app.view_state.sort_column =
                                         next_sort_column(app.view_state.sort_column);
                                 }
-                                KeyCode::Char('S') | KeyCode::Char('r') => {
+                                KeyCode::Char('S') => {
                                     app.view_state.sort_ascending = !app.view_state.sort_ascending;
                                 }
+                                KeyCode::Char('r') => {
+                                    if app.focus == app::FocusPane::Managed {
+                                        app.request_from_owner(control::ControlRequest::Restart);
+                                    } else {
+                                        app.view_state.sort_ascending =
+                                            !app.view_state.sort_ascending;
+                                    }
+                                }
+                                KeyCode::Char('x') if app.focus == app::FocusPane::Managed => {
+                                    app.request_from_owner(control::ControlRequest::Stop);
+                                }
+                                KeyCode::Char('l') if app.focus == app::FocusPane::Managed => {
+                                    app.open_log_view();
+                                }
+                                KeyCode::Char('a') if app.focus == app::FocusPane::Processes => {
+                                    app.request_adopt();
+                                }
                                 _ => {}
                             }
                         }
@@ -427,6 +722,11 @@ fn run_app(
             }
         }
 
+        app.take_owner_replies();
+        app.take_kill_outcomes();
+        if let Some(view) = app.log_view.as_mut() {
+            view.refresh();
+        }
         terminal.draw(|frame| ui::draw(frame, &app))?;
     }
 
@@ -434,7 +734,10 @@ fn run_app(
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+    run_cli(Cli::parse())
+}
+
+fn run_cli(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     let config = TuiConfig {
         record: cli.record,
         replay: cli.replay.clone(),
@@ -442,22 +745,375 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
     match cli.command {
         None => run_tui(config),
-        Some(Commands::Run {
-            commands,
-            headless,
-            kill_threshold,
-            grace_ticks,
-            max_restarts,
-            log,
-        }) => run_supervisor(
-            commands,
-            headless,
-            kill_threshold,
-            grace_ticks,
-            max_restarts,
-            log,
-            config.dark_mode,
-        ),
+        Some(Commands::Run(args)) => {
+            let RunArgs {
+                commands,
+                config: config_path,
+                headless,
+                kill_threshold,
+                grace_ticks,
+                max_restarts,
+                restart,
+                restart_window,
+                backoff_base,
+                backoff_max,
+                backoff_jitter,
+                stop_signal,
+                stop_timeout,
+                log_dir,
+                log_max_size,
+                log_max_files,
+                log_keep_runs,
+                log_timestamps,
+                output_format,
+                output,
+                memory_signal,
+                pressure_threshold,
+                pressure_metric,
+                forecast_horizon,
+                victim_strategy,
+                actions,
+                resume_threshold,
+                log,
+                init,
+                primary,
+                parent_death_signal,
+                leftovers,
+                adopt,
+            } = *args;
+            let file = match config_path {
+                Some(path) => match config::load(&path) {
+                    Ok(file) => file,
+                    Err(error) => {
+                        eprintln!("[spm] Error: {error}");
+                        std::process::exit(2);
+                    }
+                },
+                None => config::SupervisorConfig::default(),
+            };
+
+            let kill_threshold = kill_threshold.or(file.guard.kill_threshold).unwrap_or(75);
+            let mut actions = actions
+                .or(file.guard.actions)
+                .unwrap_or_else(|| vec![guard::GuardStep::Kill]);
+            if actions.last() != Some(&guard::GuardStep::Kill) {
+                actions.push(guard::GuardStep::Kill);
+            }
+            let guard_config = guard::GuardConfig {
+                kill_threshold_percent: kill_threshold,
+                grace_ticks: grace_ticks.or(file.guard.grace_ticks).unwrap_or(3),
+                max_restarts: max_restarts.or(file.guard.max_restarts).unwrap_or(10),
+                enabled: file.guard.enabled.unwrap_or(true),
+                memory_signal: memory_signal
+                    .or(file.guard.memory_signal)
+                    .unwrap_or_default(),
+                pressure_threshold: pressure_threshold.or(file.guard.pressure_threshold),
+                pressure_metric: pressure_metric
+                    .or(file.guard.pressure_metric)
+                    .unwrap_or_default(),
+                forecast_horizon: forecast_horizon
+                    .or(file.guard.forecast_horizon_secs)
+                    .filter(|secs| *secs > 0)
+                    .map(Duration::from_secs),
+                actions,
+                resume_threshold_percent: resume_threshold
+                    .or(file.guard.resume_threshold)
+                    .unwrap_or(kill_threshold.saturating_sub(10)),
+                ..guard::GuardConfig::default()
+            };
+
+            let mut specs: Vec<supervisor::ChildSpec> = file
+                .children
+                .into_iter()
+                .map(config::ChildConfig::into_spec)
+                .collect();
+            specs.extend(
+                commands
+                    .into_iter()
+                    .map(supervisor::ChildSpec::from_command),
+            );
+            for pid in adopt {
+                match supervisor::existing_process_spec(pid) {
+                    Ok(spec) => specs.push(spec),
+                    Err(error) => {
+                        eprintln!("[spm] Error: {error}");
+                        std::process::exit(2);
+                    }
+                }
+            }
+
+            let parent_death_signal = match parent_death_signal {
+                Some(signal) => match control::parse_signal(&signal) {
+                    Some(signal) => Some(signal),
+                    None => {
+                        eprintln!("[spm] Error: unknown signal '{signal}'");
+                        std::process::exit(2);
+                    }
+                },
+                None => None,
+            };
+            if let Err(error) = config::validate_backoff(backoff_base, backoff_max, backoff_jitter)
+            {
+                eprintln!("[spm] Error: {error}");
+                std::process::exit(2);
+            }
+            let stop_signal = match stop_signal {
+                Some(signal) => match control::parse_signal(&signal) {
+                    Some(signal) => Some(signal),
+                    None => {
+                        eprintln!("[spm] Error: unknown signal '{signal}'");
+                        std::process::exit(2);
+                    }
+                },
+                None => None,
+            };
+            if let Err(error) = config::validate_stop_timeout(stop_timeout) {
+                eprintln!("[spm] Error: --stop-timeout {error}");
+                std::process::exit(2);
+            }
+            let log_max_bytes = match log_max_size {
+                Some(size) => match config::parse_memory_size(&size) {
+                    Ok(bytes) => Some(bytes),
+                    Err(error) => {
+                        eprintln!("[spm] Error: --log-max-size: {error}");
+                        std::process::exit(2);
+                    }
+                },
+                None => None,
+            };
+            if let Err(error) = logs::set_output_echo(output_format.unwrap_or_default(), output) {
+                eprintln!("[spm] Error: --output: {error}");
+                std::process::exit(2);
+            }
+            if restart_window == Some(0) {
+                eprintln!("[spm] Error: --restart-window must be positive");
+                std::process::exit(2);
+            }
+            for spec in specs.iter_mut() {
+                spec.parent_death_signal = spec.parent_death_signal.or(parent_death_signal);
+                spec.restart = spec.restart.or(restart);
+                spec.restart_window = spec
+                    .restart_window
+                    .or(restart_window.map(Duration::from_secs));
+                spec.backoff_base = spec
+                    .backoff_base
+                    .or(backoff_base.map(Duration::from_secs_f64));
+                spec.backoff_max = spec
+                    .backoff_max
+                    .or(backoff_max.map(Duration::from_secs_f64));
+                spec.backoff_jitter = spec.backoff_jitter.or(backoff_jitter);
+                spec.stop_signal = spec.stop_signal.or(stop_signal);
+                spec.stop_timeout = spec
+                    .stop_timeout
+                    .or(stop_timeout.map(Duration::from_secs_f64));
+                spec.log.dir = spec.log.dir.take().or_else(|| log_dir.clone());
+                spec.log.max_bytes = spec.log.max_bytes.or(log_max_bytes);
+                spec.log.max_files = spec.log.max_files.or(log_max_files);
+                spec.log.keep_runs = spec.log.keep_runs.or(log_keep_runs);
+                spec.log.timestamps = spec.log.timestamps.or(log_timestamps.then_some(true));
+            }
+
+            if let Some(target) = primary {
+                let position = specs.iter().enumerate().position(|(index, spec)| {
+                    spec.name.as_deref() == Some(target.as_str()) || index.to_string() == target
+                });
+                match position {
+                    Some(position) => {
+                        for (index, spec) in specs.iter_mut().enumerate() {
+                            spec.primary = index == position;
+                        }
+                    }
+                    None => {
+                        eprintln!("[spm] Error: no child '{target}' for --primary");
+                        std::process::exit(2);
+                    }
+                }
+            }
+            if init && !specs.iter().any(|spec| spec.primary) {
+                if let Some(first) = specs.first_mut() {
+                    first.primary = true;
+                }
+            }
+
+            let policy = policy::ProtectionPolicy {
+                strategy: victim_strategy
+                    .or(file.guard.victim_strategy)
+                    .unwrap_or_default(),
+                ..policy::ProtectionPolicy::new()
+            };
+
+            if init {
+                monitor::enable_init_mode();
+            }
+            run_supervisor(
+                specs,
+                guard_config,
+                policy,
+                leftovers.unwrap_or_default(),
+                headless || init,
+                log,
+                config.dark_mode,
+            )
+        }
+        Some(Commands::Profile {
+            command,
+            interval,
+            json,
+            output,
+        }) => run_profile(command, interval, json, output),
+        Some(Commands::Ctl { pid, action }) => run_ctl(pid, action),
+        Some(Commands::Adopt { pids, to, headless }) => run_adopt(pids, to, headless, cli.light),
+    }
+}
+
+/// Run one command under the collectors and exit with its status.
+fn run_profile(
+    command: Vec<String>,
+    interval: f64,
+    json: bool,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !(interval > 0.0 && interval.is_finite()) {
+        eprintln!("[spm] Error: --interval must be positive");
+        std::process::exit(2);
+    }
+    let spec = match command.as_slice() {
+        [command] => supervisor::ChildSpec::from_command(command.clone()),
+        argv => supervisor::ChildSpec {
+            command: argv.join(" "),
+            argv: Some(argv.to_vec()),
+            ..supervisor::ChildSpec::default()
+        },
+    };
+    let options = profile::ProfileOptions {
+        interval: Duration::from_secs_f64(interval),
+        json,
+        output,
+    };
+    match profile::run(spec, &options) {
+        Ok(status) => std::process::exit(status),
+        Err(error) => {
+            eprintln!("[spm] Error: {error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Hand `pids` to a running supervisor, or become one for them.
+fn run_adopt(
+    pids: Vec<u32>,
+    to: Option<u32>,
+    headless: bool,
+    light: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let supervisor = match to {
+        Some(pid) => Some(pid),
+        None => match control::discover_supervisors().as_slice() {
+            [] => None,
+            [pid] => Some(*pid),
+            pids => {
+                let pids: Vec<String> = pids.iter().map(|pid| pid.to_string()).collect();
+                eprintln!(
+                    "[spm] Error: several supervisors running ({}), pass --to",
+                    pids.join(", ")
+                );
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let Some(supervisor) = supervisor else {
+        let pids: Vec<String> = pids.iter().map(|pid| pid.to_string()).collect();
+        let mut args = vec!["spm".to_string(), "run".to_string(), "--adopt".to_string()];
+        args.push(pids.join(","));
+        if headless {
+            args.push("--headless".to_string());
+        }
+        if light {
+            args.push("--light".to_string());
+        }
+        return run_cli(Cli::parse_from(args));
+    };
+
+    let mut failed = false;
+    for pid in pids {
+        match control::send_request(supervisor, &control::ControlRequest::Adopt(pid)) {
+            Ok(response) => {
+                println!("{response}");
+                failed |= !response.starts_with("{\"ok\":true");
+            }
+            Err(error) => {
+                eprintln!("[spm] Error: {error}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_ctl(pid: Option<u32>, action: CtlAction) -> Result<(), Box<dyn std::error::Error>> {
+    let request = match action {
+        CtlAction::List => control::ControlRequest::List,
+        CtlAction::Start { target } => control::ControlRequest::Start(target),
+        CtlAction::Stop { target } => control::ControlRequest::Stop(target),
+        CtlAction::Restart { target } => control::ControlRequest::Restart(target),
+        CtlAction::Kill { target } => control::ControlRequest::Kill(target),
+        CtlAction::Signal { target, signal } => match control::parse_signal(&signal) {
+            Some(signal) => control::ControlRequest::Signal(target, signal),
+            None => {
+                eprintln!("[spm] Error: unknown signal '{signal}'");
+                std::process::exit(2);
+            }
+        },
+        CtlAction::Add { name, command } => match command.as_slice() {
+            [command] => control::ControlRequest::Add {
+                name,
+                command: command.clone(),
+                argv: None,
+            },
+            argv => control::ControlRequest::Add {
+                name,
+                command: argv.join(" "),
+                argv: Some(argv.to_vec()),
+            },
+        },
+        CtlAction::Remove { target } => control::ControlRequest::Remove(target),
+    };
+
+    let pid = match pid {
+        Some(pid) => pid,
+        None => match control::discover_supervisors().as_slice() {
+            [pid] => *pid,
+            [] => {
+                eprintln!("[spm] Error: no running supervisor found");
+                std::process::exit(1);
+            }
+            pids => {
+                let pids: Vec<String> = pids.iter().map(|pid| pid.to_string()).collect();
+                eprintln!(
+                    "[spm] Error: several supervisors running ({}), pass --pid",
+                    pids.join(", ")
+                );
+                std::process::exit(1);
+            }
+        },
+    };
+
+    match control::send_request(pid, &request) {
+        Ok(response) => {
+            println!("{response}");
+            if !response.starts_with("{\"ok\":true") {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Err(error) => {
+            eprintln!("[spm] Error: {error}");
+            std::process::exit(1);
+        }
     }
 }
 
@@ -479,35 +1135,30 @@ fn run_tui(config: TuiConfig) -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn run_supervisor(
-    commands: Vec<String>,
+    specs: Vec<supervisor::ChildSpec>,
+    guard_config: guard::GuardConfig,
+    policy: policy::ProtectionPolicy,
+    leftovers: supervisor::LeftoverPolicy,
     headless: bool,
-    kill_threshold: u8,
-    grace_ticks: u8,
-    max_restarts: u32,
     log_path: Option<PathBuf>,
     dark_mode: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let is_tty = unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
     let effective_headless = headless || !is_tty;
     if !effective_headless {
-        println!("Supervisor mode: {} commands", commands.len());
+        println!("Supervisor mode: {} commands", specs.len());
     }
 
-    let config = guard::GuardConfig {
-        kill_threshold_percent: kill_threshold,
-        emergency_threshold_percent: 78,
-        grace_ticks,
-        max_restarts,
-        enabled: true,
-        post_kill_cooldown: std::time::Duration::from_secs(5),
-    };
-    let guard = Arc::new(Mutex::new(guard::Guard::new(config)));
-    let policy = policy::ProtectionPolicy::new();
+    let guard = Arc::new(Mutex::new(guard::Guard::new(guard_config)));
+
+    if let Err(error) = supervisor::become_subreaper() {
+        eprintln!("[spm] Warning: {error}");
+    }
 
-    let commands: Vec<String> = commands
+    let specs: Vec<supervisor::ChildSpec> = specs
         .into_iter()
-        .filter(|cmd| {
-            let trimmed = cmd.trim();
+        .filter(|spec| {
+            let trimmed = spec.command.trim();
             if trimmed.is_empty() {
                 eprintln!("[spm] Warning: skipping empty command");
                 false
@@ -517,32 +1168,89 @@ fn run_supervisor(
         })
         .collect();
 
-    if commands.is_empty() {
+    if specs.is_empty() {
         eprintln!("[spm] Error: no valid commands to run");
         return Ok(());
     }
 
-    let mut children: Vec<supervisor::ManagedChild> = commands
-        .iter()
+    let mut children: Vec<supervisor::ManagedChild> = specs
+        .into_iter()
         .enumerate()
-        .map(|(index, cmd)| supervisor::ManagedChild::new(index, cmd.clone()))
+        .map(|(index, spec)| supervisor::ManagedChild::from_spec(index, spec))
         .collect();
 
     let (tx, rx) = std::sync::mpsc::channel();
 
-    for child in children.iter_mut() {
+    if leftovers != supervisor::LeftoverPolicy::Ignore {
+        for leftover in supervisor::find_leftovers() {
+            let matching = children.iter_mut().find(|child| {
+                child.name == leftover.name && child.state == supervisor::ChildState::Stopped
+            });
+            let action = match matching {
+                Some(child)
+                    if leftovers == supervisor::LeftoverPolicy::Adopt && leftover.leader_alive =>
+                {
+                    supervisor::adopt_leftover(child, &leftover);
+                    "adopted"
+                }
+                _ => {
+                    let (pgid, stop) = (leftover.pgid, process::StopSignal::default());
+                    process::kill_in_background(pgid, stop, false, move |progress| {
+                        process::kill_process_group(pgid, stop, false, progress)
+                    });
+                    "killed"
+                }
+            };
+            let _ = tx.send(monitor::MonitorEvent::Leftover {
+                name: leftover.name,
+                pgid: leftover.pgid,
+                spm_pid: leftover.spm_pid,
+                action,
+            });
+        }
+    }
+
+    for child in children
+        .iter_mut()
+        .filter(|child| child.origin == supervisor::Origin::Existing)
+    {
+        match supervisor::attach_existing(child) {
+            Ok(()) => {
+                let _ = tx.send(monitor::MonitorEvent::Adopt {
+                    index: child.index,
+                    cmd: child.command.clone(),
+                    pid: child.pid.unwrap_or(0),
+                });
+            }
+            Err(error) => {
+                eprintln!("[spm] Failed to adopt '{}': {}", child.command, error);
+                child.state = supervisor::ChildState::Failed;
+            }
+        }
+    }
+
+    // Children with dependencies start from the monitor thread once those
+    // are ready.
+    for child in children.iter_mut().filter(|child| {
+        child.state == supervisor::ChildState::Stopped && !child.spec.depends_on.is_empty()
+    }) {
+        child.state = supervisor::ChildState::Waiting;
+    }
+
+    for child in children
+        .iter_mut()
+        .filter(|child| child.state == supervisor::ChildState::Stopped)
+    {
         match supervisor::spawn_child(child, effective_headless) {
             Ok(spawned) => {
                 if !effective_headless {
                     eprintln!("[spm] Spawned '{}' (PID {})", child.command, spawned.pid);
-                } else {
-                    let name = supervisor::command_name(&child.command);
-                    if let Some(stdout) = spawned.stdout {
-                        supervisor::spawn_output_reader(name.clone(), stdout, false);
-                    }
-                    if let Some(stderr) = spawned.stderr {
-                        supervisor::spawn_output_reader(name, stderr, true);
-                    }
+                }
+                for message in spawned.warnings {
+                    let _ = tx.send(monitor::MonitorEvent::Warning {
+                        index: child.index,
+                        message,
+                    });
                 }
             }
             Err(error) => {
@@ -554,7 +1262,10 @@ fn run_supervisor(
         }
     }
 
-    for child in children.iter() {
+    for child in children
+        .iter()
+        .filter(|child| child.origin == supervisor::Origin::Spawned)
+    {
         if let Some(pid) = child.pid {
             let _ = tx.send(monitor::MonitorEvent::Spawn {
                 index: child.index,
@@ -567,7 +1278,7 @@ fn run_supervisor(
 
     let managed = Arc::new(Mutex::new(children));
 
-    let _monitor = monitor::spawn_monitor_thread(
+    let monitor_thread = monitor::spawn_monitor_thread(
         Arc::clone(&managed),
         Arc::clone(&guard),
         policy,
@@ -576,11 +1287,32 @@ fn run_supervisor(
     );
 
     if effective_headless {
-        run_supervisor_headless(rx, managed, log_path);
+        run_supervisor_headless(rx, Arc::clone(&managed), log_path);
+        if monitor::is_init_mode() {
+            let code = managed
+                .lock()
+                .map(|children| monitor::primary_exit_code(&children))
+                .unwrap_or(1);
+            std::process::exit(code);
+        }
         return Ok(());
     }
 
-    run_supervisor_tui(managed, guard, rx, dark_mode)
+    let result = run_supervisor_tui(Arc::clone(&managed), guard, rx, dark_mode);
+    if monitor::is_shutdown_requested() {
+        // The monitor thread stops the children and cleans up before it ends.
+        let _ = monitor_thread.join();
+    } else {
+        // Every child finished on its own; stop what they left behind, as
+        // the headless loop does.
+        if let Ok(children) = managed.lock() {
+            monitor::shutdown_orphans(&children);
+        }
+        monitor::remove_shared_state();
+        control::remove_socket();
+        cgroup::remove_child_cgroups();
+    }
+    result
 }
 
 fn run_supervisor_tui(
@@ -639,17 +1371,38 @@ fn run_supervisor_app(
                         let outcome = match confirm.target {
                             KillTarget::Process { pid, .. } => {
                                 match process::terminate_process(pid) {
-                                    Ok(message) => message,
+                                    Ok(kill) => {
+                                        app.watched_kills.push(kill);
+                                        format!("Terminating process {pid} (SIGTERM)")
+                                    }
                                     Err(message) => message,
                                 }
                             }
-                            KillTarget::Managed { pgid, .. } => match pgid {
-                                Some(pgid) => match process::kill_process_group(pgid, false) {
-                                    Ok(message) => message,
+                            KillTarget::Managed {
+                                owner: Some(owner), ..
+                            } => app.send_to_owner(
+                                owner,
+                                control::ControlRequest::Kill(owner.index.to_string()),
+                            ),
+                            KillTarget::Managed { index, command, .. } => {
+                                let killed = match managed.lock() {
+                                    Ok(mut children) => match children
+                                        .iter_mut()
+                                        .find(|child| child.index == index)
+                                    {
+                                        Some(child) => monitor::kill_running_child(
+                                            child,
+                                            supervisor::KillOrigin::Tui,
+                                        ),
+                                        None => Err("Managed process not found".to_string()),
+                                    },
+                                    Err(_) => Err("Managed process not found".to_string()),
+                                };
+                                match killed {
+                                    Ok(()) => format!("Stopping '{command}'..."),
                                     Err(message) => message,
-                                },
-                                None => "Managed process missing pgid".to_string(),
-                            },
+                                }
+                            }
                         };
                         app.set_status_message(outcome);
                         app.confirm_kill = None;
@@ -661,8 +1414,10 @@ fn run_supervisor_app(
                 }
             } else if app.show_cmdline.is_some() {
                 app.show_cmdline = None;
-            } else if app.show_log.is_some() {
-                app.show_log = None;
+            } else if let Some(view) = app.log_view.as_mut() {
+                if !view.handle_key(key_event.code, ui::log_page_height(terminal.size()?)) {
+                    app.log_view = None;
+                }
             } else {
                 let mut recording_to_load: Option<String> = None;
                 let mut recording_to_delete: Option<String> = None;
@@ -857,6 +1612,8 @@ fn run_supervisor_app(
                                                     command: child.command.clone(),
                                                     pid: child.pid,
                                                     pgid: child.pgid,
+                                                    cgroup: child.cgroup.clone(),
+                                                    owner: child.owner,
                                                 },
                                             });
                                         }
@@ -873,6 +1630,8 @@ fn run_supervisor_app(
                                                     command: child.command.clone(),
                                                     pid: child.pid,
                                                     pgid: child.pgid,
+                                                    cgroup: child.cgroup.clone(),
+                                                    owner: child.owner,
                                                 },
                                             });
                                         } else {
@@ -900,19 +1659,35 @@ fn run_supervisor_app(
                                 }
                                 KeyCode::Char('r') => {
                                     if app.focus == app::FocusPane::Managed {
-                                        if let Some(child) =
-                                            app.managed_children.get(app.selected_managed)
+                                        if !app.request_from_owner(control::ControlRequest::Restart)
                                         {
-                                            if child.pid.is_some() {
-                                                app.restart_requested = Some(child.index);
-                                                app.set_status_message(format!(
-                                                    "Restarting '{}'...",
-                                                    child.command
-                                                ));
-                                            } else {
-                                                app.set_status_message(
-                                                    "Process not running".to_string(),
-                                                );
+                                            if let Some(child) =
+                                                app.managed_children.get(app.selected_managed)
+                                            {
+                                                if child.origin == supervisor::Origin::Existing {
+                                                    app.set_status_message(format!(
+                                                        "'{}' was adopted and cannot be restarted",
+                                                        child.name
+                                                    ));
+                                                } else if child.state
+                                                    == supervisor::ChildState::Quarantined
+                                                {
+                                                    app.restart_requested = Some(child.index);
+                                                    app.set_status_message(format!(
+                                                        "Resuming '{}'...",
+                                                        child.command
+                                                    ));
+                                                } else if child.pid.is_some() {
+                                                    app.restart_requested = Some(child.index);
+                                                    app.set_status_message(format!(
+                                                        "Restarting '{}'...",
+                                                        child.command
+                                                    ));
+                                                } else {
+                                                    app.set_status_message(
+                                                        "Process not running".to_string(),
+                                                    );
+                                                }
                                             }
                                         }
                                     } else {
@@ -920,39 +1695,32 @@ fn run_supervisor_app(
                                             !app.view_state.sort_ascending;
                                     }
                                 }
-                                KeyCode::Char('l') => {
-                                    if app.focus == app::FocusPane::Managed {
+                                KeyCode::Char('x') if app.focus == app::FocusPane::Managed => {
+                                    if !app.request_from_owner(control::ControlRequest::Stop) {
                                         if let Some(child) =
                                             app.managed_children.get(app.selected_managed)
                                         {
-                                            if let Some(ref log_path) = child.log_path {
-                                                match std::fs::read_to_string(log_path) {
-                                                    Ok(content) => {
-                                                        let lines: Vec<&str> =
-                                                            content.lines().collect();
-                                                        let start = lines.len().saturating_sub(50);
-                                                        let tail = lines[start..].join("\n");
-                                                        app.show_log = Some((
-                                                            format!(
-                                                                "{} — {}",
-                                                                child.command,
-                                                                log_path.display()
-                                                            ),
-                                                            tail,
-                                                        ));
-                                                    }
-                                                    Err(e) => app.set_status_message(format!(
-                                                        "Cannot read log: {e}"
-                                                    )),
-                                                }
-                                            } else {
-                                                app.set_status_message(
-                                                    "No log file (headless mode)".to_string(),
-                                                );
-                                            }
+                                            app.stop_requested = Some(child.index);
+                                            app.set_status_message(format!(
+                                                "Stopping '{}'...",
+                                                child.command
+                                            ));
                                         }
                                     }
                                 }
+                                KeyCode::Char('a') if app.focus == app::FocusPane::Processes => {
+                                    match app.selected_process() {
+                                        Some(process) => app.adopt_requested = Some(process.pid),
+                                        None => {
+                                            app.set_status_message(
+                                                "No process selected".to_string(),
+                                            );
+                                        }
+                                    }
+                                }
+                                KeyCode::Char('l') if app.focus == app::FocusPane::Managed => {
+                                    app.open_log_view();
+                                }
                                 _ => {}
                             }
                         }
@@ -1029,10 +1797,34 @@ fn run_supervisor_app(
                         cmd,
                         pid,
                         pod_percent.round(),
-                        format_bytes(uss)
+                        ui::format_bytes(uss)
+                    );
+                    app.set_status_message_with_duration(message, Duration::from_secs(5));
+                }
+                monitor::MonitorEvent::GuardMitigate {
+                    cmd,
+                    pid,
+                    pod_percent,
+                    step,
+                    ..
+                } => {
+                    app.guard_alert = None;
+                    let verb = match step {
+                        guard::GuardStep::Throttle => "Throttled",
+                        _ => "Froze",
+                    };
+                    let message = format!(
+                        "❄ {} {} (PID {}) — pod at {:.0}%",
+                        verb,
+                        cmd,
+                        pid,
+                        pod_percent.round()
                     );
                     app.set_status_message_with_duration(message, Duration::from_secs(5));
                 }
+                monitor::MonitorEvent::GuardResume { cmd, pid, .. } => {
+                    app.set_status_message(format!("Resumed {} (PID {})", cmd, pid));
+                }
                 monitor::MonitorEvent::GuardExhausted { pod_percent } => {
                     app.guard_alert = Some(GuardAlert::Exhausted {
                         percent: pod_percent,
@@ -1047,6 +1839,31 @@ fn run_supervisor_app(
                     );
                     app.set_status_message_with_duration(message, Duration::from_secs(5));
                 }
+                monitor::MonitorEvent::Quarantined {
+                    cmd,
+                    reason,
+                    bundle,
+                    ..
+                } => {
+                    let details = bundle
+                        .map(|path| format!(" — diagnostics in {}", path.display()))
+                        .unwrap_or_default();
+                    let message = format!("⛔ Quarantined {} ({}){}", cmd, reason, details);
+                    app.set_status_message_with_duration(message, Duration::from_secs(10));
+                }
+                monitor::MonitorEvent::Warning { message, .. } => {
+                    app.set_status_message_with_duration(
+                        format!("⚠ {message}"),
+                        Duration::from_secs(10),
+                    );
+                }
+                monitor::MonitorEvent::KillFinished { outcome, .. } => match outcome {
+                    Ok(message) => app.set_status_message(message),
+                    Err(message) => app.set_status_message_with_duration(
+                        format!("⚠ {message}"),
+                        Duration::from_secs(10),
+                    ),
+                },
                 monitor::MonitorEvent::StateUpdate => {
                     saw_state_update = true;
                 }
@@ -1056,11 +1873,40 @@ fn run_supervisor_app(
 
         if let Some(restart_idx) = app.restart_requested.take() {
             if let Ok(mut children) = managed.lock() {
-                if let Some(child) = children.get_mut(restart_idx) {
-                    if let Some(pgid) = child.pgid {
-                        let _ = crate::process::kill_process_group(pgid, false);
+                if let Some(child) = children
+                    .iter_mut()
+                    .find(|child| child.index == restart_idx)
+                {
+                    if child.state == supervisor::ChildState::Quarantined {
+                        supervisor::resume_quarantined(child);
+                    } else if child.pid.is_some() {
+                        let _ = supervisor::kill_child(child, false);
                         child.state = crate::supervisor::ChildState::Stopping { emergency: false };
+                        child.kill_origin = Some(supervisor::KillOrigin::Tui);
+                    }
+                }
+            }
+        }
+
+        if let Some(pid) = app.adopt_requested.take() {
+            if let Ok(mut children) = managed.lock() {
+                match monitor::adopt_process(&mut children, pid) {
+                    Ok(position) => {
+                        app.set_status_message(format!(
+                            "Adopted '{}' (PID {})",
+                            children[position].command, pid
+                        ));
+                        app.managed_children = children.clone();
                     }
+                    Err(error) => app.set_status_message(error),
+                }
+            }
+        }
+
+        if let Some(stop_idx) = app.stop_requested.take() {
+            if let Ok(mut children) = managed.lock() {
+                if let Some(child) = children.iter_mut().find(|child| child.index == stop_idx) {
+                    monitor::stop_child(child, supervisor::PendingAction::Stop);
                 }
             }
         }
@@ -1118,6 +1964,11 @@ fn run_supervisor_app(
             }
         }
 
+        app.take_owner_replies();
+        app.take_kill_outcomes();
+        if let Some(view) = app.log_view.as_mut() {
+            view.refresh();
+        }
         terminal.draw(|frame| ui::draw(frame, &app))?;
     }
 
@@ -1158,9 +2009,17 @@ fn run_supervisor_headless(
 
         if signal_shutdown {
             monitor::remove_shared_state();
+            control::remove_socket();
+            cgroup::remove_child_cgroups();
             break;
         }
 
+        // Once a shutdown is under way, wait for its SignalShutdown so the
+        // exit events it produces are not lost.
+        if monitor::is_shutdown_requested() {
+            continue;
+        }
+
         if let Ok(children) = managed.lock() {
             if !children.is_empty()
                 && children.iter().all(|child| {
@@ -1170,22 +2029,19 @@ fn run_supervisor_headless(
                     )
                 })
             {
-                let reason = if monitor::is_shutdown_requested() {
-                    "signal"
-                } else {
-                    "all_terminal"
-                };
                 let shutdown = format!(
-                    "{{\"ts\":\"{}\",\"event\":\"shutdown\",\"reason\":\"{}\"}}",
+                    "{{\"ts\":\"{}\",\"event\":\"shutdown\",\"reason\":\"all_terminal\"}}",
                     monitor::chrono_like_timestamp(),
-                    reason,
                 );
                 eprintln!("{}", shutdown);
                 if let Some(ref mut file) = log_file {
                     use std::io::Write;
                     let _ = writeln!(file, "{}", shutdown);
                 }
+                monitor::shutdown_orphans(&children);
                 monitor::remove_shared_state();
+                control::remove_socket();
+                cgroup::remove_child_cgroups();
                 break;
             }
         }
@@ -1193,28 +2049,12 @@ fn run_supervisor_headless(
 }
 
 fn pod_memory_percent(app: &App) -> Option<f64> {
-    let limit = app.pod_memory.cgroup_limit?;
-    if limit == 0 {
-        return None;
-    }
-    Some((app.pod_memory.cgroup_usage as f64 / limit as f64) * 100.0)
-}
-
-fn format_bytes(value: u64) -> String {
-    const KB: f64 = 1024.0;
-    const MB: f64 = 1024.0 * 1024.0;
-    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
-
-    let value_f = value as f64;
-    if value_f >= GB {
-        format!("{:.1} GB", value_f / GB)
-    } else if value_f >= MB {
-        format!("{:.1} MB", value_f / MB)
-    } else if value_f >= KB {
-        format!("{:.1} KB", value_f / KB)
-    } else {
-        format!("{} B", value)
-    }
+    let signal = app
+        .guard
+        .as_ref()
+        .map(|guard| guard.config.memory_signal)
+        .unwrap_or_default();
+    app.pod_memory.percent_for(signal)
 }
 
 fn next_sort_column(current: SortColumn) -> SortColumn {
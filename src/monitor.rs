@@ -1,18 +1,101 @@
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
 use std::thread;
 use std::time::{Duration, Instant};
 
+use crate::control::ControlRequest;
 use crate::guard::KillReason;
-use crate::{cgroup, guard, health, policy, process, supervisor};
+use crate::{cgroup, control, guard, health, policy, process, supervisor};
 
 static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+static INIT_MODE: AtomicBool = AtomicBool::new(false);
+static PRIMARY_EXITED: AtomicBool = AtomicBool::new(false);
+/// Signal children receive at shutdown. In init mode, the one spm got.
+static SHUTDOWN_SIGNAL: AtomicI32 = AtomicI32::new(libc::SIGTERM);
+/// Grace period at shutdown for children without a `stop_timeout`.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Bit `n` set: signal `n` arrived and has not been forwarded yet.
+static PENDING_SIGNALS: AtomicU64 = AtomicU64::new(0);
 
-extern "C" fn signal_handler(_sig: libc::c_int) {
+/// Signals spm passes through to its children in init mode. SIGINT and
+/// SIGTERM shut the supervisor down instead; fault signals, SIGCHLD and
+/// SIGPIPE are about spm itself.
+const FORWARDED_SIGNALS: [libc::c_int; 13] = [
+    libc::SIGHUP,
+    libc::SIGQUIT,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+    libc::SIGALRM,
+    libc::SIGWINCH,
+    libc::SIGCONT,
+    libc::SIGTSTP,
+    libc::SIGTTIN,
+    libc::SIGTTOU,
+    libc::SIGURG,
+    libc::SIGIO,
+    libc::SIGPWR,
+];
+
+extern "C" fn signal_handler(sig: libc::c_int) {
+    if INIT_MODE.load(Ordering::SeqCst) {
+        SHUTDOWN_SIGNAL.store(sig, Ordering::SeqCst);
+    }
     SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
 }
 
+extern "C" fn forward_handler(sig: libc::c_int) {
+    PENDING_SIGNALS.fetch_or(1 << sig, Ordering::SeqCst);
+}
+
+/// Run as a container entrypoint: forward every catchable signal to the
+/// children and shut down once the primary child is done. As PID 1, spm
+/// gets no default signal actions from the kernel, so everything it should
+/// react to needs a handler.
+pub fn enable_init_mode() {
+    INIT_MODE.store(true, Ordering::SeqCst);
+    for signal in FORWARDED_SIGNALS {
+        unsafe {
+            libc::signal(signal, forward_handler as *const () as libc::sighandler_t);
+        }
+    }
+}
+
+pub fn is_init_mode() -> bool {
+    INIT_MODE.load(Ordering::SeqCst)
+}
+
+/// Exit status of the primary child, shell style: its exit code, or 128 +
+/// the signal that killed it.
+pub fn primary_exit_code(children: &[supervisor::ManagedChild]) -> i32 {
+    children
+        .iter()
+        .find(|child| child.spec.primary)
+        .and_then(|child| child.last_exit.as_ref())
+        .map(|exit| match (exit.exit_code, exit.signal) {
+            (Some(code), _) => code,
+            (None, Some(signal)) => 128 + signal,
+            (None, None) => 0,
+        })
+        .unwrap_or(0)
+}
+
+fn forward_pending_signals(children: &[supervisor::ManagedChild], tx: &mpsc::Sender<MonitorEvent>) {
+    let pending = PENDING_SIGNALS.swap(0, Ordering::SeqCst);
+    if pending == 0 {
+        return;
+    }
+    for signal in FORWARDED_SIGNALS {
+        if pending & (1 << signal) == 0 {
+            continue;
+        }
+        for child in children.iter().filter(|child| is_alive(child)) {
+            let _ = supervisor::signal_child(child, signal);
+        }
+        let _ = tx.send(MonitorEvent::SignalForwarded { signal });
+    }
+}
+
 pub fn request_shutdown() {
     SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
 }
@@ -35,8 +118,8 @@ pub enum MonitorEvent {
         pid: u32,
         exit_code: Option<i32>,
         signal: Option<i32>,
-        killed_by_guard: bool,
-        killed_by_health: bool,
+        killed_by: Option<supervisor::KillOrigin>,
+        usage: supervisor::RunUsage,
     },
     GuardWarning {
         pod_percent: f64,
@@ -51,6 +134,19 @@ pub enum MonitorEvent {
         reason: KillReason,
         emergency: bool,
     },
+    GuardMitigate {
+        index: usize,
+        pid: u32,
+        cmd: String,
+        pod_percent: f64,
+        step: guard::GuardStep,
+        reason: KillReason,
+    },
+    GuardResume {
+        index: usize,
+        pid: u32,
+        cmd: String,
+    },
     GuardExhausted {
         pod_percent: f64,
     },
@@ -86,6 +182,45 @@ pub enum MonitorEvent {
         cmd: String,
         restart_count: u32,
     },
+    Stopped {
+        index: usize,
+        cmd: String,
+    },
+    Quarantined {
+        index: usize,
+        cmd: String,
+        reason: String,
+        bundle: Option<PathBuf>,
+    },
+    Orphan {
+        index: usize,
+        cmd: String,
+        pid: u32,
+    },
+    Adopt {
+        index: usize,
+        cmd: String,
+        pid: u32,
+    },
+    SignalForwarded {
+        signal: i32,
+    },
+    Leftover {
+        name: String,
+        pgid: u32,
+        spm_pid: u32,
+        action: &'static str,
+    },
+    /// Something about a child did not go as configured, though it runs.
+    Warning {
+        index: usize,
+        message: String,
+    },
+    /// A background kill of the child is done, with what it reported.
+    KillFinished {
+        index: usize,
+        outcome: Result<String, String>,
+    },
     StateUpdate,
     SignalShutdown,
 }
@@ -114,8 +249,8 @@ pub fn event_to_json(event: &MonitorEvent) -> Option<String> {
             pid,
             exit_code,
             signal,
-            killed_by_guard,
-            killed_by_health,
+            killed_by,
+            usage,
         } => {
             let ec = exit_code
                 .map(|c| format!(",\"exit_code\":{c}"))
@@ -123,16 +258,13 @@ pub fn event_to_json(event: &MonitorEvent) -> Option<String> {
             let sig = signal
                 .map(|s| format!(",\"signal\":{s}"))
                 .unwrap_or_default();
-            let kb = if *killed_by_guard {
-                ",\"killed_by\":\"guard\""
-            } else if *killed_by_health {
-                ",\"killed_by\":\"health\""
-            } else {
-                ""
-            };
+            let kb = killed_by
+                .map(|origin| format!(",\"killed_by\":\"{}\"", origin.label()))
+                .unwrap_or_default();
             Some(format!(
-                "{{\"ts\":\"{ts}\",\"event\":\"exit\",\"index\":{index},\"cmd\":\"{}\",\"pid\":{pid}{ec}{sig}{kb}}}",
-                escape_json(cmd)
+                "{{\"ts\":\"{ts}\",\"event\":\"exit\",\"index\":{index},\"cmd\":\"{}\",\"pid\":{pid}{ec}{sig}{kb},\"usage\":{}}}",
+                escape_json(cmd),
+                usage_to_json(usage)
             ))
         }
         MonitorEvent::GuardWarning {
@@ -150,19 +282,29 @@ pub fn event_to_json(event: &MonitorEvent) -> Option<String> {
             reason,
             emergency,
         } => {
-            let reason_str = match reason {
-                crate::guard::KillReason::ThresholdExceeded { pod_percent: p } => {
-                    format!("threshold_exceeded({:.1}%)", p)
-                }
-                crate::guard::KillReason::HealthCheckFailed => {
-                    "health_check_failed".to_string()
-                }
-            };
+            let reason_str = kill_reason_label(reason);
             Some(format!(
                 "{{\"ts\":\"{ts}\",\"event\":\"guard_kill\",\"index\":{index},\"pid\":{pid},\"cmd\":\"{}\",\"uss\":{uss},\"pod_percent\":{pod_percent:.1},\"reason\":\"{reason_str}\",\"emergency\":{emergency}}}",
                 escape_json(cmd)
             ))
         }
+        MonitorEvent::GuardMitigate {
+            index,
+            pid,
+            cmd,
+            pod_percent,
+            step,
+            reason,
+        } => Some(format!(
+            "{{\"ts\":\"{ts}\",\"event\":\"guard_{}\",\"index\":{index},\"pid\":{pid},\"cmd\":\"{}\",\"pod_percent\":{pod_percent:.1},\"reason\":\"{}\"}}",
+            step.as_str(),
+            escape_json(cmd),
+            kill_reason_label(reason)
+        )),
+        MonitorEvent::GuardResume { index, pid, cmd } => Some(format!(
+            "{{\"ts\":\"{ts}\",\"event\":\"guard_resume\",\"index\":{index},\"pid\":{pid},\"cmd\":\"{}\"}}",
+            escape_json(cmd)
+        )),
         MonitorEvent::GuardExhausted { pod_percent } => Some(format!(
             "{{\"ts\":\"{ts}\",\"event\":\"guard_exhausted\",\"pod_percent\":{pod_percent:.1}}}"
         )),
@@ -214,26 +356,140 @@ pub fn event_to_json(event: &MonitorEvent) -> Option<String> {
             "{{\"ts\":\"{ts}\",\"event\":\"failed\",\"index\":{index},\"cmd\":\"{}\",\"restart_count\":{restart_count}}}",
             escape_json(cmd)
         )),
+        MonitorEvent::Stopped { index, cmd } => Some(format!(
+            "{{\"ts\":\"{ts}\",\"event\":\"stopped\",\"index\":{index},\"cmd\":\"{}\"}}",
+            escape_json(cmd)
+        )),
+        MonitorEvent::Quarantined {
+            index,
+            cmd,
+            reason,
+            bundle,
+        } => {
+            let bundle = bundle
+                .as_ref()
+                .map(|p| format!(",\"bundle\":\"{}\"", escape_json(&p.display().to_string())))
+                .unwrap_or_default();
+            Some(format!(
+                "{{\"ts\":\"{ts}\",\"event\":\"quarantined\",\"index\":{index},\"cmd\":\"{}\",\"reason\":\"{}\"{bundle}}}",
+                escape_json(cmd),
+                escape_json(reason)
+            ))
+        }
+        MonitorEvent::Orphan { index, cmd, pid } => Some(format!(
+            "{{\"ts\":\"{ts}\",\"event\":\"orphan\",\"index\":{index},\"cmd\":\"{}\",\"pid\":{pid}}}",
+            escape_json(cmd)
+        )),
+        MonitorEvent::Adopt { index, cmd, pid } => Some(format!(
+            "{{\"ts\":\"{ts}\",\"event\":\"adopt\",\"index\":{index},\"cmd\":\"{}\",\"pid\":{pid}}}",
+            escape_json(cmd)
+        )),
         MonitorEvent::StateUpdate => None,
-        MonitorEvent::SignalShutdown => Some(format!(
-            "{{\"ts\":\"{ts}\",\"event\":\"shutdown\",\"reason\":\"signal\"}}"
+        MonitorEvent::Leftover {
+            name,
+            pgid,
+            spm_pid,
+            action,
+        } => Some(format!(
+            "{{\"ts\":\"{ts}\",\"event\":\"leftover\",\"name\":\"{}\",\"pgid\":{pgid},\"spm_pid\":{spm_pid},\"action\":\"{action}\"}}",
+            escape_json(name)
+        )),
+        MonitorEvent::Warning { index, message } => Some(format!(
+            "{{\"ts\":\"{ts}\",\"event\":\"warning\",\"index\":{index},\"message\":\"{}\"}}",
+            escape_json(message)
         )),
+        MonitorEvent::KillFinished { index, outcome } => {
+            let (ok, message) = match outcome {
+                Ok(message) => (true, message),
+                Err(message) => (false, message),
+            };
+            Some(format!(
+                "{{\"ts\":\"{ts}\",\"event\":\"kill_finished\",\"index\":{index},\"ok\":{ok},\"message\":\"{}\"}}",
+                escape_json(message)
+            ))
+        }
+        MonitorEvent::SignalForwarded { signal } => Some(format!(
+            "{{\"ts\":\"{ts}\",\"event\":\"signal_forwarded\",\"signal\":{signal}}}"
+        )),
+        MonitorEvent::SignalShutdown => {
+            let reason = if PRIMARY_EXITED.load(Ordering::SeqCst) {
+                "primary_exit"
+            } else {
+                "signal"
+            };
+            Some(format!(
+                "{{\"ts\":\"{ts}\",\"event\":\"shutdown\",\"reason\":\"{reason}\"}}"
+            ))
+        }
+    }
+}
+
+fn kill_reason_label(reason: &KillReason) -> String {
+    match reason {
+        KillReason::ThresholdExceeded { pod_percent: p } => {
+            format!("threshold_exceeded({:.1}%)", p)
+        }
+        KillReason::MemoryPressure { stall_percent } => {
+            format!("memory_pressure({:.1}%)", stall_percent)
+        }
+        KillReason::Forecast { seconds_to_limit } => {
+            format!("forecast({:.1}s)", seconds_to_limit)
+        }
+        KillReason::HealthCheckFailed => "health_check_failed".to_string(),
     }
 }
 
-fn escape_json(s: &str) -> String {
-    s.replace('\\', "\\\\")
-        .replace('"', "\\\"")
-        .replace('\n', "\\n")
-        .replace('\r', "\\r")
+pub fn usage_to_json(usage: &supervisor::RunUsage) -> String {
+    let wall = usage
+        .wall_secs
+        .map(|secs| format!("{secs:.3}"))
+        .unwrap_or("null".into());
+    let kernel = match usage.rusage {
+        Some(rusage) => format!(
+            "\"user_secs\":{:.3},\"sys_secs\":{:.3},\"max_rss\":{},\"minor_faults\":{},\"major_faults\":{},\"voluntary_switches\":{},\"involuntary_switches\":{}",
+            rusage.user_secs,
+            rusage.sys_secs,
+            rusage.max_rss,
+            rusage.minor_faults,
+            rusage.major_faults,
+            rusage.voluntary_switches,
+            rusage.involuntary_switches
+        ),
+        None => "\"user_secs\":null,\"sys_secs\":null,\"max_rss\":null,\"minor_faults\":null,\"major_faults\":null,\"voluntary_switches\":null,\"involuntary_switches\":null".to_string(),
+    };
+    format!(
+        "{{{kernel},\"wall_secs\":{wall},\"peak_uss\":{}}}",
+        usage.peak_uss
+    )
+}
+
+pub fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
 }
 
 pub fn chrono_like_timestamp() -> String {
+    let mut tv: libc::timeval = unsafe { std::mem::zeroed() };
+    unsafe { libc::gettimeofday(&mut tv, std::ptr::null_mut()) };
+    format_timestamp(tv.tv_sec)
+}
+
+/// Seconds since the epoch as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_timestamp(secs: libc::time_t) -> String {
     unsafe {
-        let mut tv: libc::timeval = std::mem::zeroed();
-        libc::gettimeofday(&mut tv, std::ptr::null_mut());
         let mut tm: libc::tm = std::mem::zeroed();
-        libc::gmtime_r(&tv.tv_sec, &mut tm);
+        libc::gmtime_r(&secs, &mut tm);
         format!(
             "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
             tm.tm_year + 1900,
@@ -260,18 +516,42 @@ pub struct GuardSnapshot {
     pub consecutive_ticks_above: u8,
     pub total_kills: u32,
     pub enabled: bool,
+    pub leader: bool,
+    pub leader_pid: Option<u32>,
+    pub memory_signal: &'static str,
+    pub time_to_limit: Option<f64>,
 }
 
 #[derive(Clone, Debug)]
 pub struct ChildSnapshot {
     pub index: usize,
+    pub name: String,
     pub command: String,
     pub pid: Option<u32>,
     pub state: String,
     pub total_uss: u64,
+    pub total_pss: u64,
+    pub total_rss: u64,
+    pub process_count: u32,
+    pub orphans: Vec<u32>,
+    pub adopted_pid: Option<u32>,
+    pub quarantine_reason: Option<String>,
+    pub quarantine_bundle: Option<String>,
+    pub log_path: Option<String>,
+    pub kill: Option<crate::process::KillProgress>,
     pub health_status: String,
     pub health_port: Option<u16>,
     pub restart_count: u32,
+    pub growth_rate: Option<f64>,
+    pub uptime_secs: Option<u64>,
+    pub priority: i32,
+    pub protected: bool,
+    pub mitigation: Option<&'static str>,
+    pub cgroup: Option<String>,
+    pub cgroup_memory: Option<u64>,
+    pub cpu_percent: Option<f64>,
+    pub pids: Option<u64>,
+    pub last_exit: Option<supervisor::ExitInfo>,
 }
 
 fn write_shared_state(children: &[supervisor::ManagedChild], guard: &guard::Guard) {
@@ -292,6 +572,7 @@ fn write_shared_state(children: &[supervisor::ManagedChild], guard: &guard::Guar
 pub fn remove_shared_state() {
     let pid = std::process::id();
     let _ = std::fs::remove_file(format!("/tmp/spm-state-{pid}.json"));
+    supervisor::remove_pgid_record();
 }
 
 fn build_shared_state(children: &[supervisor::ManagedChild], guard: &guard::Guard) -> SharedState {
@@ -303,18 +584,52 @@ fn build_shared_state(children: &[supervisor::ManagedChild], guard: &guard::Guar
             consecutive_ticks_above: guard.consecutive_ticks_above,
             total_kills: guard.total_kills,
             enabled: guard.config.enabled,
+            leader: guard.leader,
+            leader_pid: guard.leader_pid,
+            memory_signal: guard.config.memory_signal.as_str(),
+            time_to_limit: guard.time_to_limit,
         },
         children: children
             .iter()
             .map(|child| ChildSnapshot {
                 index: child.index,
+                name: child.name.clone(),
                 command: child.command.clone(),
                 pid: child.pid,
                 state: format!("{:?}", child.state),
                 total_uss: child.total_uss,
+                total_pss: child.total_pss,
+                total_rss: child.total_rss,
+                process_count: child.process_count,
+                orphans: child.orphans.clone(),
+                adopted_pid: child.spec.existing_pid,
+                quarantine_reason: child
+                    .quarantine
+                    .as_ref()
+                    .map(|quarantine| quarantine.reason.clone()),
+                quarantine_bundle: child
+                    .quarantine
+                    .as_ref()
+                    .and_then(|quarantine| quarantine.bundle.as_ref())
+                    .map(|path| path.display().to_string()),
+                log_path: child.log_path.as_ref().map(|path| path.display().to_string()),
+                kill: child
+                    .kill
+                    .as_ref()
+                    .and_then(|kill| kill.lock().ok().map(|kill| kill.clone())),
                 health_status: format!("{:?}", child.health.status),
                 health_port: child.health.port,
                 restart_count: child.restart_count,
+                growth_rate: child.growth_rate,
+                uptime_secs: child.started_at.map(|at| at.elapsed().as_secs()),
+                priority: child.spec.priority,
+                protected: child.spec.protected,
+                mitigation: child.mitigation.map(guard::GuardStep::as_str),
+                cgroup: child.cgroup.as_ref().map(|path| path.display().to_string()),
+                cgroup_memory: child.cgroup_stats.and_then(|stats| stats.memory_current),
+                cpu_percent: child.cpu_percent,
+                pids: child.cgroup_stats.map(|stats| stats.pids),
+                last_exit: child.last_exit.clone(),
             })
             .collect(),
     }
@@ -330,28 +645,156 @@ fn shared_state_to_json(state: &SharedState) -> String {
                 .health_port
                 .map(|port| port.to_string())
                 .unwrap_or("null".into());
+            let growth_str = child
+                .growth_rate
+                .map(|rate| format!("{rate:.3}"))
+                .unwrap_or("null".into());
+            let uptime_str = child
+                .uptime_secs
+                .map(|secs| secs.to_string())
+                .unwrap_or("null".into());
+            let mitigation_str = child
+                .mitigation
+                .map(|step| format!("\"{step}\""))
+                .unwrap_or("null".into());
+            let cgroup_str = child
+                .cgroup
+                .as_ref()
+                .map(|path| format!("\"{}\"", escape_json(path)))
+                .unwrap_or("null".into());
+            let cgroup_memory_str = child
+                .cgroup_memory
+                .map(|bytes| bytes.to_string())
+                .unwrap_or("null".into());
+            let cpu_str = child
+                .cpu_percent
+                .map(|percent| format!("{percent:.1}"))
+                .unwrap_or("null".into());
+            let adopted_str = child
+                .adopted_pid
+                .map(|pid| pid.to_string())
+                .unwrap_or("null".into());
+            let quarantine_str = child
+                .quarantine_reason
+                .as_ref()
+                .map(|reason| format!("\"{}\"", escape_json(reason)))
+                .unwrap_or("null".into());
+            let bundle_str = child
+                .quarantine_bundle
+                .as_ref()
+                .map(|path| format!("\"{}\"", escape_json(path)))
+                .unwrap_or("null".into());
+            let log_path_str = child
+                .log_path
+                .as_ref()
+                .map(|path| format!("\"{}\"", escape_json(path)))
+                .unwrap_or("null".into());
+            let kill_str = child
+                .kill
+                .as_ref()
+                .map(|kill| {
+                    let remaining = kill
+                        .deadline
+                        .map(|deadline| {
+                            format!(
+                                "{:.1}",
+                                deadline
+                                    .saturating_duration_since(Instant::now())
+                                    .as_secs_f64()
+                            )
+                        })
+                        .unwrap_or("null".into());
+                    format!(
+                        r#"{{"stage":"{}","signal":{},"remaining_secs":{}}}"#,
+                        kill.stage.as_str(),
+                        kill.stop.signal,
+                        remaining
+                    )
+                })
+                .unwrap_or("null".into());
+            let pids_str = child
+                .pids
+                .map(|count| count.to_string())
+                .unwrap_or("null".into());
+            let last_exit_str = child
+                .last_exit
+                .as_ref()
+                .map(|exit| {
+                    let number = |value: Option<i32>| {
+                        value.map(|value| value.to_string()).unwrap_or("null".into())
+                    };
+                    let killed_by = exit
+                        .killed_by
+                        .map(|origin| format!("\"{}\"", origin.label()))
+                        .unwrap_or("null".into());
+                    format!(
+                        r#"{{"exit_code":{},"signal":{},"killed_by":{},"usage":{}}}"#,
+                        number(exit.exit_code),
+                        number(exit.signal),
+                        killed_by,
+                        usage_to_json(&exit.usage)
+                    )
+                })
+                .unwrap_or("null".into());
             format!(
-                r#"{{"index":{},"command":"{}","pid":{},"state":"{}","total_uss":{},"health_status":"{}","health_port":{},"restart_count":{}}}"#,
+                r#"{{"index":{},"name":"{}","command":"{}","pid":{},"state":"{}","total_uss":{},"total_pss":{},"total_rss":{},"processes":{},"orphans":[{}],"adopted_pid":{},"quarantine_reason":{},"quarantine_bundle":{},"log_path":{},"kill":{},"health_status":"{}","health_port":{},"restart_count":{},"growth_rate":{},"uptime_secs":{},"priority":{},"protected":{},"mitigation":{},"cgroup":{},"cgroup_memory":{},"cpu_percent":{},"pids":{},"last_exit":{}}}"#,
                 child.index,
+                escape_json(&child.name),
                 escape_json(&child.command),
                 pid_str,
                 escape_json(&child.state),
                 child.total_uss,
+                child.total_pss,
+                child.total_rss,
+                child.process_count,
+                child
+                    .orphans
+                    .iter()
+                    .map(|pid| pid.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+                adopted_str,
+                quarantine_str,
+                bundle_str,
+                log_path_str,
+                kill_str,
                 escape_json(&child.health_status),
                 port_str,
-                child.restart_count
+                child.restart_count,
+                growth_str,
+                uptime_str,
+                child.priority,
+                child.protected,
+                mitigation_str,
+                cgroup_str,
+                cgroup_memory_str,
+                cpu_str,
+                pids_str,
+                last_exit_str
             )
         })
         .collect();
 
     format!(
-        r#"{{"timestamp":"{}","spm_pid":{},"guard":{{"kill_threshold_percent":{},"consecutive_ticks_above":{},"total_kills":{},"enabled":{}}},"children":[{}]}}"#,
+        r#"{{"timestamp":"{}","spm_pid":{},"guard":{{"kill_threshold_percent":{},"consecutive_ticks_above":{},"total_kills":{},"enabled":{},"leader":{},"leader_pid":{},"memory_signal":"{}","time_to_limit_secs":{}}},"children":[{}]}}"#,
         state.timestamp,
         state.spm_pid,
         state.guard.kill_threshold_percent,
         state.guard.consecutive_ticks_above,
         state.guard.total_kills,
         state.guard.enabled,
+        state.guard.leader,
+        state
+            .guard
+            .leader_pid
+            .map(|pid| pid.to_string())
+            .unwrap_or_else(|| "null".to_string()),
+        state.guard.memory_signal,
+        state
+            .guard
+            .time_to_limit
+            .map(|secs| format!("{secs:.1}"))
+            .unwrap_or_else(|| "null".to_string()),
         children_json.join(",")
     )
 }
@@ -369,8 +812,12 @@ pub fn spawn_monitor_thread(
             libc::signal(libc::SIGTERM, signal_handler as libc::sighandler_t);
         }
 
+        let mut control = control::ControlServer::bind().ok();
+        let mut leader_lock = guard::LeaderLock::default();
+
         loop {
             if SHUTDOWN_REQUESTED.load(Ordering::SeqCst) {
+                leader_lock.release();
                 let max_restarts = guard
                     .lock()
                     .map(|guard| guard.config.max_restarts)
@@ -382,17 +829,47 @@ pub fn spawn_monitor_thread(
                 break;
             }
 
-            thread::sleep(Duration::from_secs(1));
+            let tick_started = Instant::now();
+            while tick_started.elapsed() < Duration::from_secs(1) {
+                if let Some(ref mut control) = control {
+                    control.poll(|request| {
+                        let (mut children, guard) = match (managed.lock(), guard.lock()) {
+                            (Ok(children), Ok(guard)) => (children, guard),
+                            _ => return control::error_response("supervisor state unavailable"),
+                        };
+                        match handle_control_request(request, &mut children, &guard, headless, &tx)
+                        {
+                            Ok(response) => response,
+                            Err(error) => control::error_response(&error),
+                        }
+                    });
+                }
+                if let Ok(children) = managed.lock() {
+                    if PENDING_SIGNALS.load(Ordering::SeqCst) != 0 {
+                        forward_pending_signals(&children, &tx);
+                    }
+                    for child in children.iter() {
+                        supervisor::follow_log(child);
+                    }
+                }
+                thread::sleep(Duration::from_millis(100));
+            }
 
             let mut children = match managed.lock() {
                 Ok(guard) => guard,
                 Err(_) => continue,
             };
+            let tree = supervisor::ProcessTree::scan();
+            for (position, pid) in supervisor::adopt_orphans(&mut children, &tree) {
+                let _ = tx.send(MonitorEvent::Orphan {
+                    index: children[position].index,
+                    cmd: children[position].command.clone(),
+                    pid,
+                });
+            }
             for child in children.iter_mut() {
-                if child.state == supervisor::ChildState::Running {
-                    if let Some(pid) = child.pid {
-                        child.total_uss = supervisor::compute_group_uss(pid);
-                    }
+                if child.state == supervisor::ChildState::Running || !child.orphans.is_empty() {
+                    supervisor::sample_child(child, &tree);
                 }
             }
 
@@ -401,10 +878,27 @@ pub fn spawn_monitor_thread(
                 .map(|guard| guard.config.max_restarts)
                 .unwrap_or(10);
             reap_zombies(&mut children, max_restarts, true, &tx);
+            if INIT_MODE.load(Ordering::SeqCst)
+                && children.iter().any(|child| {
+                    child.spec.primary
+                        && matches!(
+                            child.state,
+                            supervisor::ChildState::Completed
+                                | supervisor::ChildState::Failed
+                                | supervisor::ChildState::Quarantined
+                        )
+                })
+            {
+                PRIMARY_EXITED.store(true, Ordering::SeqCst);
+                SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+            }
 
-            // Health check tick (runs every iteration, but health.tick() handles 5s interval)
+            // Health check tick (runs every iteration, but health.tick() handles 5s interval).
+            // A child frozen by the guard cannot answer, so it is not checked until resumed.
             for child in children.iter_mut() {
-                if matches!(child.state, supervisor::ChildState::Running) {
+                if matches!(child.state, supervisor::ChildState::Running)
+                    && child.mitigation != Some(guard::GuardStep::Freeze)
+                {
                     let prev_status = child.health.status.clone();
                     child.health.tick(child.pid);
 
@@ -423,23 +917,21 @@ pub fn spawn_monitor_thread(
                             }
                         }
                         (_, health::HealthStatus::Unhealthy) => {
-                            if let (Some(pid), Some(pgid)) = (child.pid, child.pgid) {
+                            if let Some(pid) = child.pid {
                                 let endpoint = child.health.endpoint.clone().unwrap_or_default();
+                                supervisor::note_output(
+                                    child,
+                                    &format!("killed by the health check ({endpoint} unhealthy)"),
+                                );
                                 let _ = tx.send(MonitorEvent::HealthKill {
                                     index: child.index,
                                     pid,
                                     cmd: child.command.clone(),
                                     endpoint,
                                 });
-                                let _ = process::kill_process_group(pgid, false);
+                                let _ = supervisor::kill_child(child, false);
                                 child.state = supervisor::ChildState::Stopping { emergency: false };
-                                child.last_exit = Some(supervisor::ExitInfo {
-                                    exit_code: None,
-                                    signal: Some(libc::SIGTERM),
-                                    killed_by_guard: false,
-                                    killed_by_health: true,
-                                    exited_at: Instant::now(),
-                                });
+                                child.kill_origin = Some(supervisor::KillOrigin::Health);
                             }
                         }
                         _ => {
@@ -460,12 +952,33 @@ pub fn spawn_monitor_thread(
             }
 
             let pod_memory = cgroup::read_pod_memory();
+            let mut remote_children = Vec::new();
             let action = {
                 let mut guard = match guard.lock() {
                     Ok(guard) => guard,
                     Err(_) => continue,
                 };
-                guard.evaluate(&pod_memory, &children, &policy)
+                guard.leader = guard.config.enabled && leader_lock.try_acquire();
+                if guard.leader {
+                    guard.leader_pid = Some(std::process::id());
+                    remote_children = crate::app::read_instance_children(std::process::id(), true);
+                    if remote_children.is_empty() {
+                        guard.evaluate(&pod_memory, &children, &policy)
+                    } else {
+                        let candidates: Vec<supervisor::ManagedChild> = children
+                            .iter()
+                            .chain(remote_children.iter())
+                            .cloned()
+                            .collect();
+                        guard.evaluate(&pod_memory, &candidates, &policy)
+                    }
+                } else {
+                    guard.leader_pid = guard::read_leader_pid();
+                    guard.consecutive_ticks_above = 0;
+                    guard.memory_samples.clear();
+                    guard.time_to_limit = None;
+                    guard::GuardAction::None
+                }
             };
 
             match action {
@@ -474,30 +987,33 @@ pub fn spawn_monitor_thread(
                     reason,
                     emergency,
                 } => {
-                    if let Some(child) = children.get_mut(victim_index) {
-                        let pid = child.pid.unwrap_or(0);
-                        let cmd = child.command.clone();
-                        let uss = child.total_uss;
-                        let pod_percent = pod_memory
-                            .cgroup_limit
-                            .map(|limit| (pod_memory.cgroup_usage as f64 / limit as f64) * 100.0)
-                            .unwrap_or(0.0);
-
-                        let _ = tx.send(MonitorEvent::GuardKill {
-                            index: victim_index,
-                            pid,
-                            cmd: cmd.clone(),
-                            uss,
-                            pod_percent,
-                            reason: reason.clone(),
-                            emergency,
-                        });
+                    let pod_percent = guard
+                        .lock()
+                        .ok()
+                        .and_then(|guard| pod_memory.percent_for(guard.config.memory_signal))
+                        .unwrap_or(0.0);
 
-                        if let Some(pgid) = child.pgid {
-                            let _ = process::kill_process_group(pgid, emergency);
-                        }
-                        child.state = supervisor::ChildState::Stopping { emergency };
+                    let killed = if let Some(child) = children.get_mut(victim_index) {
+                        guard_kill_child(child, reason.clone(), emergency, pod_percent, &tx);
+                        true
+                    } else if let Some(owner) = remote_children
+                        .get(victim_index - children.len())
+                        .and_then(|child| child.owner)
+                    {
+                        let request = ControlRequest::GuardKill {
+                            target: owner.index.to_string(),
+                            emergency,
+                            reason: Some(reason.clone()),
+                        };
+                        let reply;
+                        (children, reply) =
+                            send_unlocked(children, &managed, owner.spm_pid, &request);
+                        matches!(reply, Ok(reply) if reply.starts_with("{\"ok\":true"))
+                    } else {
+                        false
+                    };
 
+                    if killed {
                         if let Ok(mut guard) = guard.lock() {
                             guard.total_kills = guard.total_kills.saturating_add(1);
                             guard.consecutive_ticks_above = 0;
@@ -509,6 +1025,69 @@ pub fn spawn_monitor_thread(
                         }
                     }
                 }
+                guard::GuardAction::Mitigate {
+                    victim_index,
+                    step,
+                    reason,
+                } => {
+                    let pod_percent = guard
+                        .lock()
+                        .ok()
+                        .and_then(|guard| pod_memory.percent_for(guard.config.memory_signal))
+                        .unwrap_or(0.0);
+
+                    let taken = if let Some(child) = children.get_mut(victim_index) {
+                        match guard.lock() {
+                            Ok(guard) => Some(guard_escalate_child(
+                                child,
+                                step,
+                                &guard,
+                                reason.clone(),
+                                pod_percent,
+                                &tx,
+                            )),
+                            Err(_) => None,
+                        }
+                    } else if let Some(owner) = remote_children
+                        .get(victim_index - children.len())
+                        .and_then(|child| child.owner)
+                    {
+                        let request = ControlRequest::GuardMitigate {
+                            target: owner.index.to_string(),
+                            step,
+                            reason: Some(reason.clone()),
+                        };
+                        let reply;
+                        (children, reply) =
+                            send_unlocked(children, &managed, owner.spm_pid, &request);
+                        match reply {
+                            Ok(reply) if reply.starts_with("{\"ok\":true") => {
+                                Some(if reply.contains("\"step\":\"kill\"") {
+                                    guard::GuardStep::Kill
+                                } else {
+                                    step
+                                })
+                            }
+                            _ => None,
+                        }
+                    } else {
+                        None
+                    };
+
+                    if let (Some(taken), Ok(mut guard)) = (taken, guard.lock()) {
+                        guard.consecutive_ticks_above = 0;
+                        if taken == guard::GuardStep::Kill {
+                            guard.total_kills = guard.total_kills.saturating_add(1);
+                            guard.last_kill = Some(guard::KillEvent {
+                                victim_index,
+                                reason,
+                                at: Instant::now(),
+                            });
+                        } else {
+                            guard.last_mitigation = Some(Instant::now());
+                        }
+                    }
+                }
                 guard::GuardAction::Warning {
                     percent,
                     ticks_remaining,
@@ -526,37 +1105,37 @@ pub fn spawn_monitor_thread(
                 guard::GuardAction::None => {}
             }
 
+            let resume = guard
+                .lock()
+                .map(|guard| guard.should_resume(&pod_memory))
+                .unwrap_or(false);
+            if resume {
+                for child in children.iter_mut() {
+                    if child.mitigation.is_some() {
+                        resume_child(child);
+                        if let Some(pid) = child.pid {
+                            let _ = tx.send(MonitorEvent::GuardResume {
+                                index: child.index,
+                                pid,
+                                cmd: child.command.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+
             for child in children.iter_mut() {
                 if child.state == supervisor::ChildState::Restarting {
                     if let Some(restart_at) = child.backoff.restart_at {
-                        if Instant::now() >= restart_at {
+                        if Instant::now() >= restart_at && !supervisor::kill_pending(child) {
                             child.backoff.restart_at = None;
-                            match supervisor::spawn_child(child, headless) {
-                                Ok(spawned) => {
+                            match start_child(child, headless, &tx) {
+                                Ok(new_pid) => {
                                     child.health.reset();
-                                    if headless {
-                                        let name = supervisor::command_name(&child.command);
-                                        if let Some(stdout) = spawned.stdout {
-                                            supervisor::spawn_output_reader(
-                                                name.clone(),
-                                                stdout,
-                                                false,
-                                            );
-                                        }
-                                        if let Some(stderr) = spawned.stderr {
-                                            supervisor::spawn_output_reader(name, stderr, true);
-                                        }
-                                    }
-                                    let _ = tx.send(MonitorEvent::Spawn {
-                                        index: child.index,
-                                        cmd: child.command.clone(),
-                                        pid: spawned.pid,
-                                        log_path: child.log_path.clone(),
-                                    });
                                     let _ = tx.send(MonitorEvent::Restart {
                                         index: child.index,
                                         cmd: child.command.clone(),
-                                        new_pid: spawned.pid,
+                                        new_pid,
                                         restart_count: child.restart_count,
                                         backoff_secs: child.backoff.last_delay.as_secs_f64(),
                                     });
@@ -579,60 +1158,577 @@ pub fn spawn_monitor_thread(
                 }
             }
 
+            for child in children.iter() {
+                if let Some(outcome) = child.kill.as_ref().and_then(process::take_outcome) {
+                    let _ = tx.send(MonitorEvent::KillFinished {
+                        index: child.index,
+                        outcome,
+                    });
+                }
+            }
+
+            for index in 0..children.len() {
+                if children[index].state != supervisor::ChildState::Waiting {
+                    continue;
+                }
+                let started = match supervisor::dependency_readiness(&children[index], &children) {
+                    supervisor::Readiness::Waiting(dependency) => {
+                        let child = &mut children[index];
+                        let timeout = child
+                            .spec
+                            .ready_timeout
+                            .unwrap_or(supervisor::DEFAULT_READY_TIMEOUT);
+                        let since = *child.waiting_since.get_or_insert_with(Instant::now);
+                        if since.elapsed() < timeout {
+                            continue;
+                        }
+                        let _ = tx.send(MonitorEvent::Warning {
+                            index: child.index,
+                            message: format!(
+                                "not started: dependency '{dependency}' not ready after {}s",
+                                timeout.as_secs_f64()
+                            ),
+                        });
+                        false
+                    }
+                    supervisor::Readiness::Ready => {
+                        start_child(&mut children[index], headless, &tx).is_ok()
+                    }
+                    supervisor::Readiness::Blocked(dependency) => {
+                        let _ = tx.send(MonitorEvent::Warning {
+                            index: children[index].index,
+                            message: format!("not started: dependency '{dependency}' failed"),
+                        });
+                        false
+                    }
+                };
+                children[index].waiting_since = None;
+                if !started {
+                    let child = &mut children[index];
+                    child.state = supervisor::ChildState::Failed;
+                    let _ = tx.send(MonitorEvent::Failed {
+                        index: child.index,
+                        cmd: child.command.clone(),
+                        restart_count: child.restart_count,
+                    });
+                }
+            }
+
             if let Ok(guard_lock) = guard.lock() {
                 write_shared_state(&children, &guard_lock);
             }
+            supervisor::write_pgid_record(&children);
 
             let _ = tx.send(MonitorEvent::StateUpdate);
         }
     })
 }
 
-fn shutdown_children(
-    children: &mut Vec<supervisor::ManagedChild>,
-    max_restarts: u32,
+/// Send a request to another supervisor with our children unlocked, so the
+/// UI thread is not held up for as long as that supervisor takes to reply.
+fn send_unlocked<'a>(
+    children: MutexGuard<'a, Vec<supervisor::ManagedChild>>,
+    managed: &'a Mutex<Vec<supervisor::ManagedChild>>,
+    spm_pid: u32,
+    request: &ControlRequest,
+) -> (
+    MutexGuard<'a, Vec<supervisor::ManagedChild>>,
+    Result<String, String>,
+) {
+    drop(children);
+    let reply = control::send_request(spm_pid, request);
+    let children = managed
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    (children, reply)
+}
+
+/// Spawn a child and report its spawn event.
+fn start_child(
+    child: &mut supervisor::ManagedChild,
+    headless: bool,
+    tx: &mpsc::Sender<MonitorEvent>,
+) -> Result<u32, String> {
+    let spawned = supervisor::spawn_child(child, headless)?;
+    let _ = tx.send(MonitorEvent::Spawn {
+        index: child.index,
+        cmd: child.command.clone(),
+        pid: spawned.pid,
+        log_path: child.log_path.clone(),
+    });
+    for message in spawned.warnings {
+        let _ = tx.send(MonitorEvent::Warning {
+            index: child.index,
+            message,
+        });
+    }
+    Ok(spawned.pid)
+}
+
+fn resolve_target(children: &[supervisor::ManagedChild], target: &str) -> Result<usize, String> {
+    if let Some(position) = children.iter().position(|child| child.name == target) {
+        return Ok(position);
+    }
+    target
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| children.iter().position(|child| child.index == index))
+        .ok_or_else(|| format!("no managed child named '{target}'"))
+}
+
+fn is_alive(child: &supervisor::ManagedChild) -> bool {
+    matches!(
+        child.state,
+        supervisor::ChildState::Running | supervisor::ChildState::Stopping { .. }
+    )
+}
+
+/// Kill the child the guard picked as its victim and report it.
+fn guard_kill_child(
+    child: &mut supervisor::ManagedChild,
+    reason: KillReason,
+    emergency: bool,
+    pod_percent: f64,
     tx: &mpsc::Sender<MonitorEvent>,
 ) {
-    for child in children.iter() {
-        if matches!(
-            child.state,
-            supervisor::ChildState::Running | supervisor::ChildState::Stopping { .. }
-        ) {
+    supervisor::note_output(
+        child,
+        &format!(
+            "killed by the guard ({}, USS {} MB, pod memory {pod_percent:.1}%)",
+            kill_reason_label(&reason),
+            child.total_uss / 1_048_576
+        ),
+    );
+    let _ = tx.send(MonitorEvent::GuardKill {
+        index: child.index,
+        pid: child.pid.unwrap_or(0),
+        cmd: child.command.clone(),
+        uss: child.total_uss,
+        pod_percent,
+        reason,
+        emergency,
+    });
+
+    resume_child(child);
+    let _ = supervisor::kill_child(child, emergency);
+    child.state = supervisor::ChildState::Stopping { emergency };
+    child.kill_origin = Some(supervisor::KillOrigin::Guard);
+}
+
+/// Take `step` on the guard's victim. A step the child cannot take — no
+/// cgroup of its own to throttle — falls through to the next rung of the
+/// ladder. Returns the step actually taken.
+fn guard_escalate_child(
+    child: &mut supervisor::ManagedChild,
+    mut step: guard::GuardStep,
+    guard: &guard::Guard,
+    reason: KillReason,
+    pod_percent: f64,
+    tx: &mpsc::Sender<MonitorEvent>,
+) -> guard::GuardStep {
+    while step != guard::GuardStep::Kill {
+        if apply_mitigation(child, step).is_ok() {
+            let _ = tx.send(MonitorEvent::GuardMitigate {
+                index: child.index,
+                pid: child.pid.unwrap_or(0),
+                cmd: child.command.clone(),
+                pod_percent,
+                step,
+                reason,
+            });
+            return step;
+        }
+        step = guard.next_step(Some(step));
+    }
+    guard_kill_child(child, reason, false, pod_percent, tx);
+    guard::GuardStep::Kill
+}
+
+fn apply_mitigation(
+    child: &mut supervisor::ManagedChild,
+    step: guard::GuardStep,
+) -> Result<(), String> {
+    let pid = child.pid.ok_or("not running")?;
+    let cgroup = child
+        .cgroup
+        .clone()
+        .or_else(|| cgroup::dedicated_cgroup(pid));
+    match (step, cgroup) {
+        (guard::GuardStep::Throttle, Some(cgroup)) => {
+            let current = cgroup::read_cgroup_u64(&cgroup, "memory.current")
+                .ok_or("memory.current unavailable")?;
+            cgroup::write_cgroup_file(&cgroup, "memory.high", &current.to_string())?;
+        }
+        (guard::GuardStep::Throttle, None) => return Err("no cgroup of its own".to_string()),
+        (guard::GuardStep::Freeze, Some(cgroup)) => {
+            cgroup::write_cgroup_file(&cgroup, "cgroup.freeze", "1")?;
+        }
+        (guard::GuardStep::Freeze, None) => {
+            let pgid = child.pgid.ok_or("no process group")?;
+            supervisor::signal_process_group(pgid, libc::SIGSTOP)?;
+        }
+        (guard::GuardStep::Kill, _) => return Err("not a mitigation".to_string()),
+    }
+    child.mitigation = Some(step);
+    Ok(())
+}
+
+/// Lift any throttle or freeze the guard put on `child`.
+fn resume_child(child: &mut supervisor::ManagedChild) {
+    let Some(step) = child.mitigation.take() else {
+        return;
+    };
+    match child
+        .cgroup
+        .clone()
+        .or_else(|| child.pid.and_then(cgroup::dedicated_cgroup))
+    {
+        Some(cgroup) => {
+            let _ = cgroup::write_cgroup_file(&cgroup, "cgroup.freeze", "0");
+            let _ = cgroup::write_cgroup_file(&cgroup, "memory.high", "max");
+        }
+        None if step == guard::GuardStep::Freeze => {
             if let Some(pgid) = child.pgid {
-                let _ = supervisor::signal_process_group(pgid, libc::SIGTERM);
+                let _ = supervisor::signal_process_group(pgid, libc::SIGCONT);
             }
         }
+        None => {}
     }
+}
 
-    for _ in 0..50 {
-        thread::sleep(Duration::from_millis(100));
-        reap_zombies(children, max_restarts, false, tx);
-        let all_dead = children.iter().all(|child| {
-            !matches!(
+/// Terminate a running child as if it had crashed: its restart policy
+/// decides what happens next.
+pub fn kill_running_child(
+    child: &mut supervisor::ManagedChild,
+    origin: supervisor::KillOrigin,
+) -> Result<(), String> {
+    match child.pid {
+        Some(_) if child.state == supervisor::ChildState::Running => {
+            resume_child(child);
+            supervisor::kill_child(child, false)?;
+            child.state = supervisor::ChildState::Stopping { emergency: false };
+            child.kill_origin = Some(origin);
+            Ok(())
+        }
+        _ => Err(format!("'{}' is not running", child.name)),
+    }
+}
+
+/// Signal a running child and record what should happen once it exits.
+pub fn stop_child(child: &mut supervisor::ManagedChild, then: supervisor::PendingAction) {
+    child.pending_action = Some(then);
+    resume_child(child);
+    if child.state == supervisor::ChildState::Running {
+        let _ = supervisor::kill_child(child, false);
+        child.state = supervisor::ChildState::Stopping { emergency: false };
+    }
+}
+
+fn handle_control_request(
+    request: ControlRequest,
+    children: &mut Vec<supervisor::ManagedChild>,
+    guard: &guard::Guard,
+    headless: bool,
+    tx: &mpsc::Sender<MonitorEvent>,
+) -> Result<String, String> {
+    match request {
+        ControlRequest::List => {
+            let state = build_shared_state(children, guard);
+            Ok(format!(
+                "{{\"ok\":true,\"state\":{}}}",
+                shared_state_to_json(&state)
+            ))
+        }
+        ControlRequest::Start(target) => {
+            let position = resolve_target(children, &target)?;
+            let child = &mut children[position];
+            if child.state == supervisor::ChildState::Quarantined {
+                supervisor::resume_quarantined(child);
+                return Ok(control::ok_response());
+            }
+            if is_alive(child) {
+                return Err(format!("'{}' is already running", child.name));
+            }
+            child.backoff.restart_at = None;
+            child.pending_action = None;
+            start_child(child, headless, tx)?;
+            Ok(control::ok_response())
+        }
+        ControlRequest::Stop(target) => {
+            let position = resolve_target(children, &target)?;
+            let child = &mut children[position];
+            if is_alive(child) {
+                stop_child(child, supervisor::PendingAction::Stop);
+            } else if matches!(
                 child.state,
-                supervisor::ChildState::Running | supervisor::ChildState::Stopping { .. }
-            )
-        });
-        if all_dead {
-            break;
+                supervisor::ChildState::Restarting
+                    | supervisor::ChildState::Waiting
+                    | supervisor::ChildState::Quarantined
+            ) {
+                child.backoff.restart_at = None;
+                child.quarantine = None;
+                child.state = supervisor::ChildState::Stopped;
+                let _ = tx.send(MonitorEvent::Stopped {
+                    index: child.index,
+                    cmd: child.command.clone(),
+                });
+            } else {
+                return Err(format!("'{}' is not running", child.name));
+            }
+            Ok(control::ok_response())
+        }
+        ControlRequest::Restart(target) => {
+            let position = resolve_target(children, &target)?;
+            let child = &mut children[position];
+            if child.origin == supervisor::Origin::Existing {
+                return Err(format!(
+                    "'{}' was adopted and cannot be restarted",
+                    child.name
+                ));
+            }
+            if child.state == supervisor::ChildState::Quarantined {
+                supervisor::resume_quarantined(child);
+            } else if is_alive(child) {
+                stop_child(child, supervisor::PendingAction::Restart);
+            } else {
+                child.backoff.restart_at = None;
+                child.pending_action = None;
+                start_child(child, headless, tx)?;
+            }
+            Ok(control::ok_response())
         }
+        ControlRequest::Kill(target) => {
+            let position = resolve_target(children, &target)?;
+            kill_running_child(&mut children[position], supervisor::KillOrigin::Control)?;
+            Ok(control::ok_response())
+        }
+        ControlRequest::GuardKill {
+            target,
+            emergency,
+            reason,
+        } => {
+            if !guard.config.enabled {
+                return Err("the guard is disabled here".to_string());
+            }
+            let position = resolve_target(children, &target)?;
+            let child = &mut children[position];
+            if child.state != supervisor::ChildState::Running {
+                return Err(format!("'{}' is not running", child.name));
+            }
+            let pod_percent = cgroup::read_pod_memory()
+                .percent_for(guard.config.memory_signal)
+                .unwrap_or(0.0);
+            guard_kill_child(
+                child,
+                reason.unwrap_or(KillReason::ThresholdExceeded { pod_percent }),
+                emergency,
+                pod_percent,
+                tx,
+            );
+            Ok(control::ok_response())
+        }
+        ControlRequest::GuardMitigate {
+            target,
+            step,
+            reason,
+        } => {
+            if !guard.config.enabled {
+                return Err("the guard is disabled here".to_string());
+            }
+            let position = resolve_target(children, &target)?;
+            let child = &mut children[position];
+            if child.state != supervisor::ChildState::Running {
+                return Err(format!("'{}' is not running", child.name));
+            }
+            let pod_percent = cgroup::read_pod_memory()
+                .percent_for(guard.config.memory_signal)
+                .unwrap_or(0.0);
+            let taken = guard_escalate_child(
+                child,
+                step,
+                guard,
+                reason.unwrap_or(KillReason::ThresholdExceeded { pod_percent }),
+                pod_percent,
+                tx,
+            );
+            Ok(format!("{{\"ok\":true,\"step\":\"{}\"}}", taken.as_str()))
+        }
+        ControlRequest::Signal(target, signal) => {
+            let child = &children[resolve_target(children, &target)?];
+            match child.pid {
+                Some(_) if is_alive(child) => {
+                    supervisor::signal_child(child, signal)?;
+                    Ok(control::ok_response())
+                }
+                _ => Err(format!("'{}' is not running", child.name)),
+            }
+        }
+        ControlRequest::Add {
+            name,
+            command,
+            argv,
+        } => {
+            if let Some(ref name) = name {
+                crate::config::validate_name(name)?;
+                if children.iter().any(|child| &child.name == name) {
+                    return Err(format!("a child named '{name}' already exists"));
+                }
+            }
+            let spec = supervisor::ChildSpec {
+                name,
+                argv,
+                ..supervisor::ChildSpec::from_command(command)
+            };
+            let index = supervisor::allocate_child_index(children);
+            children.push(supervisor::ManagedChild::from_spec(index, spec));
+            let position = children.len() - 1;
+            if let Err(error) = start_child(&mut children[position], headless, tx) {
+                children[position].state = supervisor::ChildState::Failed;
+                return Err(error);
+            }
+            Ok(control::ok_response())
+        }
+        ControlRequest::Adopt(pid) => {
+            let position = adopt_process(children, pid)?;
+            let _ = tx.send(MonitorEvent::Adopt {
+                index: children[position].index,
+                cmd: children[position].command.clone(),
+                pid,
+            });
+            Ok(control::ok_response())
+        }
+        ControlRequest::Remove(target) => {
+            let position = resolve_target(children, &target)?;
+            if is_alive(&children[position]) {
+                stop_child(&mut children[position], supervisor::PendingAction::Stop);
+            }
+            let removed = children.remove(position);
+            supervisor::release_child_cgroup(&removed);
+            Ok(control::ok_response())
+        }
+    }
+}
+
+/// Add the running process `pid` as a new child. Returns its position.
+pub fn adopt_process(
+    children: &mut Vec<supervisor::ManagedChild>,
+    pid: u32,
+) -> Result<usize, String> {
+    if let Some(child) = children
+        .iter()
+        .find(|child| child.pid == Some(pid) || child.tree_pids.contains(&pid))
+    {
+        return Err(format!(
+            "PID {pid} is already supervised as '{}'",
+            child.name
+        ));
     }
+    let spec = supervisor::existing_process_spec(pid)?;
+    let index = supervisor::allocate_child_index(children);
+    let mut child = supervisor::ManagedChild::from_spec(index, spec);
+    supervisor::attach_existing(&mut child)?;
+    children.push(child);
+    Ok(children.len() - 1)
+}
 
+/// Stop every child spm started, dependents before what they depend on.
+/// Each wave runs its pre-stop hooks, gets its stop signals (SIGTERM or the
+/// init-mode shutdown signal unless a child sets its own) and every child is
+/// SIGKILLed once its grace period is up, before the next wave is signalled.
+/// Adopted processes were running before spm and are left running after it,
+/// though thawed and unthrottled if the guard mitigated them.
+fn shutdown_children(
+    children: &mut Vec<supervisor::ManagedChild>,
+    max_restarts: u32,
+    tx: &mpsc::Sender<MonitorEvent>,
+) {
+    let signal = SHUTDOWN_SIGNAL.load(Ordering::SeqCst);
     for child in children.iter_mut() {
-        if matches!(
-            child.state,
-            supervisor::ChildState::Running | supervisor::ChildState::Stopping { .. }
-        ) {
-            if let Some(pgid) = child.pgid {
-                let _ = supervisor::signal_process_group(pgid, libc::SIGKILL);
+        resume_child(child);
+    }
+    for wave in supervisor::shutdown_order(children) {
+        let wave: Vec<usize> = wave
+            .into_iter()
+            .filter(|index| children[*index].origin != supervisor::Origin::Existing)
+            .collect();
+        thread::scope(|scope| {
+            for index in &wave {
+                let child = &children[*index];
+                if is_alive(child) && child.spec.pre_stop.is_set() {
+                    scope.spawn(move || supervisor::run_pre_stop(child));
+                }
             }
-            child.state = supervisor::ChildState::Stopped;
-            child.pid = None;
-            child.pgid = None;
+        });
+
+        let mut deadlines = Vec::with_capacity(wave.len());
+        for index in &wave {
+            let child = &mut children[*index];
+            let stop = supervisor::stop_signal(child, SHUTDOWN_TIMEOUT);
+            let stop_signal = child.spec.stop_signal.unwrap_or(signal);
+            if is_alive(child) {
+                let _ = supervisor::signal_child(child, stop_signal);
+            } else {
+                supervisor::signal_orphans(child, stop_signal);
+            }
+            deadlines.push((*index, Instant::now() + stop.timeout));
+        }
+
+        loop {
+            let now = Instant::now();
+            for (index, deadline) in &deadlines {
+                let child = &mut children[*index];
+                if now >= *deadline && is_alive(child) {
+                    let _ = supervisor::signal_child(child, libc::SIGKILL);
+                    child.state = supervisor::ChildState::Stopped;
+                    child.pid = None;
+                    child.pgid = None;
+                }
+            }
+            let done = deadlines.iter().all(|(index, deadline)| {
+                let child = &children[*index];
+                !is_alive(child) && (child.orphans.is_empty() || now >= *deadline)
+            });
+            if done {
+                break;
+            }
+            thread::sleep(Duration::from_millis(100));
+            reap_zombies(children, max_restarts, false, tx);
+        }
+
+        for index in &wave {
+            let child = &mut children[*index];
+            supervisor::signal_orphans(child, libc::SIGKILL);
+            child.orphans.clear();
         }
     }
 
     remove_shared_state();
+    control::remove_socket();
+    cgroup::remove_child_cgroups();
+}
+
+/// Terminate the orphans left behind once every child has finished, the
+/// same way `shutdown_children` does: SIGTERM, a grace period, SIGKILL.
+pub fn shutdown_orphans(children: &[supervisor::ManagedChild]) {
+    let orphans: Vec<u32> = children
+        .iter()
+        .flat_map(|child| child.orphans.iter().copied())
+        .collect();
+    if orphans.is_empty() {
+        return;
+    }
+    for child in children {
+        supervisor::signal_orphans(child, libc::SIGTERM);
+    }
+    for _ in 0..30 {
+        thread::sleep(Duration::from_millis(100));
+        if !orphans.iter().any(|pid| supervisor::is_running(*pid)) {
+            return;
+        }
+    }
+    for child in children {
+        supervisor::signal_orphans(child, libc::SIGKILL);
+    }
 }
 
 fn reap_zombies(
@@ -641,16 +1737,41 @@ fn reap_zombies(
     allow_restart: bool,
     tx: &mpsc::Sender<MonitorEvent>,
 ) {
-    loop {
-        let mut status: i32 = 0;
-        let result = unsafe { libc::waitpid(-1, &mut status, libc::WNOHANG) };
-        if result <= 0 {
-            break;
+    while let Some((pid, status, rusage)) = supervisor::reap_next() {
+        let (exit_code, signal) = if libc::WIFEXITED(status) {
+            (Some(libc::WEXITSTATUS(status)), None)
+        } else if libc::WIFSIGNALED(status) {
+            (None, Some(libc::WTERMSIG(status)))
+        } else {
+            continue;
+        };
+        if let Some(child) = children.iter_mut().find(|child| child.pid == Some(pid)) {
+            handle_child_exit(
+                child,
+                pid,
+                (exit_code, signal),
+                Some(supervisor::Rusage::from(&rusage)),
+                max_restarts,
+                allow_restart,
+                tx,
+            );
+        } else {
+            for child in children.iter_mut() {
+                child.orphans.retain(|orphan| *orphan != pid);
+            }
         }
+    }
 
-        let pid = result as u32;
-        if let Some(child) = children.iter_mut().find(|child| child.pid == Some(pid)) {
-            handle_child_exit(child, pid, status, max_restarts, allow_restart, tx);
+    // Adopted children are not ours to wait for; notice their exit by polling.
+    for child in children.iter_mut() {
+        let Some(pid) = child
+            .pid
+            .filter(|_| child.origin != supervisor::Origin::Spawned)
+        else {
+            continue;
+        };
+        if !supervisor::is_running(pid) {
+            handle_child_exit(child, pid, (None, None), None, max_restarts, allow_restart, tx);
         }
     }
 }
@@ -658,48 +1779,45 @@ fn reap_zombies(
 fn handle_child_exit(
     child: &mut supervisor::ManagedChild,
     pid: u32,
-    status: i32,
+    (exit_code, signal): (Option<i32>, Option<i32>),
+    rusage: Option<supervisor::Rusage>,
     max_restarts: u32,
     allow_restart: bool,
     tx: &mpsc::Sender<MonitorEvent>,
 ) {
-    let (exit_code, signal) = if libc::WIFEXITED(status) {
-        (Some(libc::WEXITSTATUS(status)), None)
-    } else if libc::WIFSIGNALED(status) {
-        (None, Some(libc::WTERMSIG(status)))
-    } else {
-        return;
+    supervisor::finish_log(child);
+    let pending_action = child.pending_action.take();
+    child.mitigation = None;
+    let stopping = matches!(child.state, supervisor::ChildState::Stopping { .. });
+    let killed_by = child.kill_origin.take();
+    let usage = supervisor::RunUsage {
+        rusage,
+        wall_secs: child.started_at.map(|at| at.elapsed().as_secs_f64()),
+        peak_uss: child.peak_uss,
     };
-
     child.last_exit = Some(supervisor::ExitInfo {
         exit_code,
         signal,
-        killed_by_guard: matches!(child.state, supervisor::ChildState::Stopping { .. }),
-        killed_by_health: false,
+        killed_by,
         exited_at: Instant::now(),
+        usage,
     });
 
-    let was_guard = matches!(child.state, supervisor::ChildState::Stopping { .. });
-    let was_health = child
-        .last_exit
-        .as_ref()
-        .map(|e| e.killed_by_health)
-        .unwrap_or(false);
     let _ = tx.send(MonitorEvent::Exit {
         index: child.index,
         cmd: child.command.clone(),
         pid,
         exit_code,
         signal,
-        killed_by_guard: was_guard,
-        killed_by_health: was_health,
+        killed_by,
+        usage,
     });
 
-    if exit_code == Some(0) && !matches!(child.state, supervisor::ChildState::Stopping { .. }) {
+    if child.origin == supervisor::Origin::Existing && pending_action.is_none() {
+        // Nothing to restart it with.
         child.state = supervisor::ChildState::Completed;
         child.pid = None;
         child.pgid = None;
-        child.backoff.restart_at = None;
         let _ = tx.send(MonitorEvent::Completed {
             index: child.index,
             cmd: child.command.clone(),
@@ -707,16 +1825,87 @@ fn handle_child_exit(
         return;
     }
 
-    child.state = supervisor::ChildState::Stopped;
+    match pending_action {
+        Some(supervisor::PendingAction::Restart) if allow_restart => {
+            child.state = supervisor::ChildState::Restarting;
+            child.pid = None;
+            child.pgid = None;
+            child.backoff.last_delay = Duration::ZERO;
+            child.backoff.restart_at = Some(Instant::now());
+            return;
+        }
+        Some(_) => {
+            child.state = supervisor::ChildState::Stopped;
+            child.pid = None;
+            child.pgid = None;
+            child.backoff.restart_at = None;
+            let _ = tx.send(MonitorEvent::Stopped {
+                index: child.index,
+                cmd: child.command.clone(),
+            });
+            return;
+        }
+        None => {}
+    }
+
+    let policy = child.spec.restart.unwrap_or_default();
+    let restart = match policy {
+        supervisor::RestartPolicy::Always => true,
+        supervisor::RestartPolicy::OnFailure => exit_code != Some(0) || stopping,
+        supervisor::RestartPolicy::Never => false,
+        supervisor::RestartPolicy::UnlessStopped => {
+            stopping
+                || !signal.is_some_and(|signal| {
+                    matches!(signal, libc::SIGTERM | libc::SIGINT | libc::SIGHUP)
+                })
+        }
+    };
     child.pid = None;
     child.pgid = None;
+
+    if !restart || (!allow_restart && exit_code == Some(0)) {
+        child.backoff.restart_at = None;
+        if exit_code == Some(0) {
+            child.state = supervisor::ChildState::Completed;
+            let _ = tx.send(MonitorEvent::Completed {
+                index: child.index,
+                cmd: child.command.clone(),
+            });
+        } else if policy == supervisor::RestartPolicy::UnlessStopped {
+            child.state = supervisor::ChildState::Stopped;
+            let _ = tx.send(MonitorEvent::Stopped {
+                index: child.index,
+                cmd: child.command.clone(),
+            });
+        } else {
+            child.state = supervisor::ChildState::Failed;
+            let _ = tx.send(MonitorEvent::Failed {
+                index: child.index,
+                cmd: child.command.clone(),
+                restart_count: child.restart_count,
+            });
+        }
+        return;
+    }
+
+    child.state = supervisor::ChildState::Stopped;
     child.restart_count = child.restart_count.saturating_add(1);
 
+    let max_restarts = child.spec.max_restarts.unwrap_or(max_restarts);
     if allow_restart
         && child
             .backoff
             .should_restart(max_restarts, child.restart_count)
     {
+        let crash_loop = if stopping {
+            None
+        } else {
+            supervisor::record_crash(child)
+        };
+        if let Some(reason) = crash_loop {
+            quarantine_child(child, reason, tx);
+            return;
+        }
         let _ = child.backoff.schedule_restart();
         child.state = supervisor::ChildState::Restarting;
     } else {
@@ -729,3 +1918,77 @@ fn handle_child_exit(
         });
     }
 }
+
+/// Stop restarting a crash-looping child and write what is known about its
+/// crashes to a diagnostic bundle.
+fn quarantine_child(
+    child: &mut supervisor::ManagedChild,
+    reason: String,
+    tx: &mpsc::Sender<MonitorEvent>,
+) {
+    child.state = supervisor::ChildState::Quarantined;
+    child.backoff.restart_at = None;
+    let bundle = match write_crash_bundle(child, &reason) {
+        Ok(path) => Some(path),
+        Err(error) => {
+            eprintln!("spm: {error}");
+            None
+        }
+    };
+    child.quarantine = Some(supervisor::Quarantine {
+        reason: reason.clone(),
+        bundle: bundle.clone(),
+    });
+    let _ = tx.send(MonitorEvent::Quarantined {
+        index: child.index,
+        cmd: child.command.clone(),
+        reason,
+        bundle,
+    });
+}
+
+/// `/tmp/spm-crashloop-<spm pid>-<index>-<name>.json`: the command, the
+/// recent exits and the last lines of output.
+fn write_crash_bundle(child: &supervisor::ManagedChild, reason: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(format!(
+        "/tmp/spm-crashloop-{}-{}-{}.json",
+        std::process::id(),
+        child.index,
+        child.name
+    ));
+    let exits: Vec<String> = child
+        .crash_history
+        .iter()
+        .map(|exit| {
+            let exit_code = exit
+                .exit_code
+                .map(|code| code.to_string())
+                .unwrap_or("null".into());
+            let signal = exit
+                .signal
+                .map(|signal| signal.to_string())
+                .unwrap_or("null".into());
+            format!(
+                r#"{{"secs_ago":{:.1},"exit_code":{exit_code},"signal":{signal}}}"#,
+                exit.exited_at.elapsed().as_secs_f64()
+            )
+        })
+        .collect();
+    let log: Vec<String> = supervisor::recent_output(child, child.spec.crash_loop.log_lines)
+        .iter()
+        .map(|line| format!("\"{}\"", escape_json(line)))
+        .collect();
+    let json = format!(
+        r#"{{"ts":"{}","name":"{}","command":"{}","reason":"{}","restart_count":{},"exits":[{}],"log":[{}]}}"#,
+        chrono_like_timestamp(),
+        escape_json(&child.name),
+        escape_json(&child.command),
+        escape_json(reason),
+        child.restart_count,
+        exits.join(","),
+        log.join(",")
+    );
+    std::fs::write(&path, json + "\n")
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(path)
+}
@@ -1,12 +1,38 @@
+use serde::Deserialize;
+
+use crate::supervisor::{ChildState, ManagedChild};
+
+/// Children below this USS are only picked when nothing bigger is left.
+const MIN_KILL_USS: u64 = 10_000_000;
+
 #[derive(Clone, Debug)]
 pub struct ProtectionPolicy {
     pub self_pid: u32,
+    pub strategy: VictimStrategy,
+}
+
+/// How the guard ranks eligible children when it has to kill one.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum VictimStrategy {
+    #[default]
+    LargestUss,
+    /// Highest USS growth rate over the last few ticks.
+    FastestGrowing,
+    MostRecent,
+    /// Lowest `priority` first, the largest USS among equal priorities.
+    LowestPriority,
+    /// Share of the candidates' projected USS, in points out of 1000, minus
+    /// `priority` — the same shape as the kernel's oom_score with
+    /// oom_score_adj.
+    Weighted,
 }
 
 impl ProtectionPolicy {
     pub fn new() -> Self {
         Self {
             self_pid: std::process::id(),
+            strategy: VictimStrategy::default(),
         }
     }
 
@@ -14,38 +40,79 @@ impl ProtectionPolicy {
         pid == 1 || pid == self.self_pid
     }
 
-    pub fn select_victim(&self, managed: &[crate::supervisor::ManagedChild]) -> Option<usize> {
-        const MIN_KILL_USS: u64 = 10_000_000;
-
-        let eligible = || {
-            managed
-                .iter()
-                .enumerate()
-                .filter(|(_, child)| child.state == crate::supervisor::ChildState::Running)
-                .filter(|(_, child)| {
-                    child
-                        .pid
-                        .map(|pid| !self.is_protected(pid))
-                        .unwrap_or(false)
-                })
-        };
+    pub fn select_victim(&self, managed: &[ManagedChild]) -> Option<usize> {
+        let eligible: Vec<(usize, &ManagedChild)> = managed
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| child.state == ChildState::Running)
+            .filter(|(_, child)| !child.spec.protected)
+            .filter(|(_, child)| {
+                child
+                    .pid
+                    .map(|pid| !self.is_protected(pid))
+                    .unwrap_or(false)
+            })
+            .collect();
 
-        let result = eligible()
+        let large: Vec<(usize, &ManagedChild)> = eligible
+            .iter()
+            .copied()
             .filter(|(_, child)| child.total_uss >= MIN_KILL_USS)
-            .max_by(|(_, left), (_, right)| {
-                left.total_uss
-                    .cmp(&right.total_uss)
-                    .then_with(|| left.pid.unwrap_or(0).cmp(&right.pid.unwrap_or(0)))
-                    .then_with(|| left.index.cmp(&right.index))
-            })
-            .map(|(index, _)| index);
+            .collect();
 
-        if result.is_some() {
-            return result;
-        }
+        self.rank(&large).or_else(|| self.rank(&eligible))
+    }
+
+    fn rank(&self, candidates: &[(usize, &ManagedChild)]) -> Option<usize> {
+        let by_uss = |left: &ManagedChild, right: &ManagedChild| {
+            left.total_uss
+                .cmp(&right.total_uss)
+                .then_with(|| left.pid.unwrap_or(0).cmp(&right.pid.unwrap_or(0)))
+                .then_with(|| left.index.cmp(&right.index))
+        };
+        let total: f64 = candidates
+            .iter()
+            .map(|(_, child)| projected_uss(child))
+            .sum();
+        let score = |child: &ManagedChild| {
+            let share = if total > 0.0 {
+                projected_uss(child) / total * 1000.0
+            } else {
+                0.0
+            };
+            share - child.spec.priority as f64
+        };
 
-        eligible()
-            .max_by_key(|(_, child)| child.total_uss)
-            .map(|(index, _)| index)
+        let by_strategy = |left: &ManagedChild, right: &ManagedChild| match self.strategy {
+            VictimStrategy::LargestUss => by_uss(left, right),
+            VictimStrategy::FastestGrowing => left
+                .growth_rate
+                .unwrap_or(0.0)
+                .total_cmp(&right.growth_rate.unwrap_or(0.0))
+                .then_with(|| by_uss(left, right)),
+            VictimStrategy::MostRecent => left
+                .started_at
+                .cmp(&right.started_at)
+                .then_with(|| by_uss(left, right)),
+            VictimStrategy::LowestPriority => right
+                .spec
+                .priority
+                .cmp(&left.spec.priority)
+                .then_with(|| by_uss(left, right)),
+            VictimStrategy::Weighted => score(left)
+                .total_cmp(&score(right))
+                .then_with(|| by_uss(left, right)),
+        };
+
+        candidates
+            .iter()
+            .max_by(|(_, left), (_, right)| by_strategy(left, right))
+            .map(|(index, _)| *index)
     }
 }
+
+/// USS a minute from now at the child's current growth rate.
+fn projected_uss(child: &ManagedChild) -> f64 {
+    let growth_bytes = child.growth_rate.unwrap_or(0.0).max(0.0) * 1024.0 * 1024.0;
+    child.total_uss as f64 + growth_bytes
+}
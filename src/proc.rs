@@ -20,12 +20,26 @@ static CPU_SAMPLES: OnceLock<Mutex<HashMap<u32, CpuSample>>> = OnceLock::new();
 static DISK_SAMPLES: OnceLock<Mutex<HashMap<u32, DiskSample>>> = OnceLock::new();
 
 pub fn collect_processes() -> Vec<ProcessSnapshot> {
-    let mut processes = Vec::new();
     let entries = match fs::read_dir("/proc") {
         Ok(entries) => entries,
-        Err(_) => return processes,
+        Err(_) => return Vec::new(),
     };
+    let pids: Vec<u32> = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .collect();
+    collect(&pids, true)
+}
 
+/// Snapshots of only `pids`, for callers that know which processes they
+/// watch.
+pub fn collect_pids(pids: &[u32]) -> Vec<ProcessSnapshot> {
+    collect(pids, false)
+}
+
+/// With `all`, `pids` are every process, and the samples of any other are
+/// forgotten; otherwise only those of the `pids` that are gone.
+fn collect(pids: &[u32], all: bool) -> Vec<ProcessSnapshot> {
+    let mut processes = Vec::new();
     let now = Instant::now();
     let ticks_per_second = ticks_per_second();
     let page_size = page_size_bytes();
@@ -47,20 +61,7 @@ pub fn collect_processes() -> Vec<ProcessSnapshot> {
         Err(_) => return processes,
     };
 
-    for entry in entries {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(_) => continue,
-        };
-        let pid = match entry
-            .file_name()
-            .to_str()
-            .and_then(|value| value.parse::<u32>().ok())
-        {
-            Some(pid) => pid,
-            None => continue,
-        };
-
+    for &pid in pids {
         let stat_path = format!("/proc/{pid}/stat");
         let stat_content = match read_to_string(&stat_path) {
             Some(content) => content,
@@ -110,8 +111,15 @@ pub fn collect_processes() -> Vec<ProcessSnapshot> {
         seen_pids.insert(pid);
     }
 
-    cpu_samples.retain(|pid, _| seen_pids.contains(pid));
-    disk_samples.retain(|pid, _| seen_pids.contains(pid));
+    if all {
+        cpu_samples.retain(|pid, _| seen_pids.contains(pid));
+        disk_samples.retain(|pid, _| seen_pids.contains(pid));
+    } else {
+        for pid in pids.iter().filter(|pid| !seen_pids.contains(pid)) {
+            cpu_samples.remove(pid);
+            disk_samples.remove(pid);
+        }
+    }
     processes
 }
 
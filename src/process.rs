@@ -1,26 +1,210 @@
-pub fn terminate_process(pid: u32) -> Result<String, String> {
-    let result = unsafe { libc::kill(pid as i32, libc::SIGTERM) };
-    if result != 0 {
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// How long a finished kill stays visible in the UI.
+const FINISHED_KILL_TTL: Duration = Duration::from_secs(5);
+
+/// Kills running in the background, by the pid they were started for.
+static KILLS: OnceLock<Mutex<HashMap<u32, KillHandle>>> = OnceLock::new();
+
+/// Where a termination running in the background stands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KillStage {
+    /// The child's pre-stop hook is running.
+    PreStop,
+    /// The stop signal was sent; waiting out the grace period.
+    Signalled,
+    /// The grace period ran out and SIGKILL was sent.
+    Escalated,
+    /// Nothing it was sent to is running any more.
+    Reaped,
+}
+
+impl KillStage {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            KillStage::PreStop => "pre_stop",
+            KillStage::Signalled => "signalled",
+            KillStage::Escalated => "escalated",
+            KillStage::Reaped => "reaped",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pre_stop" => Some(KillStage::PreStop),
+            "signalled" => Some(KillStage::Signalled),
+            "escalated" => Some(KillStage::Escalated),
+            "reaped" => Some(KillStage::Reaped),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct KillProgress {
+    pub stage: KillStage,
+    pub stop: StopSignal,
+    /// When the stop signal escalates to SIGKILL, once it has been sent.
+    pub deadline: Option<Instant>,
+    pub finished_at: Option<Instant>,
+    pub outcome: Option<Result<String, String>>,
+}
+
+/// Shared between the thread doing the kill and whoever displays it.
+pub type KillHandle = Arc<Mutex<KillProgress>>;
+
+/// One of the blocking kills below, ready to run on a kill thread.
+pub type KillJob = Box<dyn FnOnce(&KillHandle) -> Result<String, String> + Send>;
+
+impl KillProgress {
+    pub fn start(stop: StopSignal) -> KillHandle {
+        Arc::new(Mutex::new(KillProgress {
+            stage: KillStage::Signalled,
+            stop,
+            deadline: None,
+            finished_at: None,
+            outcome: None,
+        }))
+    }
+
+    /// `SIGTERM 2s`, `SIGKILL`: short enough for a table cell.
+    pub fn label(&self) -> String {
+        match self.stage {
+            KillStage::PreStop => "pre-stop".to_string(),
+            KillStage::Signalled => {
+                let remaining = self
+                    .deadline
+                    .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+                    .unwrap_or(self.stop.timeout);
+                format!(
+                    "{} {}s",
+                    crate::control::signal_name(self.stop.signal),
+                    remaining.as_secs_f64().ceil() as u64
+                )
+            }
+            KillStage::Escalated => "SIGKILL".to_string(),
+            KillStage::Reaped => "reaped".to_string(),
+        }
+    }
+}
+
+pub fn advance(progress: &KillHandle, stage: KillStage) {
+    if let Ok(mut progress) = progress.lock() {
+        if stage == KillStage::Signalled {
+            progress.deadline = Some(Instant::now() + progress.stop.timeout);
+        }
+        progress.stage = stage;
+    }
+}
+
+/// What a finished kill reported. Taken once, so it is shown only once.
+pub fn take_outcome(progress: &KillHandle) -> Option<Result<String, String>> {
+    progress.lock().ok()?.outcome.take()
+}
+
+pub fn is_finished(progress: &KillHandle) -> bool {
+    progress
+        .lock()
+        .map(|progress| progress.finished_at.is_some())
+        .unwrap_or(true)
+}
+
+/// Run `kill` on its own thread so neither the TUI nor the monitor waits out
+/// a grace period, and track it under `pid`. A kill already running for `pid`
+/// is returned instead of starting another, unless this one is an emergency.
+pub fn kill_in_background(
+    pid: u32,
+    stop: StopSignal,
+    emergency: bool,
+    kill: impl FnOnce(&KillHandle) -> Result<String, String> + Send + 'static,
+) -> KillHandle {
+    let kills = KILLS.get_or_init(|| Mutex::new(HashMap::new()));
+    if !emergency {
+        if let Some(running) = kills
+            .lock()
+            .ok()
+            .and_then(|kills| kills.get(&pid).cloned())
+            .filter(|running| !is_finished(running))
+        {
+            return running;
+        }
+    }
+    let progress = KillProgress::start(stop);
+    if let Ok(mut kills) = kills.lock() {
+        kills.retain(|_, kill| {
+            kill.lock()
+                .map(|kill| {
+                    kill.finished_at
+                        .is_none_or(|at| at.elapsed() < FINISHED_KILL_TTL)
+                })
+                .unwrap_or(false)
+        });
+        kills.insert(pid, progress.clone());
+    }
+    let thread_progress = progress.clone();
+    std::thread::spawn(move || {
+        let outcome = kill(&thread_progress);
+        if let Ok(mut progress) = thread_progress.lock() {
+            progress.stage = KillStage::Reaped;
+            progress.finished_at = Some(Instant::now());
+            progress.outcome = Some(outcome);
+        }
+    });
+    progress
+}
+
+/// The kill running, or recently finished, for `pid`.
+pub fn kill_progress(pid: u32) -> Option<KillProgress> {
+    let progress = KILLS.get()?.lock().ok()?.get(&pid)?.lock().ok()?.clone();
+    match progress.finished_at {
+        Some(at) if at.elapsed() >= FINISHED_KILL_TTL => None,
+        _ => Some(progress),
+    }
+}
+
+/// SIGTERM a single process in the background, SIGKILL after 3 seconds.
+pub fn terminate_process(pid: u32) -> Result<KillHandle, String> {
+    if unsafe { libc::kill(pid as i32, 0) } != 0 {
         return Err(format!("Failed to send SIGTERM to {}", pid));
     }
+    Ok(kill_in_background(
+        pid,
+        StopSignal::default(),
+        false,
+        move |progress| kill_pids(&[pid], StopSignal::default(), false, progress),
+    ))
+}
 
-    std::thread::sleep(std::time::Duration::from_secs(3));
+/// The signal a process is asked to stop with, and how long it gets to exit
+/// before SIGKILL.
+#[derive(Clone, Copy, Debug)]
+pub struct StopSignal {
+    pub signal: i32,
+    pub timeout: Duration,
+}
 
-    let check = unsafe { libc::kill(pid as i32, 0) };
-    if check == 0 {
-        unsafe {
-            libc::kill(pid as i32, libc::SIGKILL);
+impl Default for StopSignal {
+    fn default() -> Self {
+        Self {
+            signal: libc::SIGTERM,
+            timeout: Duration::from_secs(3),
         }
-        Ok(format!("Process {} force-killed (SIGKILL)", pid))
-    } else {
-        Ok(format!("Process {} terminated (SIGTERM)", pid))
     }
 }
 
-pub fn kill_process_group(pgid: u32, emergency: bool) -> Result<String, String> {
+pub fn kill_process_group(
+    pgid: u32,
+    stop: StopSignal,
+    emergency: bool,
+    progress: &KillHandle,
+) -> Result<String, String> {
     let neg_pgid = -(pgid as i32);
 
     if emergency {
+        advance(progress, KillStage::Escalated);
         let result = unsafe { libc::kill(neg_pgid, libc::SIGKILL) };
         if result != 0 {
             return Err(format!("Failed to SIGKILL process group {pgid}"));
@@ -28,22 +212,152 @@ pub fn kill_process_group(pgid: u32, emergency: bool) -> Result<String, String>
         return Ok(format!("Process group {pgid} emergency-killed (SIGKILL)"));
     }
 
-    let result = unsafe { libc::kill(neg_pgid, libc::SIGTERM) };
+    let name = crate::control::signal_name(stop.signal);
+    let result = unsafe { libc::kill(neg_pgid, stop.signal) };
     if result != 0 {
-        return Err(format!("Failed to SIGTERM process group {pgid}"));
+        return Err(format!("Failed to {name} process group {pgid}"));
     }
+    advance(progress, KillStage::Signalled);
 
-    std::thread::sleep(std::time::Duration::from_secs(3));
-
-    let check = unsafe { libc::kill(neg_pgid, 0) };
-    if check == 0 {
+    if wait_for_exit(stop.timeout, || group_alive(pgid)) {
+        Ok(format!("Process group {pgid} terminated ({name})"))
+    } else {
+        advance(progress, KillStage::Escalated);
         unsafe {
             libc::kill(neg_pgid, libc::SIGKILL);
         }
         Ok(format!(
-            "Process group {pgid} force-killed (SIGKILL after SIGTERM)"
+            "Process group {pgid} force-killed (SIGKILL after {name})"
         ))
+    }
+}
+
+/// Like `kill_process_group`, for processes without a group of their own:
+/// an adopted process that does not lead its group, and what it started.
+pub fn kill_pids(
+    pids: &[u32],
+    stop: StopSignal,
+    emergency: bool,
+    progress: &KillHandle,
+) -> Result<String, String> {
+    let send = |signal: i32| {
+        for pid in pids {
+            unsafe {
+                libc::kill(*pid as i32, signal);
+            }
+        }
+    };
+
+    if emergency {
+        advance(progress, KillStage::Escalated);
+        send(libc::SIGKILL);
+        return Ok(format!(
+            "{} processes emergency-killed (SIGKILL)",
+            pids.len()
+        ));
+    }
+
+    let name = crate::control::signal_name(stop.signal);
+    send(stop.signal);
+    advance(progress, KillStage::Signalled);
+
+    if wait_for_exit(stop.timeout, || {
+        pids.iter().any(|pid| crate::supervisor::is_running(*pid))
+    }) {
+        Ok(format!("{} processes terminated ({name})", pids.len()))
     } else {
-        Ok(format!("Process group {pgid} terminated (SIGTERM)"))
+        advance(progress, KillStage::Escalated);
+        send(libc::SIGKILL);
+        Ok(format!(
+            "{} processes force-killed (SIGKILL after {name})",
+            pids.len()
+        ))
+    }
+}
+
+/// Like `kill_process_group`, but for every member of a child's cgroup, so
+/// processes that left the group with setsid are caught too. The SIGKILL goes
+/// through `cgroup.kill`.
+pub fn kill_cgroup(
+    cgroup: &Path,
+    stop: StopSignal,
+    emergency: bool,
+    progress: &KillHandle,
+) -> Result<String, String> {
+    let name = cgroup.display();
+
+    if emergency {
+        advance(progress, KillStage::Escalated);
+        force_kill_cgroup(cgroup);
+        return Ok(format!("Cgroup {name} emergency-killed (SIGKILL)"));
+    }
+
+    let signal_name = crate::control::signal_name(stop.signal);
+    for pid in crate::cgroup::cgroup_pids(cgroup) {
+        unsafe {
+            libc::kill(pid as i32, stop.signal);
+        }
+    }
+    advance(progress, KillStage::Signalled);
+
+    if wait_for_exit(stop.timeout, || {
+        !crate::cgroup::cgroup_pids(cgroup).is_empty()
+    }) {
+        Ok(format!("Cgroup {name} terminated ({signal_name})"))
+    } else {
+        advance(progress, KillStage::Escalated);
+        force_kill_cgroup(cgroup);
+        Ok(format!(
+            "Cgroup {name} force-killed (SIGKILL after {signal_name})"
+        ))
+    }
+}
+
+/// Poll `alive` until it turns false or `timeout` passes. Returns whether the
+/// process exited in time.
+fn wait_for_exit(timeout: Duration, alive: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + timeout;
+    while alive() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    true
+}
+
+/// Whether any process of the group is still running. Zombies do not count:
+/// the group leader stays one until the monitor reaps it.
+fn group_alive(pgid: u32) -> bool {
+    if unsafe { libc::kill(-(pgid as i32), 0) } != 0 {
+        return false;
+    }
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return true;
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .any(|pid| {
+            let Ok(stat) = std::fs::read_to_string(format!("/proc/{pid}/stat")) else {
+                return false;
+            };
+            let Some(end) = stat.rfind(')') else {
+                return false;
+            };
+            let fields: Vec<&str> = stat[end + 1..].split_whitespace().collect();
+            fields.first() != Some(&"Z") && fields.get(2) == Some(&pgid.to_string().as_str())
+        })
+}
+
+fn force_kill_cgroup(cgroup: &Path) {
+    if crate::cgroup::write_cgroup_file(cgroup, "cgroup.kill", "1").is_ok() {
+        return;
+    }
+    // cgroup.kill needs Linux 5.14.
+    for pid in crate::cgroup::cgroup_pids(cgroup) {
+        unsafe {
+            libc::kill(pid as i32, libc::SIGKILL);
+        }
     }
 }
@@ -0,0 +1,508 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::recording::{RecordingManager, RecordingMetadata, RecordingSnapshot};
+use crate::supervisor::{self, ChildSpec, ManagedChild, ProcessTree, RunUsage, Rusage};
+use crate::ui::format_bytes;
+use crate::{cgroup, monitor, proc};
+
+/// Rows of the text timeline; longer runs show the peaks of each stretch.
+const TIMELINE_ROWS: usize = 20;
+/// How often the command is checked for having exited between samples.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long output the command's leftovers still hold open may delay the
+/// summary.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Signal spm got and has not passed on to the command yet, or 0.
+static PENDING_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn forward_handler(sig: libc::c_int) {
+    PENDING_SIGNAL.store(sig, Ordering::SeqCst);
+}
+
+#[derive(Clone, Debug)]
+pub struct ProfileOptions {
+    pub interval: Duration,
+    pub json: bool,
+    /// Write the summary here instead of to stderr.
+    pub output: Option<PathBuf>,
+}
+
+/// The command's whole process tree at one tick.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProfileSample {
+    pub elapsed_secs: f64,
+    pub processes: u32,
+    pub uss: u64,
+    pub pss: u64,
+    pub rss: u64,
+    pub cpu_percent: f64,
+    /// Bytes per second since the previous sample.
+    pub read_rate: f64,
+    pub write_rate: f64,
+}
+
+struct Profiler {
+    started: Instant,
+    samples: Vec<ProfileSample>,
+    last_sample: Option<Instant>,
+    /// Cumulative `read_bytes`/`write_bytes` of each process at its last
+    /// sample.
+    disk_seen: HashMap<u32, (u64, u64)>,
+    read_bytes: u64,
+    write_bytes: u64,
+    recording: RecordingManager,
+}
+
+impl Profiler {
+    fn new(started: Instant) -> Self {
+        Self {
+            started,
+            samples: Vec::new(),
+            last_sample: None,
+            disk_seen: HashMap::new(),
+            read_bytes: 0,
+            write_bytes: 0,
+            recording: RecordingManager::new().whole_run(),
+        }
+    }
+
+    fn due(&self, interval: Duration) -> bool {
+        self.last_sample
+            .is_none_or(|sampled_at| sampled_at.elapsed() >= interval)
+    }
+
+    fn sample(&mut self, child: &mut ManagedChild) {
+        let now = Instant::now();
+        let since_last = self
+            .last_sample
+            .map(|sampled_at| now.duration_since(sampled_at).as_secs_f64())
+            .unwrap_or_default();
+        self.last_sample = Some(now);
+
+        supervisor::sample_child(child, &ProcessTree::scan());
+        let processes = proc::collect_pids(&child.tree_pids);
+
+        // Processes seen for the first time count everything they did so far,
+        // as they started after the previous sample.
+        let mut read = 0u64;
+        let mut write = 0u64;
+        for pid in &child.tree_pids {
+            let (read_bytes, write_bytes) = proc::read_disk_io(*pid);
+            let (read_before, write_before) = self
+                .disk_seen
+                .insert(*pid, (read_bytes, write_bytes))
+                .unwrap_or_default();
+            read += read_bytes.saturating_sub(read_before);
+            write += write_bytes.saturating_sub(write_before);
+        }
+        self.disk_seen
+            .retain(|pid, _| child.tree_pids.contains(pid));
+        self.read_bytes += read;
+        self.write_bytes += write;
+        let rate = |bytes: u64| {
+            if since_last > 0.0 {
+                bytes as f64 / since_last
+            } else {
+                0.0
+            }
+        };
+
+        self.samples.push(ProfileSample {
+            elapsed_secs: now.duration_since(self.started).as_secs_f64(),
+            processes: child.process_count,
+            uss: child.total_uss,
+            pss: child.total_pss,
+            rss: child.total_rss,
+            // The cgroup also counts processes that came and went between
+            // samples.
+            cpu_percent: child
+                .cpu_percent
+                .unwrap_or_else(|| processes.iter().map(|process| process.cpu_percent).sum()),
+            read_rate: rate(read),
+            write_rate: rate(write),
+        });
+
+        let mut pod_memory = cgroup::read_pod_memory();
+        pod_memory.rss_sum = child.total_rss;
+        self.recording.add_snapshot(RecordingSnapshot {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            processes,
+            pod_memory,
+            cpu_cores: cgroup::read_cpu_quota().cores,
+        });
+    }
+}
+
+/// What one profiled run used.
+#[derive(Clone, Debug)]
+pub struct ProfileSummary {
+    pub command: String,
+    pub pid: u32,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub usage: RunUsage,
+    pub interval: Duration,
+    pub samples: Vec<ProfileSample>,
+    pub peak: ProfileSample,
+    pub average: ProfileSample,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub recording: Option<RecordingMetadata>,
+}
+
+impl ProfileSummary {
+    /// Shell-style status: the exit code, or 128 + the signal.
+    pub fn exit_status(&self) -> i32 {
+        match (self.exit_code, self.signal) {
+            (Some(code), _) => code,
+            (None, Some(signal)) => 128 + signal,
+            (None, None) => 0,
+        }
+    }
+}
+
+/// Run `spec` to completion, sampling its tree every `options.interval`,
+/// and write the summary. Returns the command's exit status.
+pub fn run(spec: ChildSpec, options: &ProfileOptions) -> Result<i32, String> {
+    // The command gets its own session, so the terminal's signals only reach
+    // spm; pass them on and let the command decide when to stop.
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP, libc::SIGQUIT] {
+        unsafe {
+            libc::signal(signal, forward_handler as *const () as libc::sighandler_t);
+        }
+    }
+
+    let mut child = ManagedChild::from_spec(0, spec);
+    let started = Instant::now();
+    for message in supervisor::spawn_child(&mut child, true)?.warnings {
+        eprintln!("[spm] Warning: {message}");
+    }
+    let pid = child.pid.ok_or("command did not start")?;
+    let mut profiler = Profiler::new(started);
+
+    let (status, rusage) = loop {
+        let signal = PENDING_SIGNAL.swap(0, Ordering::SeqCst);
+        if signal != 0 {
+            let _ = supervisor::signal_child(&child, signal);
+        }
+        if profiler.due(options.interval) {
+            profiler.sample(&mut child);
+        }
+
+        let mut status: i32 = 0;
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::wait4(pid as i32, &mut status, libc::WNOHANG, &mut rusage) };
+        if result < 0 {
+            return Err(format!(
+                "waiting for {pid}: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        if result > 0 && (libc::WIFEXITED(status) || libc::WIFSIGNALED(status)) {
+            break (status, rusage);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    };
+    let wall_secs = started.elapsed().as_secs_f64();
+
+    // Let the output readers finish, so the summary comes after the last
+    // line of the command's output.
+    let drain_started = Instant::now();
+    while Arc::strong_count(&child.output_tail) > 1
+        && drain_started.elapsed() < OUTPUT_DRAIN_TIMEOUT
+    {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    supervisor::release_child_cgroup(&child);
+    cgroup::remove_child_cgroups();
+
+    let recording = profiler.recording.save_recording(pid, child.name.clone());
+    let summary = summarize(
+        &child,
+        pid,
+        status,
+        RunUsage {
+            rusage: Some(Rusage::from(&rusage)),
+            wall_secs: Some(wall_secs),
+            peak_uss: child.peak_uss,
+        },
+        options.interval,
+        profiler,
+        recording,
+    );
+
+    let report = if options.json {
+        format!("{}\n", summary_to_json(&summary))
+    } else {
+        format_summary(&summary)
+    };
+    match options.output {
+        Some(ref path) => std::fs::write(path, report)
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))?,
+        None => {
+            let _ = std::io::stderr().lock().write_all(report.as_bytes());
+        }
+    }
+    Ok(summary.exit_status())
+}
+
+fn summarize(
+    child: &ManagedChild,
+    pid: u32,
+    status: i32,
+    usage: RunUsage,
+    interval: Duration,
+    profiler: Profiler,
+    recording: Option<RecordingMetadata>,
+) -> ProfileSummary {
+    let (exit_code, signal) = if libc::WIFEXITED(status) {
+        (Some(libc::WEXITSTATUS(status)), None)
+    } else {
+        (None, Some(libc::WTERMSIG(status)))
+    };
+    let samples = profiler.samples;
+    let peak = samples
+        .iter()
+        .fold(ProfileSample::default(), |peak, sample| ProfileSample {
+            elapsed_secs: peak.elapsed_secs.max(sample.elapsed_secs),
+            processes: peak.processes.max(sample.processes),
+            uss: peak.uss.max(sample.uss),
+            pss: peak.pss.max(sample.pss),
+            rss: peak.rss.max(sample.rss),
+            cpu_percent: peak.cpu_percent.max(sample.cpu_percent),
+            read_rate: peak.read_rate.max(sample.read_rate),
+            write_rate: peak.write_rate.max(sample.write_rate),
+        });
+    let count = samples.len().max(1);
+    let mean =
+        |value: fn(&ProfileSample) -> f64| samples.iter().map(value).sum::<f64>() / count as f64;
+    let average = ProfileSample {
+        elapsed_secs: usage.wall_secs.unwrap_or_default(),
+        processes: mean(|sample| sample.processes as f64).round() as u32,
+        uss: mean(|sample| sample.uss as f64) as u64,
+        pss: mean(|sample| sample.pss as f64) as u64,
+        rss: mean(|sample| sample.rss as f64) as u64,
+        cpu_percent: mean(|sample| sample.cpu_percent),
+        read_rate: mean(|sample| sample.read_rate),
+        write_rate: mean(|sample| sample.write_rate),
+    };
+    ProfileSummary {
+        command: child.command.clone(),
+        pid,
+        exit_code,
+        signal,
+        usage,
+        interval,
+        peak,
+        average,
+        samples,
+        read_bytes: profiler.read_bytes,
+        write_bytes: profiler.write_bytes,
+        recording,
+    }
+}
+
+/// At most `TIMELINE_ROWS` rows: each one the peaks of a stretch of
+/// consecutive samples, stamped with when the stretch began.
+fn timeline(samples: &[ProfileSample]) -> Vec<ProfileSample> {
+    let per_row = samples.len().div_ceil(TIMELINE_ROWS).max(1);
+    samples
+        .chunks(per_row)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .copied()
+                .reduce(|row, sample| ProfileSample {
+                    elapsed_secs: row.elapsed_secs,
+                    processes: row.processes.max(sample.processes),
+                    uss: row.uss.max(sample.uss),
+                    pss: row.pss.max(sample.pss),
+                    rss: row.rss.max(sample.rss),
+                    cpu_percent: row.cpu_percent.max(sample.cpu_percent),
+                    read_rate: row.read_rate.max(sample.read_rate),
+                    write_rate: row.write_rate.max(sample.write_rate),
+                })
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+pub fn format_summary(summary: &ProfileSummary) -> String {
+    let mut lines = vec![format!("[spm] Profile of \"{}\"", summary.command)];
+    let status = match (summary.exit_code, summary.signal) {
+        (Some(code), _) => format!("exited with {code}"),
+        (None, Some(signal)) => format!("killed by {}", crate::control::signal_name(signal)),
+        (None, None) => "unknown".to_string(),
+    };
+    lines.push(format!("  Exit status:                   {status}"));
+    lines.push(format!(
+        "  Elapsed (wall clock):          {:.2}s",
+        summary.usage.wall_secs.unwrap_or_default()
+    ));
+    if let Some(rusage) = summary.usage.rusage {
+        lines.push(format!(
+            "  User / system CPU time:        {:.2}s / {:.2}s",
+            rusage.user_secs, rusage.sys_secs
+        ));
+        lines.push(format!(
+            "  Max RSS (kernel):              {}",
+            format_bytes(rusage.max_rss)
+        ));
+        lines.push(format!(
+            "  Page faults (minor / major):   {} / {}",
+            rusage.minor_faults, rusage.major_faults
+        ));
+        lines.push(format!(
+            "  Context switches (vol / inv):  {} / {}",
+            rusage.voluntary_switches, rusage.involuntary_switches
+        ));
+    }
+    lines.push(format!(
+        "  Disk read / written:           {} / {}",
+        format_bytes(summary.read_bytes),
+        format_bytes(summary.write_bytes)
+    ));
+    lines.push(String::new());
+    lines.push(format!("  {:<12}{:>12}{:>12}", "", "peak", "average"));
+    let (peak, average) = (&summary.peak, &summary.average);
+    lines.push(format!(
+        "  {:<12}{:>12}{:>12}",
+        "Processes", peak.processes, average.processes
+    ));
+    for (label, peak, average) in [
+        ("USS", peak.uss, average.uss),
+        ("PSS", peak.pss, average.pss),
+        ("RSS", peak.rss, average.rss),
+    ] {
+        lines.push(format!(
+            "  {label:<12}{:>12}{:>12}",
+            format_bytes(peak),
+            format_bytes(average)
+        ));
+    }
+    lines.push(format!(
+        "  {:<12}{:>11.1}%{:>11.1}%",
+        "CPU", peak.cpu_percent, average.cpu_percent
+    ));
+    lines.push(format!(
+        "  {:<12}{:>12}{:>12}",
+        "Disk read/s",
+        format_bytes(peak.read_rate as u64),
+        format_bytes(average.read_rate as u64)
+    ));
+    lines.push(format!(
+        "  {:<12}{:>12}{:>12}",
+        "Disk write/s",
+        format_bytes(peak.write_rate as u64),
+        format_bytes(average.write_rate as u64)
+    ));
+
+    let rows = timeline(&summary.samples);
+    if !rows.is_empty() {
+        lines.push(String::new());
+        lines.push(if rows.len() < summary.samples.len() {
+            format!(
+                "  Timeline (peaks of every {} samples, {:.1}s apart):",
+                summary.samples.len().div_ceil(rows.len()),
+                summary.interval.as_secs_f64()
+            )
+        } else {
+            format!("  Timeline (every {:.1}s):", summary.interval.as_secs_f64())
+        });
+        lines.push(format!(
+            "  {:>8}{:>7}{:>11}{:>11}{:>11}{:>8}{:>11}{:>11}",
+            "time", "procs", "USS", "PSS", "RSS", "CPU", "read/s", "write/s"
+        ));
+        for row in rows {
+            lines.push(format!(
+                "  {:>7.1}s{:>7}{:>11}{:>11}{:>11}{:>7.0}%{:>11}{:>11}",
+                row.elapsed_secs,
+                row.processes,
+                format_bytes(row.uss),
+                format_bytes(row.pss),
+                format_bytes(row.rss),
+                row.cpu_percent,
+                format_bytes(row.read_rate as u64),
+                format_bytes(row.write_rate as u64)
+            ));
+        }
+    }
+
+    if let Some(ref recording) = summary.recording {
+        lines.push(String::new());
+        lines.push(format!(
+            "  Recording:                     {} ({} snapshots)",
+            recording.id, recording.snapshot_count
+        ));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+fn sample_to_json(sample: &ProfileSample) -> String {
+    format!(
+        "\"processes\":{},\"uss\":{},\"pss\":{},\"rss\":{},\"cpu_percent\":{:.1},\"read_rate\":{:.0},\"write_rate\":{:.0}",
+        sample.processes,
+        sample.uss,
+        sample.pss,
+        sample.rss,
+        sample.cpu_percent,
+        sample.read_rate,
+        sample.write_rate
+    )
+}
+
+pub fn summary_to_json(summary: &ProfileSummary) -> String {
+    let optional = |value: Option<i32>| {
+        value
+            .map(|value| value.to_string())
+            .unwrap_or("null".into())
+    };
+    let samples: Vec<String> = summary
+        .samples
+        .iter()
+        .map(|sample| {
+            format!(
+                "{{\"t\":{:.3},{}}}",
+                sample.elapsed_secs,
+                sample_to_json(sample)
+            )
+        })
+        .collect();
+    let recording = summary
+        .recording
+        .as_ref()
+        .map(|recording| {
+            format!(
+                "{{\"id\":\"{}\",\"path\":\"{}\",\"snapshots\":{}}}",
+                monitor::escape_json(&recording.id),
+                monitor::escape_json(&recording.file_path.to_string_lossy()),
+                recording.snapshot_count
+            )
+        })
+        .unwrap_or("null".into());
+    format!(
+        "{{\"command\":\"{}\",\"pid\":{},\"exit_code\":{},\"signal\":{},\"usage\":{},\"disk\":{{\"read_bytes\":{},\"write_bytes\":{}}},\"peak\":{{{}}},\"average\":{{{}}},\"interval_secs\":{:.3},\"samples\":[{}],\"recording\":{}}}",
+        monitor::escape_json(&summary.command),
+        summary.pid,
+        optional(summary.exit_code),
+        optional(summary.signal),
+        monitor::usage_to_json(&summary.usage),
+        summary.read_bytes,
+        summary.write_bytes,
+        sample_to_json(&summary.peak),
+        sample_to_json(&summary.average),
+        summary.interval.as_secs_f64(),
+        samples.join(","),
+        recording
+    )
+}
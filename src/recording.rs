@@ -9,7 +9,11 @@ use std::time::{Duration, Instant, SystemTime};
 use crate::app::{PodMemorySnapshot, ProcessSnapshot};
 
 const MAGIC: &[u8; 4] = b"SPMR";
-const VERSION: u8 = 1;
+const VERSION: u8 = 3;
+
+/// Snapshots a whole-run recording holds. Past that, every other one is
+/// dropped and later ones are taken half as often.
+const WHOLE_RUN_SNAPSHOTS: usize = 3600;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RecordingSnapshot {
@@ -36,6 +40,85 @@ pub struct Recording {
     pub snapshots: Vec<RecordingSnapshot>,
 }
 
+/// `PodMemorySnapshot` as written by version 1, before `working_set`.
+#[derive(Deserialize)]
+struct PodMemoryV1 {
+    cgroup_usage: u64,
+    cgroup_limit: Option<u64>,
+    rss_sum: u64,
+    terminator_threshold_percent: u8,
+}
+
+/// `PodMemorySnapshot` as written by version 2, before `pressure`.
+#[derive(Deserialize)]
+struct PodMemoryV2 {
+    cgroup_usage: u64,
+    working_set: u64,
+    cgroup_limit: Option<u64>,
+    rss_sum: u64,
+    terminator_threshold_percent: u8,
+}
+
+impl From<PodMemoryV1> for PodMemorySnapshot {
+    fn from(old: PodMemoryV1) -> Self {
+        // Page cache was not split out yet; the working set is at most usage.
+        PodMemorySnapshot {
+            cgroup_usage: old.cgroup_usage,
+            working_set: old.cgroup_usage,
+            cgroup_limit: old.cgroup_limit,
+            pressure: None,
+            rss_sum: old.rss_sum,
+            terminator_threshold_percent: old.terminator_threshold_percent,
+        }
+    }
+}
+
+impl From<PodMemoryV2> for PodMemorySnapshot {
+    fn from(old: PodMemoryV2) -> Self {
+        PodMemorySnapshot {
+            cgroup_usage: old.cgroup_usage,
+            working_set: old.working_set,
+            cgroup_limit: old.cgroup_limit,
+            pressure: None,
+            rss_sum: old.rss_sum,
+            terminator_threshold_percent: old.terminator_threshold_percent,
+        }
+    }
+}
+
+/// A recording from an older version, with its own pod memory layout.
+#[derive(Deserialize)]
+struct LegacyRecording<P> {
+    metadata: RecordingMetadata,
+    snapshots: Vec<LegacySnapshot<P>>,
+}
+
+#[derive(Deserialize)]
+struct LegacySnapshot<P> {
+    timestamp: u64,
+    processes: Vec<ProcessSnapshot>,
+    pod_memory: P,
+    cpu_cores: Option<f64>,
+}
+
+impl<P: Into<PodMemorySnapshot>> From<LegacyRecording<P>> for Recording {
+    fn from(old: LegacyRecording<P>) -> Self {
+        Recording {
+            metadata: old.metadata,
+            snapshots: old
+                .snapshots
+                .into_iter()
+                .map(|snapshot| RecordingSnapshot {
+                    timestamp: snapshot.timestamp,
+                    processes: snapshot.processes,
+                    pod_memory: snapshot.pod_memory.into(),
+                    cpu_cores: snapshot.cpu_cores,
+                })
+                .collect(),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct RecordingManager {
     buffer: VecDeque<RecordingSnapshot>,
@@ -43,6 +126,10 @@ pub struct RecordingManager {
     recordings_dir: PathBuf,
     last_saved_pids: HashMap<u32, Instant>,
     max_storage_bytes: u64,
+    /// Keep one of every `stride` snapshots offered, for whole-run
+    /// recordings; `None` keeps a rolling window.
+    stride: Option<usize>,
+    offered: usize,
 }
 
 impl RecordingManager {
@@ -62,20 +149,49 @@ impl RecordingManager {
             recordings_dir,
             last_saved_pids: HashMap::new(),
             max_storage_bytes: max_storage_mb * 1024 * 1024,
+            stride: None,
+            offered: 0,
         };
         manager.cleanup_old_recordings();
         manager.enforce_storage_cap();
         manager
     }
 
+    /// Cover the run from its start until the recording is saved, rather
+    /// than the moments before an exit, thinning it out as it grows.
+    pub fn whole_run(mut self) -> Self {
+        self.max_snapshots = WHOLE_RUN_SNAPSHOTS;
+        self.stride = Some(1);
+        self
+    }
+
     pub fn add_snapshot(&mut self, snapshot: RecordingSnapshot) {
+        if let Some(ref mut stride) = self.stride {
+            self.offered += 1;
+            if !self.offered.is_multiple_of(*stride) {
+                return;
+            }
+            if self.buffer.len() >= self.max_snapshots {
+                // Halve the resolution rather than lose the start of the run.
+                let mut index = 0;
+                self.buffer.retain(|_| {
+                    index += 1;
+                    index % 2 == 1
+                });
+                *stride *= 2;
+            }
+        }
         self.buffer.push_back(snapshot);
         while self.buffer.len() > self.max_snapshots {
             self.buffer.pop_front();
         }
     }
 
-    pub fn save_recording(&mut self, trigger_pid: u32, trigger_name: String) -> Option<usize> {
+    pub fn save_recording(
+        &mut self,
+        trigger_pid: u32,
+        trigger_name: String,
+    ) -> Option<RecordingMetadata> {
         if self.buffer.is_empty() {
             return None;
         }
@@ -126,7 +242,7 @@ impl RecordingManager {
 
         self.last_saved_pids.insert(trigger_pid, now);
         self.enforce_storage_cap();
-        Some(recording.snapshots.len())
+        Some(recording.metadata)
     }
 
     pub fn list_recordings(&self) -> Vec<RecordingMetadata> {
@@ -266,16 +382,21 @@ impl RecordingManager {
                 "invalid recording magic",
             ));
         }
-        if header[4] != VERSION {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "unsupported recording version",
-            ));
-        }
 
         let mut data = Vec::new();
         file.read_to_end(&mut data)?;
-        let mut recording: Recording = bincode::deserialize(&data)
+        let decoded = match header[4] {
+            1 => bincode::deserialize::<LegacyRecording<PodMemoryV1>>(&data).map(Recording::from),
+            2 => bincode::deserialize::<LegacyRecording<PodMemoryV2>>(&data).map(Recording::from),
+            VERSION => bincode::deserialize::<Recording>(&data),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unsupported recording version",
+                ))
+            }
+        };
+        let mut recording = decoded
             .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
         recording.metadata.file_path = path.to_path_buf();
         Ok(recording)
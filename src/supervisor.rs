@@ -1,185 +1,958 @@
-use std::fs::File;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ffi::CString;
 use std::io::{BufRead, BufReader};
-use std::os::unix::process::CommandExt;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
 use std::path::PathBuf;
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+/// Grace period after the stop signal when a child is killed, unless it
+/// sets `stop_timeout`.
+pub const KILL_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Lines of output kept per child for crash diagnostics and the log pane.
+const OUTPUT_TAIL_LINES: usize = 5000;
+
+/// The most recent output lines of a child, across its restarts.
+pub type OutputTail = Arc<Mutex<OutputBuffer>>;
+
+#[derive(Debug, Default)]
+pub struct OutputBuffer {
+    pub lines: VecDeque<OutputLine>,
+    /// Lines ever pushed, so readers can tell which ones they have not seen.
+    pub pushed: u64,
+}
+
+/// Starts the lines spm adds to a child's output, e.g. when it kills it.
+pub const SPM_NOTE_PREFIX: &str = "[spm] ";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+    /// A note from spm itself.
+    Spm,
+}
+
+#[derive(Clone, Debug)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    pub text: String,
+}
+
 #[derive(Clone, Debug)]
 pub struct ManagedChild {
     pub index: usize,
+    pub name: String,
     pub command: String,
+    pub spec: ChildSpec,
     pub pid: Option<u32>,
     pub pgid: Option<u32>,
     pub state: ChildState,
     pub restart_count: u32,
     pub backoff: BackoffState,
     pub total_uss: u64,
+    pub total_pss: u64,
+    pub total_rss: u64,
+    /// Processes in the child's tree, itself included.
+    pub process_count: u32,
+    /// Every pid in the tree at the last sample, used to tell whose orphan a
+    /// process reparented to spm is.
+    pub tree_pids: Vec<u32>,
+    /// Descendants whose parent exited and that spm, as subreaper, adopted.
+    /// They outlive restarts of the child and are killed at shutdown.
+    pub orphans: Vec<u32>,
+    /// Who started the current run. Unless spm spawned it itself, it cannot
+    /// wait for it: its exit is noticed by polling and its status is unknown.
+    pub origin: Origin,
+    pub uss_samples: VecDeque<(Instant, u64)>,
+    /// Highest USS sampled since the current run started.
+    pub peak_uss: u64,
+    /// USS growth in MB/min, as shown for processes in the main table.
+    pub growth_rate: Option<f64>,
     pub health: crate::health::HealthState,
     pub log_path: Option<PathBuf>,
     pub started_at: Option<Instant>,
+    /// When it began waiting for its dependencies.
+    pub waiting_since: Option<Instant>,
     pub last_exit: Option<ExitInfo>,
+    pub pending_action: Option<PendingAction>,
+    /// Who had the current run killed, once a kill has started.
+    pub kill_origin: Option<KillOrigin>,
+    pub owner: Option<RemoteOwner>,
+    /// Throttle or freeze the guard currently holds on this child.
+    pub mitigation: Option<crate::guard::GuardStep>,
+    /// The child's own cgroup, when spm's cgroup v2 subtree is delegated.
+    pub cgroup: Option<PathBuf>,
+    pub cgroup_stats: Option<crate::cgroup::CgroupStats>,
+    pub cpu_percent: Option<f64>,
+    pub output_tail: OutputTail,
+    /// The log the current or last run writes to.
+    pub log_writer: Option<Arc<Mutex<crate::logs::LogWriter>>>,
+    /// The background kill of the current or last run.
+    pub kill: Option<crate::process::KillHandle>,
+    /// Crashes inside the crash-loop window, oldest first.
+    pub crash_history: VecDeque<ExitInfo>,
+    pub quarantine: Option<Quarantine>,
+}
+
+/// Why a crash-looping child was taken out of service, and where its
+/// diagnostics were written.
+#[derive(Clone, Debug)]
+pub struct Quarantine {
+    pub reason: String,
+    pub bundle: Option<PathBuf>,
+}
+
+/// When repeated crashes count as a crash loop. `restarts = 0`, the
+/// default, turns detection off.
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrashLoopConfig {
+    pub restarts: u32,
+    pub window_secs: u64,
+    /// Output lines saved in the diagnostic bundle.
+    pub log_lines: usize,
+}
+
+/// Run before a child is sent its stop signal, e.g. to drain connections:
+/// a shell command, or an HTTP GET on `http` at `port` (default: the
+/// child's health port).
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PreStopConfig {
+    pub command: Option<String>,
+    pub http: Option<String>,
+    pub port: Option<u16>,
+    /// Default: 5 seconds.
+    pub timeout_secs: Option<f64>,
+}
+
+impl PreStopConfig {
+    pub fn is_set(&self) -> bool {
+        self.command.is_some() || self.http.is_some()
+    }
+}
+
+impl Default for CrashLoopConfig {
+    fn default() -> Self {
+        Self {
+            restarts: 0,
+            window_secs: 60,
+            log_lines: 50,
+        }
+    }
+}
+
+/// What to do once a child we deliberately signalled has exited, instead of
+/// treating the exit as a crash.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PendingAction {
+    Stop,
+    Restart,
+}
+
+/// Set on children read from another supervisor's state file: requests for
+/// them go over that supervisor's control socket.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RemoteOwner {
+    pub spm_pid: u32,
+    pub index: usize,
+}
+
+/// Everything needed to (re)spawn a child, as declared on the command line or
+/// in a config file.
+#[derive(Clone, Debug, Default)]
+pub struct ChildSpec {
+    pub name: Option<String>,
+    pub command: String,
+    /// When set, the program is exec'd directly with these arguments instead
+    /// of going through `sh -c`, so nothing needs shell quoting.
+    pub argv: Option<Vec<String>>,
+    pub cwd: Option<PathBuf>,
+    pub env: Vec<(String, String)>,
+    pub max_restarts: Option<u32>,
+    pub restart: Option<RestartPolicy>,
+    /// Count `max_restarts` over this sliding window instead of in total.
+    pub restart_window: Option<Duration>,
+    /// First restart delay, doubled on each restart up to `backoff_max`.
+    pub backoff_base: Option<Duration>,
+    pub backoff_max: Option<Duration>,
+    /// Each delay is varied by up to this fraction either way, so children
+    /// that crashed together do not restart in lockstep.
+    pub backoff_jitter: Option<f64>,
+    pub crash_loop: CrashLoopConfig,
+    pub health: crate::health::HealthConfig,
+    /// Higher values are killed later; only the `lowest-priority` and
+    /// `weighted` victim strategies look at it.
+    pub priority: i32,
+    /// Never chosen as a guard victim.
+    pub protected: bool,
+    /// `memory.max` of the child's cgroup, in bytes.
+    pub memory_max: Option<u64>,
+    /// `cpu.max` of the child's cgroup, in cores.
+    pub cpu_max: Option<f64>,
+    /// In init mode, the child whose exit ends the supervisor and whose exit
+    /// code spm exits with.
+    pub primary: bool,
+    /// Delivered to the child by the kernel when spm dies (PR_SET_PDEATHSIG).
+    pub parent_death_signal: Option<i32>,
+    /// Asks the child to stop before SIGKILL (default: SIGTERM).
+    pub stop_signal: Option<i32>,
+    /// Time between the stop signal and SIGKILL (default: 3s for kills, 5s
+    /// at shutdown).
+    pub stop_timeout: Option<Duration>,
+    pub pre_stop: PreStopConfig,
+    pub log: crate::logs::LogSettings,
+    /// Set for processes taken over with `spm adopt`: supervised but never
+    /// spawned or restarted, as spm only knows their command line.
+    pub existing_pid: Option<u32>,
+    /// Names of children that must be healthy, or have completed, before
+    /// this one starts. It is stopped before them at shutdown.
+    pub depends_on: Vec<String>,
+    /// How long it waits for them before it is marked Failed (default: 300s).
+    pub ready_timeout: Option<Duration>,
+}
+
+/// Which exits a child is restarted after. A stop through spm (`ctl stop`,
+/// `x`) is always final.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// After every exit, including exit code 0.
+    Always,
+    /// After a non-zero exit or a kill; exit code 0 completes the child.
+    #[default]
+    OnFailure,
+    Never,
+    /// Like `always`, except when something outside spm terminated it with
+    /// SIGTERM, SIGINT or SIGHUP.
+    UnlessStopped,
+}
+
+/// What a new `spm run` does with process groups a previous instance left
+/// running when it died without cleaning up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, clap::ValueEnum)]
+pub enum LeftoverPolicy {
+    /// Take over leftovers whose name matches a configured child instead of
+    /// spawning it again; kill the rest.
+    #[default]
+    Adopt,
+    Kill,
+    Ignore,
+}
+
+/// A child process group recorded by an spm instance that is gone.
+#[derive(Clone, Debug)]
+pub struct Leftover {
+    pub spm_pid: u32,
+    pub name: String,
+    pub pgid: u32,
+    /// The group leader itself is still the recorded process, not just
+    /// something it started.
+    pub leader_alive: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Origin {
+    #[default]
+    Spawned,
+    /// A leftover of a dead spm, supervised in place of spawning the child.
+    /// Once it exits, the child is spawned normally.
+    Leftover,
+    /// Running before spm and taken over with `spm adopt`. spm only knows
+    /// its command line, so it is never spawned, restarted or stopped.
+    Existing,
+}
+
+/// Who had spm kill a child's current run, reported with its exit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KillOrigin {
+    Guard,
+    Health,
+    /// A `kill` over the control socket.
+    Control,
+    Tui,
+}
+
+impl KillOrigin {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Guard => "guard",
+            Self::Health => "health",
+            Self::Control => "ctl",
+            Self::Tui => "tui",
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum ChildState {
+    /// Not started yet because a dependency is not ready.
+    Waiting,
     Running,
-    Stopping { emergency: bool },
+    Stopping {
+        emergency: bool,
+    },
     Stopped,
     Restarting,
     Completed,
     Failed,
+    /// Crash-looping: held back from restarting until resumed by hand.
+    Quarantined,
 }
 
 #[derive(Clone, Debug)]
 pub struct BackoffState {
+    pub base_delay: Duration,
     pub current_delay: Duration,
     pub max_delay: Duration,
+    pub jitter: f64,
     pub restart_at: Option<Instant>,
     pub last_delay: Duration,
     pub stable_since: Option<Instant>,
     pub stability_threshold: Duration,
+    /// Restarts inside the window, when restarts are limited per window.
+    pub window: Option<Duration>,
+    pub recent_restarts: VecDeque<Instant>,
+    /// State of the generator jitter is drawn from, seeded per child so
+    /// children that crash on the same tick draw different offsets.
+    rng: u64,
 }
 
 #[derive(Clone, Debug)]
 pub struct ExitInfo {
     pub exit_code: Option<i32>,
     pub signal: Option<i32>,
-    pub killed_by_guard: bool,
-    pub killed_by_health: bool,
+    pub killed_by: Option<KillOrigin>,
     pub exited_at: Instant,
+    pub usage: RunUsage,
+}
+
+/// What one run of a child cost.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RunUsage {
+    /// From `wait4`; unknown for processes spm could not wait for.
+    pub rusage: Option<Rusage>,
+    /// Since the child was spawned or adopted.
+    pub wall_secs: Option<f64>,
+    /// Highest tree USS the monitor sampled during the run.
+    pub peak_uss: u64,
+}
+
+/// The kernel's accounting of an exited process and the descendants it
+/// waited for.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rusage {
+    pub user_secs: f64,
+    pub sys_secs: f64,
+    /// Largest RSS the process ever had, in bytes.
+    pub max_rss: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+    pub voluntary_switches: u64,
+    pub involuntary_switches: u64,
+}
+
+impl From<&libc::rusage> for Rusage {
+    fn from(usage: &libc::rusage) -> Self {
+        let secs = |time: libc::timeval| time.tv_sec as f64 + time.tv_usec as f64 / 1e6;
+        Self {
+            user_secs: secs(usage.ru_utime),
+            sys_secs: secs(usage.ru_stime),
+            // ru_maxrss is in kilobytes on Linux.
+            max_rss: usage.ru_maxrss.max(0) as u64 * 1024,
+            minor_faults: usage.ru_minflt.max(0) as u64,
+            major_faults: usage.ru_majflt.max(0) as u64,
+            voluntary_switches: usage.ru_nvcsw.max(0) as u64,
+            involuntary_switches: usage.ru_nivcsw.max(0) as u64,
+        }
+    }
 }
 
 pub struct SpawnedChild {
     pub pid: u32,
-    pub stdout: Option<std::process::ChildStdout>,
-    pub stderr: Option<std::process::ChildStderr>,
+    /// Configured limits that could not be applied to the child's cgroup.
+    pub warnings: Vec<String>,
 }
 
 impl BackoffState {
     pub fn new() -> Self {
         Self {
+            base_delay: Duration::from_secs(1),
             current_delay: Duration::from_secs(1),
             max_delay: Duration::from_secs(30),
+            jitter: 0.0,
             restart_at: None,
             last_delay: Duration::from_secs(1),
             stable_since: None,
             stability_threshold: Duration::from_secs(60),
+            window: None,
+            recent_restarts: VecDeque::new(),
+            rng: random_seed(),
         }
     }
 
-    pub fn next_delay(&mut self) -> Duration {
-        let delay = self.current_delay;
-        self.last_delay = delay;
-        self.current_delay = (self.current_delay * 2).min(self.max_delay);
-        delay
+    pub fn from_spec(spec: &ChildSpec) -> Self {
+        let defaults = Self::new();
+        let base_delay = spec.backoff_base.unwrap_or(defaults.base_delay);
+        Self {
+            base_delay,
+            current_delay: base_delay,
+            max_delay: spec
+                .backoff_max
+                .unwrap_or(defaults.max_delay)
+                .max(base_delay),
+            jitter: spec.backoff_jitter.unwrap_or(0.0).clamp(0.0, 1.0),
+            last_delay: base_delay,
+            window: spec.restart_window,
+            ..defaults
+        }
     }
 
     pub fn schedule_restart(&mut self) -> Duration {
-        let delay = self.current_delay;
+        let delay = self.jittered(self.current_delay);
         self.restart_at = Some(Instant::now() + delay);
         self.last_delay = delay;
         self.current_delay = (self.current_delay * 2).min(self.max_delay);
         delay
     }
 
+    /// `delay` moved by a random amount of up to `jitter` of itself.
+    fn jittered(&mut self, delay: Duration) -> Duration {
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        // splitmix64; uniform in [-1, 1).
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut bits = self.rng;
+        bits = (bits ^ (bits >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        bits = (bits ^ (bits >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        bits ^= bits >> 31;
+        let spread = (bits >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0;
+        delay.mul_f64((1.0 + self.jitter * spread).max(0.0))
+    }
+
     pub fn reset_if_stable(&mut self) {
         if let Some(stable_since) = self.stable_since {
             if stable_since.elapsed() >= self.stability_threshold {
-                self.current_delay = Duration::from_secs(1);
+                self.current_delay = self.base_delay;
                 self.last_delay = self.current_delay;
             }
         }
     }
 
-    pub fn should_restart(&self, max_restarts: u32, current_count: u32) -> bool {
-        current_count <= max_restarts
+    /// Whether one more restart is within `max_restarts`: in total, or
+    /// within the window when one is set. Counts the restart it allows.
+    pub fn should_restart(&mut self, max_restarts: u32, current_count: u32) -> bool {
+        let Some(window) = self.window else {
+            return current_count <= max_restarts;
+        };
+        let now = Instant::now();
+        while self
+            .recent_restarts
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > window)
+        {
+            self.recent_restarts.pop_front();
+        }
+        if self.recent_restarts.len() as u32 >= max_restarts {
+            return false;
+        }
+        self.recent_restarts.push_back(now);
+        true
+    }
+}
+
+/// A seed from the kernel's random pool, or from the clock and pid if it
+/// cannot be read.
+fn random_seed() -> u64 {
+    let mut seed = 0u64;
+    let filled = unsafe {
+        libc::getrandom(
+            &mut seed as *mut u64 as *mut libc::c_void,
+            std::mem::size_of::<u64>(),
+            0,
+        )
+    };
+    if filled == std::mem::size_of::<u64>() as isize {
+        return seed;
+    }
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or(0);
+    nanos ^ ((std::process::id() as u64) << 32)
+}
+
+/// Lowest index no child of this supervisor has had yet.
+static NEXT_CHILD_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+/// Index for a child added at runtime. Indexes are never reused or
+/// renumbered: other supervisors and viewers target children by them, and
+/// log and cgroup names carry them.
+pub fn allocate_child_index(children: &[ManagedChild]) -> usize {
+    let index = children
+        .iter()
+        .map(|child| child.index + 1)
+        .max()
+        .unwrap_or(0)
+        .max(NEXT_CHILD_INDEX.load(Ordering::SeqCst));
+    NEXT_CHILD_INDEX.store(index + 1, Ordering::SeqCst);
+    index
+}
+
+impl ChildSpec {
+    pub fn from_command(command: String) -> Self {
+        Self {
+            command,
+            ..Self::default()
+        }
     }
 }
 
 impl ManagedChild {
+    pub fn record_uss(&mut self, uss: u64) {
+        self.total_uss = uss;
+        self.peak_uss = self.peak_uss.max(uss);
+        self.uss_samples.push_back((Instant::now(), uss));
+        while self.uss_samples.len() > 10 {
+            self.uss_samples.pop_front();
+        }
+        self.growth_rate = crate::app::compute_growth_rate(&self.uss_samples);
+    }
+
     pub fn new(index: usize, command: String) -> Self {
+        Self::from_spec(index, ChildSpec::from_command(command))
+    }
+
+    pub fn from_spec(index: usize, spec: ChildSpec) -> Self {
+        let name = spec
+            .name
+            .clone()
+            .unwrap_or_else(|| extract_command_name(&spec.command));
+        let backoff = BackoffState::from_spec(&spec);
+        let origin = if spec.existing_pid.is_some() {
+            Origin::Existing
+        } else {
+            Origin::Spawned
+        };
         Self {
             index,
-            command,
+            name,
+            command: spec.command.clone(),
+            health: crate::health::HealthState::with_config(spec.health.clone()),
+            spec,
             pid: None,
             pgid: None,
             state: ChildState::Stopped,
             restart_count: 0,
-            backoff: BackoffState::new(),
+            backoff,
             total_uss: 0,
-            health: crate::health::HealthState::new(),
+            total_pss: 0,
+            total_rss: 0,
+            process_count: 0,
+            tree_pids: Vec::new(),
+            orphans: Vec::new(),
+            origin,
+            uss_samples: VecDeque::new(),
+            peak_uss: 0,
+            growth_rate: None,
             log_path: None,
             started_at: None,
+            waiting_since: None,
             last_exit: None,
+            pending_action: None,
+            kill_origin: None,
+            owner: None,
+            mitigation: None,
+            cgroup: None,
+            cgroup_stats: None,
+            cpu_percent: None,
+            output_tail: Arc::new(Mutex::new(OutputBuffer::default())),
+            log_writer: None,
+            kill: None,
+            crash_history: VecDeque::new(),
+            quarantine: None,
         }
     }
 }
 
 pub fn spawn_child(child: &mut ManagedChild, headless: bool) -> Result<SpawnedChild, String> {
-    let (stdout_cfg, stderr_cfg, log_path) = if headless {
-        (Stdio::piped(), Stdio::piped(), None)
-    } else {
-        let name = extract_command_name(&child.command);
-        let tmp_path = format!("/tmp/spm-{}-{}.log", child.index, name);
-        let file = File::create(&tmp_path).map_err(|e| format!("Failed to create log: {e}"))?;
-        let file2 = file
-            .try_clone()
-            .map_err(|e| format!("Failed to clone log: {e}"))?;
-        (
-            Stdio::from(file),
-            Stdio::from(file2),
-            Some(PathBuf::from(tmp_path)),
-        )
+    if child.origin == Origin::Existing {
+        return Err(format!(
+            "'{}' was adopted and cannot be started",
+            child.name
+        ));
+    }
+    if kill_pending(child) {
+        return Err(format!("'{}' is still being stopped", child.name));
+    }
+    // Headless output goes to spm's own stdout/stderr, and to a log only
+    // when a log directory was asked for.
+    let settings = &child.spec.log;
+    let log_path = (!headless || settings.dir.is_some())
+        .then(|| crate::logs::log_path(settings, child.index, &child.name));
+    let mut log = match log_path {
+        Some(ref path) => {
+            crate::logs::preserve_previous_run(
+                path,
+                &previous_run_reason(child.last_exit.as_ref()),
+                settings.keep_runs(),
+            );
+            Some(crate::logs::LogWriter::create(path, settings)?)
+        }
+        None => None,
     };
 
-    let mut cmd = Command::new("sh");
-    cmd.arg("-c").arg(&child.command);
-    cmd.stdout(stdout_cfg).stderr(stderr_cfg);
+    let mut cmd = match child.spec.argv.as_deref() {
+        Some([program, args @ ..]) => {
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd
+        }
+        _ => {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(&child.command);
+            cmd
+        }
+    };
+    if let Some(ref cwd) = child.spec.cwd {
+        cmd.current_dir(cwd);
+    }
+    cmd.envs(child.spec.env.iter().map(|(key, value)| (key, value)));
+    // Under the TUI the child writes straight to its log, and its stderr to
+    // a file next to it, so it keeps running, and writing, if spm dies: a
+    // pipe would get it SIGPIPE.
+    // Timestamps need spm to see each line, so such logs still go through
+    // spm, as all output does when headless.
+    match log {
+        Some(ref mut log) if !headless && !settings.timestamps() => {
+            let (stdout, stderr) = log.child_output()?;
+            cmd.stdout(stdout).stderr(stderr);
+        }
+        _ => {
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+    }
+    let log = log.map(|log| Arc::new(Mutex::new(log)));
+
+    let mut warnings = Vec::new();
+    if child.cgroup.is_none() {
+        child.cgroup = create_child_cgroup(child, &mut warnings);
+    }
+    // Built before fork: nothing may allocate between fork and exec.
+    let parent = std::process::id() as libc::pid_t;
+    let death_signal = child.spec.parent_death_signal;
+    let stop_signal = child.spec.stop_signal;
+    let cgroup_procs = child
+        .cgroup
+        .as_ref()
+        .and_then(|cgroup| CString::new(cgroup.join("cgroup.procs").as_os_str().as_bytes()).ok());
     unsafe {
-        cmd.pre_exec(|| {
+        cmd.pre_exec(move || {
             libc::setsid();
+            // An ignored signal stays ignored across exec, and a shell cannot
+            // trap it: spm started in the background ignores SIGINT.
+            if let Some(signal) = stop_signal {
+                libc::signal(signal, libc::SIG_DFL);
+            }
+            // Tied to the spawning thread; spm only spawns from the main and
+            // monitor threads, which live as long as the supervisor.
+            if let Some(signal) = death_signal {
+                libc::prctl(libc::PR_SET_PDEATHSIG, signal, 0, 0, 0);
+                if libc::getppid() != parent {
+                    return Err(std::io::Error::other("supervisor exited during spawn"));
+                }
+            }
+            if let Some(ref procs) = cgroup_procs {
+                let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                if fd >= 0 {
+                    libc::write(fd, b"0".as_ptr() as *const libc::c_void, 1);
+                    libc::close(fd);
+                }
+            }
             Ok(())
         });
     }
 
     let mut process = cmd.spawn().map_err(|e| format!("Failed to spawn: {e}"))?;
     let pid = process.id();
-    let stdout = process.stdout.take();
-    let stderr = process.stderr.take();
+    child.log_writer = log.clone();
+    let sink = OutputSink {
+        index: child.index,
+        name: child.name.clone(),
+        pid,
+        echo: headless,
+        tail: child.output_tail.clone(),
+        log,
+    };
+    if let Some(stdout) = process.stdout.take() {
+        spawn_output_reader(stdout, false, sink.clone());
+    }
+    if let Some(stderr) = process.stderr.take() {
+        spawn_output_reader(stderr, true, sink);
+    }
 
     child.pid = Some(pid);
     child.pgid = Some(pid);
+    child.kill = None;
     child.state = ChildState::Running;
     child.started_at = Some(Instant::now());
+    child.uss_samples.clear();
+    child.peak_uss = 0;
+    child.growth_rate = None;
+    child.mitigation = None;
+    child.origin = Origin::Spawned;
+    child.cgroup_stats = None;
+    child.cpu_percent = None;
     child.backoff.stable_since = Some(Instant::now());
     child.log_path = log_path;
-    child.health = crate::health::HealthState::new_with_baseline(pid);
+    child.health = crate::health::HealthState::new_with_baseline(pid, child.spec.health.clone());
 
-    Ok(SpawnedChild {
-        pid,
-        stdout,
-        stderr,
-    })
+    Ok(SpawnedChild { pid, warnings })
+}
+
+/// How the run whose log is about to be set aside ended, for its file name.
+fn previous_run_reason(exit: Option<&ExitInfo>) -> String {
+    match exit {
+        Some(ExitInfo {
+            signal: Some(signal),
+            ..
+        }) => format!("signal-{signal}"),
+        Some(ExitInfo {
+            exit_code: Some(code),
+            ..
+        }) => format!("exit-{code}"),
+        _ => "unknown".to_string(),
+    }
+}
+
+/// Give the child its own cgroup with the limits from its spec. Limits that
+/// cannot be set (no cgroup, or controller not delegated) go to `warnings`.
+fn create_child_cgroup(child: &ManagedChild, warnings: &mut Vec<String>) -> Option<PathBuf> {
+    let name: String = format!("{}-{}", child.index, child.name)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut limits = Vec::new();
+    if let Some(bytes) = child.spec.memory_max {
+        limits.push(("memory_max", "memory.max", bytes.to_string()));
+    }
+    if let Some(cores) = child.spec.cpu_max {
+        let quota = format!(
+            "{} {CPU_PERIOD_USEC}",
+            (cores * CPU_PERIOD_USEC as f64) as u64
+        );
+        limits.push(("cpu_max", "cpu.max", quota));
+    }
+
+    let Some(cgroup) = crate::cgroup::create_child_cgroup(&name) else {
+        if !limits.is_empty() {
+            warnings.push(format!(
+                "{}: limits not applied: no writable cgroup v2 hierarchy",
+                child.name
+            ));
+        }
+        return None;
+    };
+    if !limits.is_empty() {
+        warnings.extend(crate::cgroup::enable_child_controllers());
+    }
+    for (setting, file, value) in limits {
+        if let Err(error) = crate::cgroup::write_cgroup_file(&cgroup, file, &value) {
+            let cause = crate::cgroup::controller_error().map_or(error, str::to_string);
+            warnings.push(format!("{}: {setting} not applied: {cause}", child.name));
+        }
+    }
+    Some(cgroup)
+}
+
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+/// Where the lines a child writes end up. stdout and stderr share one, so
+/// both land in the same log in the order they were read.
+#[derive(Clone)]
+pub struct OutputSink {
+    pub index: usize,
+    pub name: String,
+    pub pid: u32,
+    /// Repeat each line as `--output-format` says (headless).
+    pub echo: bool,
+    pub tail: OutputTail,
+    pub log: Option<Arc<Mutex<crate::logs::LogWriter>>>,
 }
 
 pub fn spawn_output_reader(
-    name: String,
     reader: impl std::io::Read + Send + 'static,
     is_stderr: bool,
+    sink: OutputSink,
 ) -> std::thread::JoinHandle<()> {
     std::thread::spawn(move || {
         let buf = BufReader::new(reader);
-        for line in buf.lines().flatten() {
-            if is_stderr {
-                eprintln!("[{}] {}", name, line);
-            } else {
-                println!("[{}] {}", name, line);
+        for line in buf.split(b'\n').map_while(Result::ok) {
+            let line = String::from_utf8_lossy(&line).into_owned();
+            if sink.echo {
+                crate::logs::echo_line(&crate::logs::EchoedLine {
+                    index: sink.index,
+                    name: &sink.name,
+                    pid: sink.pid,
+                    is_stderr,
+                    line: &line,
+                });
             }
+            if let Some(ref log) = sink.log {
+                if let Ok(mut log) = log.lock() {
+                    log.write_line(&line);
+                }
+            }
+            let stream = if is_stderr {
+                OutputStream::Stderr
+            } else {
+                OutputStream::Stdout
+            };
+            push_output(&sink.tail, OutputLine { stream, text: line });
         }
     })
 }
 
+fn push_output(tail: &OutputTail, line: OutputLine) {
+    if let Ok(mut tail) = tail.lock() {
+        if tail.lines.len() >= OUTPUT_TAIL_LINES {
+            tail.lines.pop_front();
+        }
+        tail.lines.push_back(line);
+        tail.pushed += 1;
+    }
+}
+
+/// Add a line of spm's own between the child's output, in its log and its
+/// output tail, so the log pane can find it.
+pub fn note_output(child: &ManagedChild, note: &str) {
+    let text = format!("{SPM_NOTE_PREFIX}{note}");
+    if let Some(ref log) = child.log_writer {
+        if let Ok(mut log) = log.lock() {
+            log.write_line(&text);
+            // Read back from the log with the child's own lines.
+            if log.is_direct() {
+                return;
+            }
+        }
+    }
+    push_output(
+        &child.output_tail,
+        OutputLine {
+            stream: OutputStream::Spm,
+            text,
+        },
+    );
+}
+
+/// Move what the child wrote to a log it writes itself into its output
+/// tail. spm's notes in it are told apart by their prefix.
+pub fn follow_log(child: &ManagedChild) {
+    let Some(ref log) = child.log_writer else {
+        return;
+    };
+    let lines = match log.lock() {
+        Ok(mut log) => log.take_new_lines(),
+        Err(_) => return,
+    };
+    for mut line in lines {
+        if line.stream == OutputStream::Stdout && line.text.starts_with(SPM_NOTE_PREFIX) {
+            line.stream = OutputStream::Spm;
+        }
+        push_output(&child.output_tail, line);
+    }
+}
+
+/// `follow_log` once the child has exited, taking the last of its stderr.
+pub fn finish_log(child: &ManagedChild) {
+    if let Some(Ok(mut log)) = child.log_writer.as_ref().map(|log| log.lock()) {
+        log.finish_stderr();
+    }
+    follow_log(child);
+}
+
+/// The last `lines` lines the child wrote. The tail survives log rotation,
+/// so this holds even right after the log rolled over.
+pub fn recent_output(child: &ManagedChild, lines: usize) -> Vec<String> {
+    match child.output_tail.lock() {
+        Ok(tail) => tail
+            .lines
+            .iter()
+            .skip(tail.lines.len().saturating_sub(lines))
+            .map(|line| line.text.clone())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Remember the crash about to be restarted; exits spm caused, such as guard
+/// and health-check kills, are not crashes. Returns why the child counts
+/// as crash-looping once `crash_loop.restarts` crashes fall inside
+/// `crash_loop.window_secs`.
+pub fn record_crash(child: &mut ManagedChild) -> Option<String> {
+    let config = &child.spec.crash_loop;
+    if config.restarts == 0 {
+        return None;
+    }
+    let window = Duration::from_secs(config.window_secs);
+    if let Some(ref exit) = child.last_exit {
+        child.crash_history.push_back(exit.clone());
+    }
+    while child
+        .crash_history
+        .front()
+        .is_some_and(|exit| exit.exited_at.elapsed() > window)
+    {
+        child.crash_history.pop_front();
+    }
+    let crashes = child.crash_history.len();
+    if (crashes as u32) < config.restarts {
+        return None;
+    }
+    let last = child
+        .crash_history
+        .back()
+        .map(describe_exit)
+        .unwrap_or_default();
+    Some(format!(
+        "{crashes} crashes in {}s, last {last}",
+        config.window_secs
+    ))
+}
+
+pub fn describe_exit(exit: &ExitInfo) -> String {
+    match (exit.exit_code, exit.signal) {
+        (Some(code), _) => format!("exit code {code}"),
+        (None, Some(signal)) => format!("signal {signal}"),
+        (None, None) => "unknown exit status".to_string(),
+    }
+}
+
+/// Put a quarantined child back into service: its crash history and
+/// restart count start over and the next tick restarts it.
+pub fn resume_quarantined(child: &mut ManagedChild) {
+    child.quarantine = None;
+    child.crash_history.clear();
+    child.restart_count = 0;
+    child.backoff.recent_restarts.clear();
+    child.backoff.current_delay = child.backoff.base_delay;
+    child.backoff.last_delay = Duration::ZERO;
+    child.backoff.restart_at = Some(Instant::now());
+    child.state = ChildState::Restarting;
+}
+
 pub fn signal_process_group(pgid: u32, signal: i32) -> Result<(), String> {
     let neg_pgid = -(pgid as i32);
     let result = unsafe { libc::kill(neg_pgid, signal) };
@@ -195,63 +968,702 @@ pub fn signal_process_group(pgid: u32, signal: i32) -> Result<(), String> {
     Ok(())
 }
 
-pub fn compute_group_uss(pid: u32) -> u64 {
-    let mut total = read_pid_uss(pid);
-    if let Ok(entries) = std::fs::read_dir("/proc") {
-        for entry in entries.flatten() {
-            if let Some(child_pid) = entry
-                .file_name()
-                .to_str()
-                .and_then(|value| value.parse::<u32>().ok())
+/// Send `signal` to every process of a child: its cgroup members when it has
+/// a cgroup, its process group otherwise.
+pub fn signal_child(child: &ManagedChild, signal: i32) -> Result<(), String> {
+    if let Some(ref cgroup) = child.cgroup {
+        let pids = crate::cgroup::cgroup_pids(cgroup);
+        if !pids.is_empty() {
+            if signal == libc::SIGKILL
+                && crate::cgroup::write_cgroup_file(cgroup, "cgroup.kill", "1").is_ok()
             {
-                if child_pid == pid {
-                    continue;
+                return Ok(());
+            }
+            for pid in pids {
+                unsafe {
+                    libc::kill(pid as i32, signal);
                 }
-                if is_descendant(child_pid, pid) {
-                    total = total.saturating_add(read_pid_uss(child_pid));
+            }
+            return Ok(());
+        }
+    }
+    signal_orphans(child, signal);
+    match child.pgid {
+        Some(pgid) => signal_process_group(pgid, signal),
+        None => {
+            for pid in member_pids(child) {
+                unsafe {
+                    libc::kill(pid as i32, signal);
                 }
             }
+            Ok(())
         }
     }
-    total
 }
 
-fn read_pid_uss(pid: u32) -> u64 {
-    let path = format!("/proc/{pid}/smaps_rollup");
-    let content = match std::fs::read_to_string(&path) {
-        Ok(content) => content,
-        Err(_) => return 0,
+/// The child's tree at the last sample, or just the child before the first.
+fn member_pids(child: &ManagedChild) -> Vec<u32> {
+    if child.tree_pids.is_empty() {
+        child.pid.into_iter().collect()
+    } else {
+        child.tree_pids.clone()
+    }
+}
+
+/// Signal the child's adopted orphans, which may have left its process group.
+pub fn signal_orphans(child: &ManagedChild, signal: i32) {
+    for pid in &child.orphans {
+        unsafe {
+            libc::kill(*pid as i32, signal);
+        }
+    }
+}
+
+/// Alive and not yet a zombie.
+pub fn is_running(pid: u32) -> bool {
+    std::fs::read_to_string(format!("/proc/{pid}/stat"))
+        .ok()
+        .and_then(|content| {
+            let end = content.rfind(')')?;
+            content[end + 1..]
+                .split_whitespace()
+                .next()
+                .map(|state| state != "Z")
+        })
+        .unwrap_or(false)
+}
+
+/// Clock ticks after boot at which `pid` started (field 22 of its stat
+/// file). Together with the pid, identifies a process across pid reuse.
+pub fn process_start_ticks(pid: u32) -> Option<u64> {
+    stat_fields(pid)?.get(19)?.parse().ok()
+}
+
+fn process_group_of(pid: u32) -> Option<u32> {
+    stat_fields(pid)?.get(2)?.parse().ok()
+}
+
+/// Fields of `/proc/<pid>/stat` after the command name, starting at state.
+fn stat_fields(pid: u32) -> Option<Vec<String>> {
+    let content = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let end = content.rfind(')')?;
+    Some(
+        content[end + 1..]
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+fn pgid_record_path(spm_pid: u32) -> String {
+    format!("/tmp/spm-pgids-{spm_pid}")
+}
+
+/// The contents of the pgid record as last written.
+static PGID_RECORD: Mutex<String> = Mutex::new(String::new());
+
+/// Record the process group of every live child, one `<pgid> <start ticks>
+/// <name>` line each, so the next `spm run` can find them if this instance
+/// dies without cleaning up. The file is only rewritten when that changes.
+pub fn write_pgid_record(children: &[ManagedChild]) {
+    let mut record = String::new();
+    // Adopted processes were running before spm and must not be killed as
+    // its leftovers.
+    for child in children
+        .iter()
+        .filter(|child| child.owner.is_none() && child.origin != Origin::Existing)
+    {
+        if let Some(pgid) = child.pgid {
+            let start = process_start_ticks(pgid).unwrap_or(0);
+            record.push_str(&format!("{pgid} {start} {}\n", child.name));
+        }
+    }
+    let Ok(mut last) = PGID_RECORD.lock() else {
+        return;
     };
+    if *last == record {
+        return;
+    }
+    let path = pgid_record_path(std::process::id());
+    let tmp_path = format!("{path}.tmp");
+    if std::fs::write(&tmp_path, &record).is_ok() && std::fs::rename(&tmp_path, &path).is_ok() {
+        *last = record;
+    }
+}
 
-    let mut private_clean = 0u64;
-    let mut private_dirty = 0u64;
-    for line in content.lines() {
-        if line.starts_with("Private_Clean:") {
-            if let Some(kb) = parse_kb_value(line) {
-                private_clean = private_clean.saturating_add(kb.saturating_mul(1024));
+pub fn remove_pgid_record() {
+    let _ = std::fs::remove_file(pgid_record_path(std::process::id()));
+}
+
+/// Process groups recorded by spm instances that are no longer running and
+/// still have members. The records are consumed.
+pub fn find_leftovers() -> Vec<Leftover> {
+    let Ok(entries) = std::fs::read_dir("/tmp") else {
+        return Vec::new();
+    };
+    let mut leftovers = Vec::new();
+    for entry in entries.flatten() {
+        let Some(spm_pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix("spm-pgids-"))
+            .and_then(|pid| pid.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        if spm_pid == std::process::id() || is_running(spm_pid) {
+            continue;
+        }
+        let content = std::fs::read_to_string(entry.path()).unwrap_or_default();
+        let _ = std::fs::remove_file(entry.path());
+        for line in content.lines() {
+            let mut fields = line.splitn(3, ' ');
+            let (Some(pgid), Some(start), Some(name)) = (
+                fields.next().and_then(|pgid| pgid.parse::<u32>().ok()),
+                fields.next().and_then(|start| start.parse::<u64>().ok()),
+                fields.next(),
+            ) else {
+                continue;
+            };
+            let leader_alive = is_running(pgid) && process_start_ticks(pgid) == Some(start);
+            if leader_alive || group_has_members(pgid, start) {
+                leftovers.push(Leftover {
+                    spm_pid,
+                    name: name.to_string(),
+                    pgid,
+                    leader_alive,
+                });
+            }
+        }
+    }
+    leftovers
+}
+
+/// Any live process in group `pgid` that started no earlier than its
+/// recorded leader, i.e. not an unrelated group that reused the number.
+fn group_has_members(pgid: u32, leader_start: u64) -> bool {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        entry
+            .file_name()
+            .to_str()
+            .and_then(|value| value.parse::<u32>().ok())
+            .is_some_and(|pid| {
+                process_group_of(pid) == Some(pgid)
+                    && process_start_ticks(pid).is_some_and(|start| start >= leader_start)
+                    && is_running(pid)
+            })
+    })
+}
+
+/// Supervise a still-running leftover as `child` instead of spawning it.
+pub fn adopt_leftover(child: &mut ManagedChild, leftover: &Leftover) {
+    attach(child, leftover.pgid, Some(leftover.pgid), Origin::Leftover);
+}
+
+/// Spec for taking over the running process `pid`, named after its comm.
+pub fn existing_process_spec(pid: u32) -> Result<ChildSpec, String> {
+    if pid == 1 || pid == std::process::id() {
+        return Err(format!("refusing to adopt PID {pid}"));
+    }
+    if !is_running(pid) {
+        return Err(format!("no running process with PID {pid}"));
+    }
+    let cmdline = std::fs::read(format!("/proc/{pid}/cmdline"))
+        .map_err(|e| format!("Failed to read command line of {pid}: {e}"))?;
+    let command = cmdline
+        .split(|byte| *byte == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if command.is_empty() {
+        return Err(format!("PID {pid} is a kernel thread"));
+    }
+    let name = std::fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|comm| comm.trim().to_string());
+    Ok(ChildSpec {
+        name,
+        command,
+        existing_pid: Some(pid),
+        ..ChildSpec::default()
+    })
+}
+
+/// Start supervising the process named by `child.spec.existing_pid`. Its
+/// process group is only used when it leads one; otherwise it shares the
+/// group with whoever started it, and signals go to its tree instead.
+pub fn attach_existing(child: &mut ManagedChild) -> Result<(), String> {
+    let pid = child
+        .spec
+        .existing_pid
+        .ok_or_else(|| format!("'{}' is not an adopted process", child.name))?;
+    if !is_running(pid) {
+        return Err(format!("no running process with PID {pid}"));
+    }
+    let pgid = process_group_of(pid).filter(|pgid| *pgid == pid);
+    attach(child, pid, pgid, Origin::Existing);
+    Ok(())
+}
+
+/// Supervise the running, not self-spawned `pid` as `child`.
+fn attach(child: &mut ManagedChild, pid: u32, pgid: Option<u32>, origin: Origin) {
+    child.pid = Some(pid);
+    child.pgid = pgid;
+    child.state = ChildState::Running;
+    child.origin = origin;
+    child.started_at = Some(Instant::now());
+    child.backoff.stable_since = Some(Instant::now());
+    // No baseline: ports it already listens on are the ones to probe.
+    child.health = crate::health::HealthState::with_config(child.spec.health.clone());
+}
+
+/// How long a child waits for its dependencies unless it sets
+/// `ready_timeout_secs`.
+pub const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Whether a waiting child's dependencies let it start.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Readiness {
+    Ready,
+    /// The named dependency is not ready yet.
+    Waiting(String),
+    /// The named dependency failed or is gone, so it never will be ready.
+    Blocked(String),
+}
+
+/// Every dependency of `child` has completed, or is running and ready by its
+/// health check.
+pub fn dependency_readiness(child: &ManagedChild, children: &[ManagedChild]) -> Readiness {
+    for name in &child.spec.depends_on {
+        let Some(dependency) = children.iter().find(|other| &other.name == name) else {
+            return Readiness::Blocked(name.clone());
+        };
+        match dependency.state {
+            ChildState::Completed => {}
+            ChildState::Running if dependency.health.is_ready() => {}
+            ChildState::Failed => return Readiness::Blocked(name.clone()),
+            _ => return Readiness::Waiting(name.clone()),
+        }
+    }
+    Readiness::Ready
+}
+
+/// Child indices in the order they are stopped: dependents before what they
+/// depend on, children at the same depth together.
+pub fn shutdown_order(children: &[ManagedChild]) -> Vec<Vec<usize>> {
+    let depths: Vec<usize> = (0..children.len())
+        .map(|index| dependency_depth(children, index, &mut Vec::new()))
+        .collect();
+    let deepest = depths.iter().copied().max().unwrap_or(0);
+    (0..=deepest)
+        .rev()
+        .map(|depth| {
+            (0..children.len())
+                .filter(|index| depths[*index] == depth)
+                .collect::<Vec<_>>()
+        })
+        .filter(|wave| !wave.is_empty())
+        .collect()
+}
+
+/// Length of the longest dependency chain below child `index`.
+fn dependency_depth(children: &[ManagedChild], index: usize, visiting: &mut Vec<usize>) -> usize {
+    if visiting.contains(&index) {
+        return 0;
+    }
+    visiting.push(index);
+    let depth = children[index]
+        .spec
+        .depends_on
+        .iter()
+        .filter_map(|name| children.iter().position(|other| &other.name == name))
+        .map(|dependency| 1 + dependency_depth(children, dependency, visiting))
+        .max()
+        .unwrap_or(0);
+    visiting.pop();
+    depth
+}
+
+/// Make spm the reaper of every descendant whose parent exits, instead of
+/// init, so orphaned grandchildren stay visible.
+pub fn become_subreaper() -> Result<(), String> {
+    let result = unsafe { libc::prctl(libc::PR_SET_CHILD_SUBREAPER, 1, 0, 0, 0) };
+    if result != 0 {
+        return Err(format!(
+            "Failed to become child subreaper: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+/// Attach processes reparented to spm to the child whose tree they were in
+/// at the last sample, and forget orphans that are gone. Returns the
+/// position and pid of each newly adopted orphan.
+pub fn adopt_orphans(children: &mut [ManagedChild], tree: &ProcessTree) -> Vec<(usize, u32)> {
+    for child in children.iter_mut() {
+        child
+            .orphans
+            .retain(|pid| std::path::Path::new(&format!("/proc/{pid}")).exists());
+    }
+
+    let mut adopted = Vec::new();
+    for pid in tree.children_of(std::process::id()) {
+        if children
+            .iter()
+            .any(|child| child.pid == Some(*pid) || child.orphans.contains(pid))
+        {
+            continue;
+        }
+        if let Some(position) = children
+            .iter()
+            .position(|child| child.tree_pids.contains(pid))
+        {
+            children[position].orphans.push(*pid);
+            adopted.push((position, *pid));
+        }
+    }
+    adopted
+}
+
+/// Start stopping everything the child started, in the background: pre-stop
+/// hook → stop signal → grace → SIGKILL, or straight to SIGKILL in an
+/// emergency. `child.kill` tracks how far it got.
+pub fn kill_child(child: &mut ManagedChild, emergency: bool) -> Result<(), String> {
+    let stop = stop_signal(child, KILL_TIMEOUT);
+    let kill: crate::process::KillJob = match (&child.cgroup, child.pgid) {
+        (Some(cgroup), _) if !crate::cgroup::cgroup_pids(cgroup).is_empty() => {
+            let cgroup = cgroup.clone();
+            Box::new(move |progress| {
+                crate::process::kill_cgroup(&cgroup, stop, emergency, progress)
+            })
+        }
+        (_, Some(pgid)) => Box::new(move |progress| {
+            crate::process::kill_process_group(pgid, stop, emergency, progress)
+        }),
+        _ if child.pid.is_some() => {
+            let pids = member_pids(child);
+            Box::new(move |progress| crate::process::kill_pids(&pids, stop, emergency, progress))
+        }
+        _ => return Err(format!("{} has no process group", child.name)),
+    };
+    let hook_child = (!emergency && child.spec.pre_stop.is_set()).then(|| child.clone());
+    let key = child.pid.or(child.pgid).unwrap_or(0);
+    child.kill = Some(crate::process::kill_in_background(
+        key,
+        stop,
+        emergency,
+        move |progress| {
+            let hook = match hook_child {
+                Some(ref hook_child) => {
+                    crate::process::advance(progress, crate::process::KillStage::PreStop);
+                    run_pre_stop(hook_child)
+                }
+                None => Ok(()),
+            };
+            let outcome = kill(progress);
+            match hook {
+                Ok(()) => outcome,
+                Err(error) => outcome.map(|message| format!("{message}; {error}")),
+            }
+        },
+    ));
+    Ok(())
+}
+
+/// A kill of the child's previous run is still waiting out its grace
+/// period; starting it again now could get the new run killed too.
+pub fn kill_pending(child: &ManagedChild) -> bool {
+    child
+        .kill
+        .as_ref()
+        .is_some_and(|kill| !crate::process::is_finished(kill))
+}
+
+/// The child's stop signal and grace period, `default_timeout` unless it
+/// sets its own.
+pub fn stop_signal(child: &ManagedChild, default_timeout: Duration) -> crate::process::StopSignal {
+    crate::process::StopSignal {
+        signal: child.spec.stop_signal.unwrap_or(libc::SIGTERM),
+        timeout: child.spec.stop_timeout.unwrap_or(default_timeout),
+    }
+}
+
+/// Run the child's pre-stop hook, if it has one and is running, for at most
+/// its timeout. A failed hook is reported but the child is stopped anyway.
+pub fn run_pre_stop(child: &ManagedChild) -> Result<(), String> {
+    let hook = &child.spec.pre_stop;
+    let Some(pid) = child.pid.filter(|_| hook.is_set()) else {
+        return Ok(());
+    };
+    let timeout = Duration::from_secs_f64(hook.timeout_secs.unwrap_or(5.0));
+
+    if let Some(ref path) = hook.http {
+        let port = hook
+            .port
+            .or(child.spec.health.port)
+            .or(child.health.port)
+            .ok_or_else(|| format!("pre-stop for '{}': no port to call", child.name))?;
+        if !crate::health::probe_health(port, path) {
+            return Err(format!(
+                "pre-stop for '{}': GET :{port}{path} did not return 2xx",
+                child.name
+            ));
+        }
+    }
+
+    if let Some(ref command) = hook.command {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg(command)
+            .env("SPM_CHILD_PID", pid.to_string())
+            .env("SPM_CHILD_NAME", &child.name)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if let Some(ref cwd) = child.spec.cwd {
+            cmd.current_dir(cwd);
+        }
+        for (key, value) in &child.spec.env {
+            cmd.env(key, value);
+        }
+        // In its own group, so a timeout kills whatever the hook started too.
+        cmd.process_group(0);
+        let hook_pid = {
+            let mut hooks = pre_stop_hooks();
+            let hook_process = cmd
+                .spawn()
+                .map_err(|e| format!("pre-stop for '{}': {e}", child.name))?;
+            hooks.push((hook_process.id(), None));
+            hook_process.id()
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut hooks = pre_stop_hooks();
+            let mut status: i32 = 0;
+            let mut reaped = hooks
+                .iter()
+                .find(|(pid, _)| *pid == hook_pid)
+                .and_then(|(_, reaped)| *reaped);
+            if reaped.is_none()
+                && unsafe { libc::waitpid(hook_pid as i32, &mut status, libc::WNOHANG) } > 0
+            {
+                reaped = Some(status);
             }
-        } else if line.starts_with("Private_Dirty:") {
-            if let Some(kb) = parse_kb_value(line) {
-                private_dirty = private_dirty.saturating_add(kb.saturating_mul(1024));
+            if reaped.is_none() && Instant::now() < deadline {
+                drop(hooks);
+                std::thread::sleep(Duration::from_millis(50));
+                continue;
             }
+            if reaped.is_none() {
+                // Not reaped yet, so the pid is still the hook's.
+                unsafe {
+                    libc::kill(-(hook_pid as i32), libc::SIGKILL);
+                    libc::waitpid(hook_pid as i32, &mut status, 0);
+                }
+            }
+            hooks.retain(|(pid, _)| *pid != hook_pid);
+            return match reaped.map(std::process::ExitStatus::from_raw) {
+                Some(status) if status.success() => Ok(()),
+                Some(status) => Err(format!("pre-stop for '{}' failed: {status}", child.name)),
+                None => Err(format!(
+                    "pre-stop for '{}' timed out after {:.1}s",
+                    child.name,
+                    timeout.as_secs_f64()
+                )),
+            };
         }
     }
+    Ok(())
+}
+
+/// Pre-stop hooks still running, with the raw wait status of those the
+/// monitor reaped before `run_pre_stop` could.
+static PRE_STOP_HOOKS: Mutex<Vec<(u32, Option<i32>)>> = Mutex::new(Vec::new());
 
-    private_clean.saturating_add(private_dirty)
+fn pre_stop_hooks() -> std::sync::MutexGuard<'static, Vec<(u32, Option<i32>)>> {
+    PRE_STOP_HOOKS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
 }
 
-fn parse_kb_value(line: &str) -> Option<u64> {
-    line.split_whitespace().nth(1)?.parse::<u64>().ok()
+/// Reap the next exited child of spm: its pid, raw wait status and rusage.
+/// Pre-stop hooks are reaped too, but their status is kept for
+/// `run_pre_stop`, which owns them; the hook list stays locked meanwhile,
+/// so a hook's pid is never reused while it may still be signalled.
+pub fn reap_next() -> Option<(u32, i32, libc::rusage)> {
+    let mut hooks = pre_stop_hooks();
+    loop {
+        let mut status: i32 = 0;
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        let result = unsafe { libc::wait4(-1, &mut status, libc::WNOHANG, &mut rusage) };
+        if result <= 0 {
+            return None;
+        }
+        let pid = result as u32;
+        match hooks.iter_mut().find(|(hook, _)| *hook == pid) {
+            Some(hook) => hook.1 = Some(status),
+            None => return Some((pid, status, rusage)),
+        }
+    }
 }
 
-fn is_descendant(child_pid: u32, ancestor_pid: u32) -> bool {
-    let stat_path = format!("/proc/{child_pid}/stat");
-    if let Ok(content) = std::fs::read_to_string(&stat_path) {
-        if let Some(ppid) = parse_ppid(&content) {
-            return ppid == ancestor_pid;
+/// Refresh a child's memory from every process in its tree — its cgroup
+/// members when it has a cgroup, all descendants of the child and of its
+/// orphans otherwise — plus the cgroup's CPU usage.
+pub fn sample_child(child: &mut ManagedChild, tree: &ProcessTree) {
+    let cgroup_pids = child
+        .cgroup
+        .as_deref()
+        .map(crate::cgroup::cgroup_pids)
+        .unwrap_or_default();
+    let pids = if cgroup_pids.is_empty() {
+        child
+            .pid
+            .iter()
+            .chain(child.orphans.iter())
+            .flat_map(|root| tree.descendants(*root))
+            .collect()
+    } else {
+        cgroup_pids
+    };
+    let memory = compute_tree_memory(&pids);
+    child.record_uss(memory.uss);
+    child.total_pss = memory.pss;
+    child.total_rss = memory.rss;
+    child.process_count = memory.processes;
+    child.tree_pids = pids;
+
+    let Some(cgroup) = child.cgroup.as_deref() else {
+        return;
+    };
+    let stats = crate::cgroup::read_cgroup_stats(cgroup);
+    if let Some(previous) = child.cgroup_stats {
+        if let (Some(before), Some(now), Some(at)) = (
+            previous.cpu_usage_usec,
+            stats.cpu_usage_usec,
+            previous.sampled_at,
+        ) {
+            let elapsed = at.elapsed().as_micros() as f64;
+            if elapsed > 0.0 {
+                child.cpu_percent = Some(now.saturating_sub(before) as f64 / elapsed * 100.0);
+            }
+        }
+    }
+    child.cgroup_stats = Some(stats);
+}
+
+/// Drop a child's cgroup once nothing is left in it.
+pub fn release_child_cgroup(child: &ManagedChild) {
+    if let Some(cgroup) = child.cgroup.as_deref() {
+        if crate::cgroup::cgroup_pids(cgroup).is_empty() {
+            crate::cgroup::remove_child_cgroup(cgroup);
+        }
+    }
+}
+
+/// Parent → children links for every process, from a single pass over
+/// /proc.
+#[derive(Clone, Debug, Default)]
+pub struct ProcessTree {
+    children: HashMap<u32, Vec<u32>>,
+}
+
+/// Summed memory of a set of processes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TreeMemory {
+    pub uss: u64,
+    pub pss: u64,
+    pub rss: u64,
+    pub processes: u32,
+}
+
+impl ProcessTree {
+    pub fn scan() -> Self {
+        let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+        if let Ok(entries) = std::fs::read_dir("/proc") {
+            for entry in entries.flatten() {
+                let Some(pid) = entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|value| value.parse::<u32>().ok())
+                else {
+                    continue;
+                };
+                if let Some(ppid) = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+                    .ok()
+                    .and_then(|content| parse_ppid(&content))
+                {
+                    children.entry(ppid).or_default().push(pid);
+                }
+            }
+        }
+        Self { children }
+    }
+
+    pub fn children_of(&self, pid: u32) -> &[u32] {
+        self.children.get(&pid).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// `root` and everything below it, at any depth. Parents are read one
+    /// process at a time while pids are reused, so the scan can contain a
+    /// cycle; each pid is visited once.
+    pub fn descendants(&self, root: u32) -> Vec<u32> {
+        let mut pids = vec![root];
+        let mut visited = HashSet::from([root]);
+        let mut next = 0;
+        while next < pids.len() {
+            if let Some(children) = self.children.get(&pids[next]) {
+                pids.extend(children.iter().filter(|pid| visited.insert(**pid)));
+            }
+            next += 1;
         }
+        pids
     }
-    false
+}
+
+pub fn compute_tree_memory(pids: &[u32]) -> TreeMemory {
+    pids.iter().filter_map(|pid| read_pid_memory(*pid)).fold(
+        TreeMemory::default(),
+        |total, (uss, pss, rss)| TreeMemory {
+            uss: total.uss.saturating_add(uss),
+            pss: total.pss.saturating_add(pss),
+            rss: total.rss.saturating_add(rss),
+            processes: total.processes + 1,
+        },
+    )
+}
+
+/// USS, PSS and RSS of one process from `smaps_rollup`.
+fn read_pid_memory(pid: u32) -> Option<(u64, u64, u64)> {
+    let path = format!("/proc/{pid}/smaps_rollup");
+    let content = std::fs::read_to_string(&path).ok()?;
+
+    let mut uss = 0u64;
+    let mut pss = 0u64;
+    let mut rss = 0u64;
+    for line in content.lines() {
+        let Some(bytes) = parse_kb_value(line).map(|kb| kb.saturating_mul(1024)) else {
+            continue;
+        };
+        if line.starts_with("Private_Clean:") || line.starts_with("Private_Dirty:") {
+            uss = uss.saturating_add(bytes);
+        } else if line.starts_with("Pss:") {
+            pss = bytes;
+        } else if line.starts_with("Rss:") {
+            rss = bytes;
+        }
+    }
+
+    Some((uss, pss, rss))
+}
+
+fn parse_kb_value(line: &str) -> Option<u64> {
+    line.split_whitespace().nth(1)?.parse::<u64>().ok()
 }
 
 fn parse_ppid(stat_content: &str) -> Option<u32> {
@@ -261,10 +1673,6 @@ fn parse_ppid(stat_content: &str) -> Option<u32> {
     fields.get(1)?.parse::<u32>().ok()
 }
 
-pub fn command_name(command: &str) -> String {
-    extract_command_name(command)
-}
-
 fn extract_command_name(command: &str) -> String {
     command
         .split_whitespace()
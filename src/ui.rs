@@ -5,9 +5,12 @@ use ratatui::widgets::{Block, Borders, Clear, Gauge, Paragraph, Row, Table, Tabl
 use ratatui::Frame;
 
 use crate::app::{App, GuardAlert, KillTarget, PodMemorySnapshot, ProcessSnapshot, SortColumn};
+use crate::guard::GuardStep;
 use crate::health::HealthStatus;
+use crate::logview::LogView;
 use crate::replay::{AppMode, RecordingListState, ReplayState};
-use crate::supervisor::ChildState;
+use crate::supervisor::Origin;
+use crate::supervisor::{ChildState, OutputStream};
 
 #[derive(Clone, Copy)]
 struct Theme {
@@ -69,8 +72,8 @@ pub fn draw(frame: &mut Frame, app: &App) {
         draw_cmdline_modal(frame, *pid, name, cmdline, &theme);
     }
 
-    if let Some((ref title, ref content)) = app.show_log {
-        draw_log_modal(frame, title, content, &theme);
+    if let Some(ref view) = app.log_view {
+        draw_log_pane(frame, view, &theme);
     }
 }
 
@@ -318,29 +321,98 @@ fn draw_cmdline_modal(frame: &mut Frame, pid: u32, name: &str, cmdline: &str, th
     frame.render_widget(content, area);
 }
 
-fn draw_log_modal(frame: &mut Frame, title: &str, content: &str, theme: &Theme) {
+/// Output lines that fit in the log pane on a terminal of `size`: all but
+/// its borders and key line.
+pub fn log_page_height(size: ratatui::layout::Size) -> usize {
+    size.height.saturating_sub(3) as usize
+}
+
+fn draw_log_pane(frame: &mut Frame, view: &LogView, theme: &Theme) {
     let area = frame.area();
-    let w = (area.width as f32 * 0.85) as u16;
-    let h = (area.height as f32 * 0.7) as u16;
-    let x = (area.width.saturating_sub(w)) / 2;
-    let y = (area.height.saturating_sub(h)) / 2;
-    let modal = Rect::new(x, y, w, h);
-    frame.render_widget(ratatui::widgets::Clear, modal);
+    frame.render_widget(Clear, area);
+    let page = log_page_height(area.as_size());
+    let visible = view.visible();
+    let top = view.top(page);
+
+    let position = if view.follow {
+        "following".to_string()
+    } else {
+        format!(
+            "{}-{}/{}",
+            (top + 1).min(visible.len()),
+            (top + page).min(visible.len()),
+            visible.len()
+        )
+    };
     let block = Block::default()
         .borders(Borders::ALL)
         .border_style(Style::default().fg(theme.accent))
-        .title(format!(" Log: {} (any key to close) ", title))
+        .title(format!(" Log: {} ", view.title))
         .title_style(
             Style::default()
                 .fg(Color::White)
                 .add_modifier(Modifier::BOLD),
         )
+        .title(Line::from(format!(" {} | {} ", view.filter.label(), position)).right_aligned())
         .style(Style::default().bg(theme.bg));
-    let text = Paragraph::new(content.to_string())
-        .style(Style::default().fg(theme.fg))
-        .wrap(ratatui::widgets::Wrap { trim: false })
-        .block(block);
-    frame.render_widget(text, modal);
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let needle = view.search.to_lowercase();
+    let lines: Vec<Line> = visible
+        .iter()
+        .enumerate()
+        .skip(top)
+        .take(page)
+        .map(|(position, line)| {
+            let mut style = match line.stream {
+                OutputStream::Stdout => Style::default().fg(theme.fg),
+                OutputStream::Stderr => Style::default().fg(Color::Rgb(255, 140, 120)),
+                OutputStream::Spm => Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            };
+            if view.current_match == Some(position) {
+                style = style.bg(theme.highlight_bg);
+            }
+            highlight_matches(&line.text, &needle, style)
+        })
+        .collect();
+    let text_area = Rect::new(inner.x, inner.y, inner.width, inner.height.saturating_sub(1));
+    frame.render_widget(Paragraph::new(lines), text_area);
+
+    let footer = if view.search_input {
+        Line::from(format!("/{}▏", view.search)).style(Style::default().fg(theme.fg))
+    } else {
+        Line::from(
+            "↑/↓ PgUp/PgDn: scroll | g/G: top/bottom | f: follow | /: search | n/N: next/prev | s: stdout/stderr | K: last kill | Esc: close",
+        )
+        .style(Style::default().fg(theme.fg_dim))
+    };
+    let footer_area = Rect::new(inner.x, inner.bottom().saturating_sub(1), inner.width, 1);
+    frame.render_widget(Paragraph::new(footer), footer_area);
+}
+
+/// `text` with every case-insensitive occurrence of `needle` highlighted.
+fn highlight_matches<'a>(text: &'a str, needle: &str, style: Style) -> Line<'a> {
+    let lowered = text.to_lowercase();
+    // Lowercasing can change byte lengths; only highlight when it did not.
+    if needle.is_empty() || lowered.len() != text.len() {
+        return Line::from(Span::styled(text, style));
+    }
+    let found = style.bg(Color::Yellow).fg(Color::Black);
+    let mut spans = Vec::new();
+    let mut rest = 0;
+    for (start, _) in lowered.match_indices(needle) {
+        if start < rest {
+            continue;
+        }
+        spans.push(Span::styled(&text[rest..start], style));
+        spans.push(Span::styled(&text[start..start + needle.len()], found));
+        rest = start + needle.len();
+    }
+    spans.push(Span::styled(&text[rest..], style));
+    Line::from(spans)
 }
 
 fn render_gauges(
@@ -357,12 +429,21 @@ fn render_gauges(
         .split(area);
 
     let mem_state = memory_gauge_state(pod_memory);
-    let mem_block = Block::default()
+    let mut mem_block = Block::default()
         .borders(Borders::ALL)
         .border_style(Style::default().fg(theme.border))
         .title("Pod Memory")
         .title_style(Style::default().fg(theme.accent))
         .style(Style::default().bg(theme.bg));
+    if let Some(pressure) = pod_memory.pressure {
+        mem_block = mem_block.title(
+            Line::from(format!(
+                " PSI some {:.1}/{:.1} full {:.1}/{:.1} ",
+                pressure.some_avg10, pressure.some_avg60, pressure.full_avg10, pressure.full_avg60
+            ))
+            .right_aligned(),
+        );
+    }
     let mem_gauge = Gauge::default()
         .block(mem_block.clone())
         .ratio(mem_state.ratio)
@@ -459,10 +540,14 @@ fn render_process_table(
         } else {
             process.pid.to_string()
         };
+        let name = match crate::process::kill_progress(process.pid) {
+            Some(kill) => format!("{} [{}]", process.name, kill.label()),
+            None => process.name.clone(),
+        };
 
         Row::new(vec![
             pid_label,
-            process.name.clone(),
+            name,
             cmdline_display,
             format!("{:.1}", process.cpu_percent),
             format_bytes(process.uss),
@@ -542,9 +627,10 @@ fn memory_gauge_state(pod_memory: &PodMemorySnapshot) -> MemoryGaugeState {
                 Color::Green
             };
             let label = format!(
-                "{} / {} | Avail: {} | {}%",
+                "{} / {} | WS: {} | Avail: {} | {}%",
                 format_bytes(usage),
                 format_bytes(limit),
+                format_bytes(pod_memory.working_set),
                 format_bytes(available),
                 percent
             );
@@ -552,8 +638,9 @@ fn memory_gauge_state(pod_memory: &PodMemorySnapshot) -> MemoryGaugeState {
         }
         _ => {
             let label = format!(
-                "{} / unlimited | RSS Sum: {}",
+                "{} / unlimited | WS: {} | RSS Sum: {}",
                 format_bytes(usage),
+                format_bytes(pod_memory.working_set),
                 format_bytes(rss_sum)
             );
             (label, None, Style::default().fg(Color::Gray))
@@ -709,9 +796,9 @@ fn status_line(app: &App) -> (String, Style) {
     };
 
     let keys = if app.supervisor_mode {
-        "q: quit | k: kill | r: restart | l: logs | Tab: switch pane | w: watch | R: recordings | s: sort | /: filter"
+        "q: quit | k: kill | a: adopt | r: restart/resume | x: stop | l: logs | Tab: switch pane | w: watch | R: recordings | s: sort | /: filter"
     } else {
-        "q: quit | k: kill | w: watch | R: recordings | s: sort | /: filter | ↑/↓: select"
+        "q: quit | k: kill | a: adopt | w: watch | R: recordings | s: sort | /: filter | ↑/↓: select"
     };
 
     if !app.view_state.filter.trim().is_empty() {
@@ -748,6 +835,18 @@ fn guard_status_line(app: &App) -> Line<'_> {
         ]);
     }
 
+    if !guard.leader {
+        let leader = guard
+            .leader_pid
+            .map(|pid| format!(" — spm {pid} guards the pod"))
+            .unwrap_or_default();
+        return Line::from(vec![
+            Span::raw("🛡 Guard: "),
+            Span::styled("DEFERRED", Style::default().fg(Color::Gray)),
+            Span::raw(leader),
+        ]);
+    }
+
     match &app.guard_alert {
         Some(GuardAlert::Exhausted { .. }) => Line::from(vec![
             Span::raw("🛡 Guard: "),
@@ -773,7 +872,7 @@ fn guard_status_line(app: &App) -> Line<'_> {
         }
         None => {
             let percent = pod_memory_percent(app).unwrap_or(0.0);
-            Line::from(vec![
+            let mut spans = vec![
                 Span::raw("🛡 Guard: "),
                 Span::styled("ARMED", Style::default().fg(Color::Green)),
                 Span::raw(format!(
@@ -781,17 +880,33 @@ fn guard_status_line(app: &App) -> Line<'_> {
                     percent.round(),
                     guard.total_kills
                 )),
-            ])
+            ];
+            if let Some(seconds) = guard.time_to_limit.filter(|seconds| *seconds < 3600.0) {
+                spans.push(Span::styled(
+                    format!(" | limit in {}", format_eta(seconds)),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+            Line::from(spans)
         }
     }
 }
 
 fn pod_memory_percent(app: &App) -> Option<f64> {
-    let limit = app.pod_memory.cgroup_limit?;
-    if limit == 0 {
-        return None;
+    let signal = app
+        .guard
+        .as_ref()
+        .map(|guard| guard.config.memory_signal)
+        .unwrap_or_default();
+    app.pod_memory.percent_for(signal)
+}
+
+fn format_eta(seconds: f64) -> String {
+    if seconds < 60.0 {
+        format!("{:.0}s", seconds)
+    } else {
+        format!("{:.0}m", seconds / 60.0)
     }
-    Some((app.pod_memory.cgroup_usage as f64 / limit as f64) * 100.0)
 }
 
 fn managed_pane_height(app: &App) -> u16 {
@@ -806,6 +921,11 @@ fn render_managed_processes(frame: &mut Frame, area: Rect, app: &App, theme: &Th
         "Command".to_string(),
         "State".to_string(),
         "USS".to_string(),
+        "PSS".to_string(),
+        "RSS".to_string(),
+        "Procs".to_string(),
+        "Cgroup".to_string(),
+        "CPU".to_string(),
         "Health".to_string(),
         "Restarts".to_string(),
     ])
@@ -817,19 +937,57 @@ fn render_managed_processes(frame: &mut Frame, area: Rect, app: &App, theme: &Th
     );
 
     let rows = app.managed_children.iter().map(|child| {
-        let command = child.command.clone();
-        let state = child_state_label(&child.state);
+        let command = if child.spec.protected {
+            format!("🔒 {}", child.command)
+        } else if child.origin == Origin::Existing {
+            format!("⇲ {}", child.command)
+        } else {
+            child.command.clone()
+        };
+        let command = match child.quarantine {
+            Some(ref quarantine) => format!("{command} — {} (r: resume)", quarantine.reason),
+            None => command,
+        };
+        let kill = child
+            .kill
+            .as_ref()
+            .and_then(|kill| kill.lock().ok().map(|kill| kill.label()));
+        let state = match (&child.state, child.mitigation, kill) {
+            (ChildState::Stopping { .. }, _, Some(kill)) => kill,
+            (ChildState::Running, Some(GuardStep::Throttle), _) => "Throttled".to_string(),
+            (ChildState::Running, Some(GuardStep::Freeze), _) => "Frozen".to_string(),
+            (state, _, _) => child_state_label(state),
+        };
+        let fg = if child.state == ChildState::Quarantined {
+            Color::Yellow
+        } else {
+            theme.fg
+        };
         let health = health_label(&child.health.status, child.health.port);
+        let cgroup_memory = child
+            .cgroup_stats
+            .and_then(|stats| stats.memory_current)
+            .map(format_bytes)
+            .unwrap_or_else(|| "-".to_string());
+        let cpu = child
+            .cpu_percent
+            .map(|percent| format!("{percent:.1}%"))
+            .unwrap_or_else(|| "-".to_string());
 
         Row::new(vec![
             child.index.to_string(),
             command,
             state,
             format_bytes(child.total_uss),
+            format_bytes(child.total_pss),
+            format_bytes(child.total_rss),
+            child.process_count.to_string(),
+            cgroup_memory,
+            cpu,
             health,
             child.restart_count.to_string(),
         ])
-        .style(Style::default().fg(theme.fg).bg(theme.bg))
+        .style(Style::default().fg(fg).bg(theme.bg))
     });
 
     let table = Table::new(
@@ -839,6 +997,11 @@ fn render_managed_processes(frame: &mut Frame, area: Rect, app: &App, theme: &Th
             Constraint::Fill(1),
             Constraint::Length(12),
             Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            Constraint::Length(5),
+            Constraint::Length(10),
+            Constraint::Length(7),
             Constraint::Length(12),
             Constraint::Length(8),
         ],
@@ -873,12 +1036,14 @@ fn render_managed_processes(frame: &mut Frame, area: Rect, app: &App, theme: &Th
 
 fn child_state_label(state: &ChildState) -> String {
     match state {
+        ChildState::Waiting => "Waiting".to_string(),
         ChildState::Running => "Running".to_string(),
         ChildState::Stopping { .. } => "Stopping".to_string(),
         ChildState::Restarting => "Restarting".to_string(),
         ChildState::Completed => "Completed".to_string(),
         ChildState::Failed => "Failed".to_string(),
         ChildState::Stopped => "Stopped".to_string(),
+        ChildState::Quarantined => "Quarantined".to_string(),
     }
 }
 
@@ -945,7 +1110,7 @@ fn format_timestamp(ts: u64) -> String {
     format!("{:02}:{:02}:{:02}", hours, mins, secs)
 }
 
-fn format_bytes(value: u64) -> String {
+pub fn format_bytes(value: u64) -> String {
     const KB: f64 = 1024.0;
     const MB: f64 = 1024.0 * 1024.0;
     const GB: f64 = 1024.0 * 1024.0 * 1024.0;
@@ -595,6 +595,1361 @@ else
 fi
 rm -f /tmp/spm-test-events.json
 
+header "TEST 24: Config file — argv command, cwd, env, per-child max_restarts"
+
+mkdir -p /tmp/spm-test-cfg
+cat > /tmp/spm-test-cfg/spm.toml <<'TOML'
+[[child]]
+name = "cfg-child"
+command = ["sh", "-c", "echo \"it's $SPM_TEST_VALUE in $(pwd)\"; exit 1"]
+cwd = "/tmp/spm-test-cfg"
+env = { SPM_TEST_VALUE = "spm-test-sentinel value" }
+max_restarts = 1
+health = { enabled = false }
+TOML
+
+OUTPUT=$(timeout 15 $BINARY run --config /tmp/spm-test-cfg/spm.toml --headless 2>&1) || true
+if echo "$OUTPUT" | grep -q "\[cfg-child\] it's spm-test-sentinel value in /tmp/spm-test-cfg"; then
+    log_pass "Config child ran with its name, cwd and env (no quoting issues)"
+else
+    log_fail "Config child output" "got: $(echo "$OUTPUT" | head -3)"
+fi
+RC=$(count_restart "$OUTPUT")
+if [[ "$RC" -eq 1 ]] && has_shutdown "$OUTPUT"; then
+    log_pass "Per-child max_restarts=1 honoured"
+else
+    log_fail "Per-child max_restarts" "expected 1 restart then shutdown, got $RC restarts"
+fi
+
+printf '[[child]]\ncmd = "sleep 1"\n' > /tmp/spm-test-cfg/bad.toml
+OUTPUT=$(timeout 5 $BINARY run --config /tmp/spm-test-cfg/bad.toml --headless 2>&1)
+RC=$?
+if [[ "$RC" -ne 0 ]] && echo "$OUTPUT" | grep -q "unknown field"; then
+    log_pass "Invalid config rejected with an error"
+else
+    log_fail "Invalid config" "rc=$RC, got: $(echo "$OUTPUT" | head -2)"
+fi
+
+printf '[[child]]\nname = "../api"\ncommand = "sleep 1"\n' > /tmp/spm-test-cfg/bad-name.toml
+printf '[[child]]\nname = "api"\ncommand = "sleep 1"\n[[child]]\nname = "api"\ncommand = "sleep 2"\n' > /tmp/spm-test-cfg/dup-name.toml
+BAD_NAME=$(timeout 5 $BINARY run --config /tmp/spm-test-cfg/bad-name.toml --headless 2>&1)
+DUP_NAME=$(timeout 5 $BINARY run --config /tmp/spm-test-cfg/dup-name.toml --headless 2>&1)
+if echo "$BAD_NAME" | grep -q "contains '/'" && echo "$DUP_NAME" | grep -q "more than one child has this name"; then
+    log_pass "Child names with path characters and duplicate names are rejected"
+else
+    log_fail "Child name validation" "got: $BAD_NAME / $DUP_NAME"
+fi
+rm -rf /tmp/spm-test-cfg
+
+header "TEST 25: Control socket — spm ctl list/add/stop/restart/remove"
+
+$BINARY run "sleep 300" --headless 2>/tmp/spm-test-ctl.json &
+SPM_PID=$!
+sleep 2
+
+if [[ -S /tmp/spm-ctl-$SPM_PID.sock ]]; then
+    log_pass "Control socket created next to state file"
+else
+    log_fail "Control socket" "/tmp/spm-ctl-$SPM_PID.sock missing"
+fi
+
+OUTPUT=$($BINARY ctl --pid $SPM_PID add --name ctl-child -- sleep 301 2>&1)
+sleep 1
+if echo "$OUTPUT" | grep -q '"ok":true' && pgrep -f "sleep 301" >/dev/null; then
+    log_pass "ctl add started a new child"
+else
+    log_fail "ctl add" "got: $OUTPUT"
+fi
+
+OUTPUT=$($BINARY ctl --pid $SPM_PID stop ctl-child 2>&1)
+sleep 1
+STATE=$($BINARY ctl --pid $SPM_PID list 2>&1)
+if echo "$STATE" | grep -q '"name":"ctl-child"[^}]*"state":"Stopped"' && ! pgrep -f "sleep 301" >/dev/null; then
+    log_pass "ctl stop left the child Stopped without restarting it"
+else
+    log_fail "ctl stop" "state: $STATE"
+fi
+
+OLD_PID=$(pgrep -f "^sleep 300$" | head -1)
+$BINARY ctl --pid $SPM_PID restart 0 >/dev/null 2>&1
+sleep 2
+NEW_PID=$(pgrep -f "^sleep 300$" | head -1)
+if [[ -n "$NEW_PID" && "$NEW_PID" != "$OLD_PID" ]] && grep -q '"restart_count":0' <(grep '"event":"restart"' /tmp/spm-test-ctl.json); then
+    log_pass "ctl restart replaced the process without counting a crash"
+else
+    log_fail "ctl restart" "old=$OLD_PID new=$NEW_PID"
+fi
+
+$BINARY ctl --pid $SPM_PID remove ctl-child >/dev/null 2>&1
+if ! $BINARY ctl --pid $SPM_PID list 2>&1 | grep -q "ctl-child"; then
+    log_pass "ctl remove dropped the child"
+else
+    log_fail "ctl remove" "child still listed"
+fi
+
+$BINARY ctl --pid $SPM_PID add --name argv-child -- sh -c 'printf "%s\n" "$1" > /tmp/spm-test-argv; exec sleep 302' sh "it's  spaced" >/dev/null 2>&1
+sleep 1
+STATE=$($BINARY ctl --pid $SPM_PID list 2>&1)
+if [[ "$(cat /tmp/spm-test-argv 2>/dev/null)" == "it's  spaced" ]]; then
+    log_pass "ctl add passes a program and its arguments through without re-quoting"
+else
+    log_fail "ctl add argv" "got: $(cat /tmp/spm-test-argv 2>&1)"
+fi
+if echo "$STATE" | grep -q '"index":2,"name":"argv-child"' && $BINARY ctl --pid $SPM_PID stop 2 >/dev/null 2>&1 \
+    && sleep 1 && ! pgrep -f "sleep 302" >/dev/null && pgrep -f "^sleep 300$" >/dev/null; then
+    log_pass "Indexes are not reused or renumbered after a remove"
+else
+    log_fail "Stable indexes" "state: $STATE"
+fi
+rm -f /tmp/spm-test-argv
+
+if ! $BINARY ctl --pid $SPM_PID stop no-such-child >/dev/null 2>&1; then
+    log_pass "ctl returns non-zero for unknown target"
+else
+    log_fail "ctl unknown target" "exit code 0"
+fi
+
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+if [[ ! -e /tmp/spm-ctl-$SPM_PID.sock ]]; then
+    log_pass "Control socket removed on shutdown"
+else
+    log_fail "Control socket cleanup" "socket still present"
+fi
+rm -f /tmp/spm-test-ctl.json
+
+header "TEST 26: ctl kill — treated as a crash, restart policy applies"
+
+$BINARY run "sleep 302" --headless 2>/tmp/spm-test-ctl-kill.json &
+SPM_PID=$!
+sleep 2
+
+OLD_PID=$(pgrep -f "^sleep 302$" | head -1)
+OUTPUT=$($BINARY ctl --pid $SPM_PID kill 0 2>&1)
+sleep 3
+NEW_PID=$(pgrep -f "^sleep 302$" | head -1)
+if echo "$OUTPUT" | grep -q '"ok":true' && [[ -n "$NEW_PID" && "$NEW_PID" != "$OLD_PID" ]]; then
+    log_pass "ctl kill terminated the child and it was restarted"
+else
+    log_fail "ctl kill" "reply=$OUTPUT old=$OLD_PID new=$NEW_PID"
+fi
+
+if grep '"event":"restart"' /tmp/spm-test-ctl-kill.json | grep -q '"restart_count":1'; then
+    log_pass "ctl kill counts against max_restarts like a crash"
+else
+    log_fail "ctl kill restart count" "$(grep restart /tmp/spm-test-ctl-kill.json)"
+fi
+
+if grep '"event":"exit"' /tmp/spm-test-ctl-kill.json | grep -q '"killed_by":"ctl"' \
+    && ! grep -q '"killed_by":"guard"' /tmp/spm-test-ctl-kill.json; then
+    log_pass "The exit of a ctl kill is reported as killed_by ctl, not by the guard"
+else
+    log_fail "ctl kill origin" "$(grep '"event":"exit"' /tmp/spm-test-ctl-kill.json)"
+fi
+
+SOCKET_MODE=$(stat -c %a /tmp/spm-ctl-$SPM_PID.sock 2>/dev/null)
+if [[ "$SOCKET_MODE" == "600" ]]; then
+    log_pass "Only spm's own user can use the control socket"
+else
+    log_fail "Control socket mode" "mode=$SOCKET_MODE"
+fi
+
+python3 - /tmp/spm-ctl-$SPM_PID.sock <<'PY' &
+import socket, sys, time
+idle = [socket.socket(socket.AF_UNIX) for _ in range(5)]
+for sock in idle:
+    sock.connect(sys.argv[1])
+time.sleep(3)
+PY
+IDLE_PID=$!
+sleep 0.3
+START=$(date +%s%N)
+OUTPUT=$($BINARY ctl --pid $SPM_PID list 2>&1)
+ELAPSED_MS=$(( ($(date +%s%N) - START) / 1000000 ))
+if echo "$OUTPUT" | grep -q '"ok":true' && [[ $ELAPSED_MS -lt 800 ]]; then
+    log_pass "Clients that never send a request do not hold up others (${ELAPSED_MS}ms)"
+else
+    log_fail "Idle control clients" "took ${ELAPSED_MS}ms: $OUTPUT"
+fi
+kill $IDLE_PID 2>/dev/null || true
+wait $IDLE_PID 2>/dev/null || true
+
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+rm -f /tmp/spm-test-ctl-kill.json
+
+header "TEST 27: Cluster-wide guard — one leader, others defer"
+
+$BINARY run "sleep 303" --headless 2>/tmp/spm-test-leader-a.json &
+SPM_A=$!
+sleep 2
+$BINARY run "sleep 304" --headless 2>/tmp/spm-test-leader-b.json &
+SPM_B=$!
+sleep 2
+
+STATE_A=$(cat /tmp/spm-state-$SPM_A.json 2>/dev/null)
+STATE_B=$(cat /tmp/spm-state-$SPM_B.json 2>/dev/null)
+if echo "$STATE_A" | grep -q '"leader":true' && echo "$STATE_B" | grep -q "\"leader\":false,\"leader_pid\":$SPM_A"; then
+    log_pass "First supervisor leads the guard, second defers to it"
+else
+    log_fail "Guard leader election" "A: $STATE_A | B: $STATE_B"
+fi
+
+REPLY=$(python3 -c "
+import socket, sys
+s = socket.socket(socket.AF_UNIX)
+s.connect('/tmp/spm-ctl-$SPM_B.sock')
+s.sendall(b'guard-kill 0 forecast=42.0\n')
+print(s.makefile().readline().strip())
+")
+sleep 3
+if echo "$REPLY" | grep -q '"ok":true' && grep '"event":"guard_kill"' /tmp/spm-test-leader-b.json | grep -q '"reason":"forecast(42.0s)"'; then
+    log_pass "guard-kill from the leader is handled as a guard kill by the owner, with the leader's reason"
+else
+    log_fail "guard-kill request" "reply=$REPLY $(grep guard_kill /tmp/spm-test-leader-b.json)"
+fi
+
+cat > /tmp/spm-test-noguard.toml <<'TOML'
+[guard]
+enabled = false
+
+[[child]]
+command = "sleep 309"
+TOML
+$BINARY run --config /tmp/spm-test-noguard.toml --headless 2>/dev/null &
+SPM_C=$!
+sleep 2
+REPLY=$(python3 -c "
+import socket, sys
+s = socket.socket(socket.AF_UNIX)
+s.connect('/tmp/spm-ctl-$SPM_C.sock')
+s.sendall(b'guard-kill 0\n')
+print(s.makefile().readline().strip())
+")
+if echo "$REPLY" | grep -q '"ok":false' && pgrep -f "^sleep 309$" >/dev/null \
+    && grep -q '"enabled":false' /tmp/spm-state-$SPM_C.json 2>/dev/null; then
+    log_pass "A supervisor with the guard disabled refuses guard requests"
+else
+    log_fail "guard-kill with the guard disabled" "reply=$REPLY"
+fi
+kill -INT $SPM_C 2>/dev/null || true
+wait $SPM_C 2>/dev/null || true
+rm -f /tmp/spm-test-noguard.toml
+
+kill -INT $SPM_A 2>/dev/null || true
+wait $SPM_A 2>/dev/null || true
+sleep 2
+if grep -q '"leader":true' /tmp/spm-state-$SPM_B.json 2>/dev/null; then
+    log_pass "Follower takes over the guard when the leader exits"
+else
+    log_fail "Guard failover" "$(cat /tmp/spm-state-$SPM_B.json 2>/dev/null)"
+fi
+
+kill -INT $SPM_B 2>/dev/null || true
+wait $SPM_B 2>/dev/null || true
+rm -f /tmp/spm-test-leader-a.json /tmp/spm-test-leader-b.json
+
+header "TEST 28: Guard memory signal — usage by default, working set on request"
+
+$BINARY run "sleep 305" --headless 2>/dev/null &
+SPM_PID=$!
+sleep 2
+if grep -q '"memory_signal":"usage"' /tmp/spm-state-$SPM_PID.json 2>/dev/null; then
+    log_pass "Guard watches raw usage by default"
+else
+    log_fail "Default memory signal" "$(cat /tmp/spm-state-$SPM_PID.json 2>/dev/null)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+SPM_GUARD_MEMORY_SIGNAL=working-set $BINARY run "sleep 306" --headless 2>/dev/null &
+SPM_PID=$!
+sleep 2
+if grep -q '"memory_signal":"working-set"' /tmp/spm-state-$SPM_PID.json 2>/dev/null; then
+    log_pass "SPM_GUARD_MEMORY_SIGNAL=working-set switches the guard to the working set"
+else
+    log_fail "Memory signal env var" "$(cat /tmp/spm-state-$SPM_PID.json 2>/dev/null)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+if ! $BINARY run "sleep 307" --headless --memory-signal rss >/dev/null 2>&1; then
+    log_pass "Unknown memory signal is rejected"
+else
+    log_fail "Memory signal validation" "'rss' accepted"
+fi
+
+header "TEST 29: PSI pressure trigger — flags and config"
+
+mkdir -p /tmp/spm-test-psi
+cat > /tmp/spm-test-psi/spm.toml <<'EOF'
+[guard]
+pressure_threshold = 20.0
+pressure_metric = "some-avg60"
+
+[[child]]
+command = "sleep 308"
+EOF
+$BINARY run --config /tmp/spm-test-psi/spm.toml --headless 2>/dev/null &
+SPM_PID=$!
+sleep 2
+if kill -0 $SPM_PID 2>/dev/null; then
+    log_pass "Config with pressure_threshold/pressure_metric accepted"
+else
+    log_fail "PSI config" "supervisor exited"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+if ! $BINARY run "sleep 309" --headless --pressure-metric full-avg300 >/dev/null 2>&1; then
+    log_pass "Unknown pressure metric is rejected"
+else
+    log_fail "Pressure metric validation" "'full-avg300' accepted"
+fi
+rm -rf /tmp/spm-test-psi
+
+header "TEST 30: Predictive guard — forecast horizon"
+
+mkdir -p /tmp/spm-test-forecast
+cat > /tmp/spm-test-forecast/spm.toml <<'EOF'
+[guard]
+forecast_horizon_secs = 10
+
+[[child]]
+command = "sleep 310"
+EOF
+$BINARY run --config /tmp/spm-test-forecast/spm.toml --headless 2>/dev/null &
+SPM_PID=$!
+sleep 2
+if kill -0 $SPM_PID 2>/dev/null; then
+    log_pass "Config with forecast_horizon_secs accepted"
+else
+    log_fail "Forecast config" "supervisor exited"
+fi
+if grep -q '"time_to_limit_secs":' /tmp/spm-state-$SPM_PID.json 2>/dev/null; then
+    log_pass "State file publishes the guard's time to limit"
+else
+    log_fail "time_to_limit_secs in state" "$(cat /tmp/spm-state-$SPM_PID.json 2>/dev/null)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+rm -rf /tmp/spm-test-forecast
+
+if ! $BINARY run "sleep 311" --headless --forecast-horizon soon >/dev/null 2>&1; then
+    log_pass "Non-numeric forecast horizon is rejected"
+else
+    log_fail "Forecast horizon validation" "'soon' accepted"
+fi
+
+header "TEST 31: Victim strategies — priority and protected children"
+
+mkdir -p /tmp/spm-test-victim
+cat > /tmp/spm-test-victim/spm.toml <<'EOF'
+[guard]
+kill_threshold = 1
+grace_ticks = 1
+max_restarts = 0
+victim_strategy = "lowest-priority"
+
+[[child]]
+name = "notebook"
+command = ["python3", "-c", "import time; x=bytearray(30*10**6); time.sleep(60)"]
+protected = true
+
+[[child]]
+name = "worker"
+command = ["python3", "-c", "import time; x=bytearray(20*10**6); time.sleep(60)"]
+priority = 100
+
+[[child]]
+name = "scratch"
+command = ["python3", "-c", "import time; x=bytearray(15*10**6); time.sleep(60)"]
+EOF
+$BINARY run --config /tmp/spm-test-victim/spm.toml --headless 2>/tmp/spm-test-victim/events.json &
+SPM_PID=$!
+sleep 5
+STATE=$(cat /tmp/spm-state-$SPM_PID.json 2>/dev/null)
+if echo "$STATE" | grep -q '"name":"notebook"[^}]*"protected":true' && echo "$STATE" | grep -q '"name":"worker"[^}]*"priority":100'; then
+    log_pass "priority and protected are published in the state file"
+else
+    log_fail "Victim state fields" "$STATE"
+fi
+
+FIRST_KILL=$(grep '"event":"guard_kill"' /tmp/spm-test-victim/events.json | head -1)
+if [[ -z "$FIRST_KILL" ]]; then
+    log_skip "Victim order" "no cgroup memory limit, guard inactive"
+elif echo "$FIRST_KILL" | grep -q 'bytearray(15'; then
+    log_pass "lowest-priority kills the smaller default-priority child before the priority 100 one"
+else
+    log_fail "Victim order" "$FIRST_KILL"
+fi
+if grep '"event":"guard_kill"' /tmp/spm-test-victim/events.json | grep -q 'bytearray(30'; then
+    log_fail "Protected child" "notebook was killed"
+else
+    log_pass "Protected child is never killed by the guard"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+rm -rf /tmp/spm-test-victim
+
+if ! $BINARY run "sleep 312" --headless --victim-strategy random >/dev/null 2>&1; then
+    log_pass "Unknown victim strategy is rejected"
+else
+    log_fail "Victim strategy validation" "'random' accepted"
+fi
+
+header "TEST 32: Guard action ladder — freeze, then resume below threshold"
+
+$BINARY run "sleep 313" --headless --actions freeze,kill 2>/tmp/spm-test-ladder.json &
+SPM_PID=$!
+sleep 2
+REPLY=$(python3 -c "
+import socket
+s = socket.socket(socket.AF_UNIX)
+s.connect('/tmp/spm-ctl-$SPM_PID.sock')
+s.sendall(b'guard-freeze 0\n')
+print(s.makefile().readline().strip())
+")
+sleep 3
+if echo "$REPLY" | grep -q '"step":"freeze"' && grep -q '"event":"guard_freeze"' /tmp/spm-test-ladder.json; then
+    log_pass "guard-freeze stops the child instead of killing it"
+else
+    log_fail "guard-freeze" "reply=$REPLY"
+fi
+if grep -q '"event":"guard_resume"' /tmp/spm-test-ladder.json && [[ "$(ps -o stat= -p "$(pgrep -f '^sleep 313$' | head -1)" 2>/dev/null)" != T* ]]; then
+    log_pass "Frozen child is resumed once pod memory is below the resume threshold"
+else
+    log_fail "Guard resume" "$(cat /tmp/spm-test-ladder.json)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+rm -f /tmp/spm-test-ladder.json
+
+if ! $BINARY run "sleep 314" --headless --actions freeze,explode >/dev/null 2>&1; then
+    log_pass "Unknown guard action is rejected"
+else
+    log_fail "Guard action validation" "'explode' accepted"
+fi
+
+header "TEST 33: Per-child cgroups — limits from config, exact membership"
+
+mkdir -p /tmp/spm-test-cgroup
+cat > /tmp/spm-test-cgroup/spm.toml <<'EOF'
+[[child]]
+name = "limited"
+command = "setsid sleep 315 & sleep 316"
+memory_max = "64M"
+cpu_max = 0.5
+EOF
+$BINARY run --config /tmp/spm-test-cgroup/spm.toml --headless 2>/tmp/spm-test-cgroup/events.json &
+SPM_PID=$!
+sleep 3
+STATE=$(cat /tmp/spm-state-$SPM_PID.json 2>/dev/null)
+CGROUP=$(echo "$STATE" | python3 -c "import json,sys; print(json.load(sys.stdin)['children'][0]['cgroup'] or '')" 2>/dev/null)
+if [[ -z "$CGROUP" ]]; then
+    log_skip "Per-child cgroup" "cgroup v2 subtree not delegated, children tracked by process group"
+    if grep '"event":"warning"' /tmp/spm-test-cgroup/events.json | grep -q 'limited: limits not applied'; then
+        log_pass "Limits that cannot be applied are reported as a warning event"
+    else
+        log_fail "Limit warning" "$(cat /tmp/spm-test-cgroup/events.json)"
+    fi
+else
+    if [[ "$(cat "$CGROUP/memory.max" 2>/dev/null)" == "67108864" ]] && grep -q '^50000 100000' "$CGROUP/cpu.max" 2>/dev/null; then
+        log_pass "memory_max and cpu_max are written to the child's cgroup"
+    elif grep '"event":"warning"' /tmp/spm-test-cgroup/events.json | grep -q 'limited: memory_max not applied'; then
+        log_pass "Limits that cannot be applied are reported as a warning event"
+    else
+        log_fail "Per-child limits" "neither applied nor reported: $(cat /tmp/spm-test-cgroup/events.json)"
+    fi
+    if [[ "$(wc -l < "$CGROUP/cgroup.procs")" -ge 3 ]]; then
+        log_pass "setsid'd grandchild stays in the child's cgroup"
+    else
+        log_fail "Cgroup membership" "$(cat "$CGROUP/cgroup.procs")"
+    fi
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+if [[ -n "$CGROUP" ]] && pgrep -f '^sleep 315$' >/dev/null; then
+    log_fail "Cgroup shutdown" "setsid'd grandchild survived"
+    pkill -f '^sleep 315$' || true
+elif [[ -n "$CGROUP" ]]; then
+    log_pass "Shutdown kills every member of the child's cgroup"
+fi
+pkill -f '^sleep 315$' 2>/dev/null || true
+rm -rf /tmp/spm-test-cgroup
+
+$BINARY run "sleep 318" --headless 2>/dev/null &
+SPM_PID=$!
+sleep 2
+CGROUP=$(python3 -c "import json,sys; print(json.load(open(sys.argv[1]))['children'][0]['cgroup'] or '')" /tmp/spm-state-$SPM_PID.json 2>/dev/null)
+if [[ -z "$CGROUP" ]]; then
+    log_skip "Supervisor cgroup" "cgroup v2 subtree not delegated"
+elif [[ "$(grep '^0::' /proc/$SPM_PID/cgroup)" == "$(grep '^0::' /proc/self/cgroup)" ]]; then
+    log_pass "spm stays in its own cgroup when no child sets limits"
+else
+    log_fail "Supervisor cgroup" "moved to $(grep '^0::' /proc/$SPM_PID/cgroup)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+cat > /tmp/spm-test-cgroup.toml <<'EOF'
+[[child]]
+command = "sleep 317"
+memory_max = "lots"
+EOF
+if ! $BINARY run --config /tmp/spm-test-cgroup.toml --headless >/dev/null 2>&1; then
+    log_pass "Invalid memory_max is rejected"
+else
+    log_fail "memory_max validation" "'lots' accepted"
+fi
+rm -f /tmp/spm-test-cgroup.toml
+
+header "TEST 34: Descendant-tree memory accounting"
+
+$BINARY run "sh -c 'python3 -c \"import time; x=bytearray(40*10**6); time.sleep(60)\" & wait'" --headless 2>/dev/null &
+SPM_PID=$!
+sleep 4
+STATE=$(cat /tmp/spm-state-$SPM_PID.json 2>/dev/null)
+read -r USS PSS RSS PROCS <<< "$(echo "$STATE" | python3 -c "
+import json, sys
+c = json.load(sys.stdin)['children'][0]
+print(c['total_uss'], c['total_pss'], c['total_rss'], c['processes'])
+" 2>/dev/null)"
+if [[ "${USS:-0}" -ge 40000000 ]]; then
+    log_pass "Grandchild's memory counts toward the managed child (USS $USS)"
+else
+    log_fail "Tree USS" "$STATE"
+fi
+if [[ "${PROCS:-0}" -ge 3 && "${RSS:-0}" -ge "${PSS:-1}" && "${PSS:-0}" -ge "${USS:-1}" ]]; then
+    log_pass "Process count, PSS and RSS cover the whole tree ($PROCS processes)"
+else
+    log_fail "Tree accounting" "$STATE"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+header "TEST 35: Subreaper — orphaned grandchildren are adopted and killed at shutdown"
+
+$BINARY run "sh -c 'setsid sleep 318 & sleep 2'" "sleep 319" --headless 2>/tmp/spm-test-orphan.json &
+SPM_PID=$!
+sleep 5
+ORPHAN_PID=$(pgrep -f '^sleep 318$' | head -1)
+if [[ -n "$ORPHAN_PID" && "$(ps -o ppid= -p "$ORPHAN_PID" | tr -d ' ')" == "$SPM_PID" ]]; then
+    log_pass "Orphan is reparented to spm instead of init"
+else
+    log_fail "Subreaper" "orphan=$ORPHAN_PID ppid=$(ps -o ppid= -p "$ORPHAN_PID" 2>/dev/null)"
+fi
+STATE=$(cat /tmp/spm-state-$SPM_PID.json 2>/dev/null)
+if grep -q "\"event\":\"orphan\",\"index\":0[^}]*\"pid\":$ORPHAN_PID" /tmp/spm-test-orphan.json && echo "$STATE" | grep -q "\"orphans\":\[$ORPHAN_PID\]"; then
+    log_pass "Orphan is attached to the child that spawned it"
+else
+    log_fail "Orphan tracking" "$STATE"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+if pgrep -f '^sleep 318$' >/dev/null; then
+    log_fail "Orphan shutdown" "sleep 318 survived"
+    pkill -f '^sleep 318$' || true
+else
+    log_pass "Orphans are killed at shutdown"
+fi
+rm -f /tmp/spm-test-orphan.json
+
+header "TEST 36: Init mode — signal forwarding and primary exit code"
+
+$BINARY run --init --max-restarts 0 \
+    "trap 'exit 7' USR1; while :; do sleep 0.2; done" "sleep 322" 2>/tmp/spm-test-init.json &
+SPM_PID=$!
+sleep 2
+kill -USR1 $SPM_PID 2>/dev/null || true
+for _ in $(seq 1 30); do
+    kill -0 $SPM_PID 2>/dev/null || break
+    sleep 0.5
+done
+wait $SPM_PID 2>/dev/null
+EXIT_CODE=$?
+if grep -q '"event":"signal_forwarded","signal":10' /tmp/spm-test-init.json; then
+    log_pass "SIGUSR1 is forwarded to children"
+else
+    log_fail "Signal forwarding" "$(cat /tmp/spm-test-init.json)"
+fi
+if [[ "$EXIT_CODE" == "7" ]] && grep -q '"reason":"primary_exit"' /tmp/spm-test-init.json; then
+    log_pass "spm exits with the primary child's exit code"
+else
+    log_fail "Primary exit code" "exit=$EXIT_CODE"
+fi
+if pgrep -f '^sleep 322$' >/dev/null; then
+    log_fail "Init shutdown" "secondary child survived"
+    pkill -f '^sleep 322$' || true
+else
+    log_pass "Other children are stopped when the primary exits"
+fi
+
+$BINARY run --init --primary waiter "sleep 323" 2>/dev/null &
+SPM_PID=$!
+wait $SPM_PID 2>/dev/null
+if [[ $? -ne 0 ]]; then
+    log_pass "Unknown --primary is rejected"
+else
+    log_fail "--primary validation" "'waiter' accepted"
+fi
+
+$BINARY run --init "sleep 324" 2>/dev/null &
+SPM_PID=$!
+sleep 2
+kill -TERM $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null
+EXIT_CODE=$?
+if [[ "$EXIT_CODE" == "143" ]]; then
+    log_pass "SIGTERM is forwarded and reported as 128+15"
+else
+    log_fail "SIGTERM in init mode" "exit=$EXIT_CODE"
+fi
+rm -f /tmp/spm-test-init.json
+
+header "TEST 37: Parent-death signal and leftovers from a killed supervisor"
+
+$BINARY run "exec sleep 325" --headless --parent-death-signal KILL 2>/dev/null &
+SPM_PID=$!
+disown $SPM_PID
+sleep 2
+kill -KILL $SPM_PID 2>/dev/null || true
+sleep 1
+if pgrep -f '^sleep 325$' >/dev/null; then
+    log_fail "Parent-death signal" "child survived SIGKILL of spm"
+    pkill -f '^sleep 325$' || true
+else
+    log_pass "Child dies with spm when --parent-death-signal is set"
+fi
+rm -f /tmp/spm-state-$SPM_PID.json /tmp/spm-ctl-$SPM_PID.sock /tmp/spm-pgids-$SPM_PID
+
+mkdir -p /tmp/spm-test-leftover
+cat > /tmp/spm-test-leftover/spm.toml <<'EOF'
+[[child]]
+name = "sleeper"
+command = ["sleep", "326"]
+EOF
+$BINARY run --config /tmp/spm-test-leftover/spm.toml --headless 2>/dev/null &
+OLD_PID=$!
+disown $OLD_PID
+sleep 2
+LEFTOVER=$(pgrep -f '^sleep 326$' | head -1)
+kill -KILL $OLD_PID 2>/dev/null || true
+sleep 0.5
+if [[ -n "$LEFTOVER" && -f /tmp/spm-pgids-$OLD_PID ]] && grep -q "^$LEFTOVER .* sleeper$" /tmp/spm-pgids-$OLD_PID; then
+    log_pass "Child pgids are recorded on disk"
+else
+    log_fail "Pgid record" "$(cat /tmp/spm-pgids-$OLD_PID 2>/dev/null)"
+fi
+rm -f /tmp/spm-state-$OLD_PID.json /tmp/spm-ctl-$OLD_PID.sock
+
+$BINARY run --config /tmp/spm-test-leftover/spm.toml --headless 2>/tmp/spm-test-leftover/events.json &
+SPM_PID=$!
+disown $SPM_PID
+sleep 3
+if grep -q "\"event\":\"leftover\",\"name\":\"sleeper\",\"pgid\":$LEFTOVER,\"spm_pid\":$OLD_PID,\"action\":\"adopted\"" /tmp/spm-test-leftover/events.json \
+    && ! grep -q '"event":"spawn"' /tmp/spm-test-leftover/events.json \
+    && grep -q "\"pid\":$LEFTOVER" /tmp/spm-state-$SPM_PID.json; then
+    log_pass "Restarted spm adopts the leftover instead of spawning a duplicate"
+else
+    log_fail "Leftover adoption" "$(cat /tmp/spm-test-leftover/events.json)"
+fi
+kill -TERM $LEFTOVER 2>/dev/null || true
+sleep 4
+if grep -q "\"event\":\"exit\",\"index\":0[^}]*\"pid\":$LEFTOVER" /tmp/spm-test-leftover/events.json && grep -q '"event":"spawn"' /tmp/spm-test-leftover/events.json; then
+    log_pass "Adopted leftover's exit is noticed and the child is respawned"
+else
+    log_fail "Adopted exit" "$(cat /tmp/spm-test-leftover/events.json)"
+fi
+kill -KILL $SPM_PID 2>/dev/null || true
+sleep 0.5
+rm -f /tmp/spm-state-$SPM_PID.json /tmp/spm-ctl-$SPM_PID.sock
+
+$BINARY run --config /tmp/spm-test-leftover/spm.toml --headless --leftovers kill 2>/tmp/spm-test-leftover/events.json &
+NEW_PID=$!
+sleep 5
+if grep -q '"event":"leftover","name":"sleeper"[^}]*"action":"killed"' /tmp/spm-test-leftover/events.json && [[ "$(pgrep -fc '^sleep 326$')" == "1" ]]; then
+    log_pass "--leftovers kill stops the leftover group and spawns a fresh child"
+else
+    log_fail "Leftover cleanup" "$(cat /tmp/spm-test-leftover/events.json)"
+fi
+kill -INT $NEW_PID 2>/dev/null || true
+wait $NEW_PID 2>/dev/null || true
+pkill -f '^sleep 326$' 2>/dev/null || true
+rm -rf /tmp/spm-test-leftover
+
+header "TEST 38: Adopting already-running processes"
+
+sleep 338 &
+ADOPT_PID=$!
+disown $ADOPT_PID
+$BINARY run --adopt $ADOPT_PID --headless 2>/tmp/spm-test-adopt.json &
+SPM_PID=$!
+sleep 2
+if grep -q "\"event\":\"adopt\",\"index\":0,\"cmd\":\"sleep 338\",\"pid\":$ADOPT_PID" /tmp/spm-test-adopt.json \
+    && grep -q "\"pid\":$ADOPT_PID,\"state\":\"Running\"[^}]*\"adopted_pid\":$ADOPT_PID" /tmp/spm-state-$SPM_PID.json; then
+    log_pass "spm run --adopt supervises a running process"
+else
+    log_fail "Run --adopt" "$(cat /tmp/spm-test-adopt.json)"
+fi
+
+sleep 339 &
+SECOND_PID=$!
+disown $SECOND_PID
+OUTPUT=$($BINARY adopt $SECOND_PID 2>&1)
+sleep 2
+if echo "$OUTPUT" | grep -q '"ok":true' \
+    && grep -q "\"event\":\"adopt\",\"index\":1,\"cmd\":\"sleep 339\",\"pid\":$SECOND_PID" /tmp/spm-test-adopt.json; then
+    log_pass "spm adopt hands a process to the running supervisor"
+else
+    log_fail "spm adopt" "$OUTPUT"
+fi
+
+OUTPUT=$($BINARY ctl restart 1 2>&1 || true)
+if echo "$OUTPUT" | grep -q "cannot be restarted"; then
+    log_pass "Adopted processes are not restarted on request"
+else
+    log_fail "Adopted restart" "$OUTPUT"
+fi
+
+kill -KILL $SECOND_PID 2>/dev/null || true
+sleep 3
+if grep -q "\"event\":\"exit\",\"index\":1[^}]*\"pid\":$SECOND_PID" /tmp/spm-test-adopt.json \
+    && grep -q '"event":"completed","index":1' /tmp/spm-test-adopt.json \
+    && ! grep -q '"event":"spawn"' /tmp/spm-test-adopt.json; then
+    log_pass "Adopted process exit is noticed and it is not respawned"
+else
+    log_fail "Adopted exit" "$(cat /tmp/spm-test-adopt.json)"
+fi
+
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+if kill -0 $ADOPT_PID 2>/dev/null; then
+    log_pass "Adopted processes are left running when spm exits"
+else
+    log_fail "Adopted shutdown" "process $ADOPT_PID was killed"
+fi
+kill -KILL $ADOPT_PID 2>/dev/null || true
+rm -f /tmp/spm-test-adopt.json
+
+header "TEST 39: Service dependencies and ordered startup/shutdown"
+
+mkdir -p /tmp/spm-test-deps
+cat > /tmp/spm-test-deps/spm.toml <<'EOF_TOML'
+[[child]]
+name = "db"
+command = "trap 'exit 0' TERM; while :; do sleep 0.1; done"
+health = { enabled = false }
+
+[[child]]
+name = "migrate"
+command = "sleep 1"
+depends_on = ["db"]
+
+[[child]]
+name = "api"
+command = "trap 'sleep 1; exit 0' TERM; while :; do sleep 0.1; done"
+depends_on = ["db", "migrate"]
+EOF_TOML
+$BINARY run --config /tmp/spm-test-deps/spm.toml --headless 2>/tmp/spm-test-deps/events.json &
+SPM_PID=$!
+sleep 1.5
+if grep -q '"state":"Waiting"' /tmp/spm-state-$SPM_PID.json && ! grep -q '"event":"spawn","index":2' /tmp/spm-test-deps/events.json; then
+    log_pass "Dependent child waits for its dependencies"
+else
+    log_fail "Dependency wait" "$(cat /tmp/spm-test-deps/events.json)"
+fi
+sleep 3.5
+MIGRATE_DONE=$(grep -n '"event":"completed","index":1' /tmp/spm-test-deps/events.json | cut -d: -f1)
+API_SPAWN=$(grep -n '"event":"spawn","index":2' /tmp/spm-test-deps/events.json | cut -d: -f1)
+if [[ -n "$MIGRATE_DONE" && -n "$API_SPAWN" && "$API_SPAWN" -gt "$MIGRATE_DONE" ]]; then
+    log_pass "Child starts once a one-shot dependency has completed"
+else
+    log_fail "Dependency start" "$(cat /tmp/spm-test-deps/events.json)"
+fi
+
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+API_EXIT=$(grep -n '"event":"exit","index":2' /tmp/spm-test-deps/events.json | cut -d: -f1)
+DB_EXIT=$(grep -n '"event":"exit","index":0' /tmp/spm-test-deps/events.json | cut -d: -f1)
+if [[ -n "$API_EXIT" && -n "$DB_EXIT" && "$DB_EXIT" -gt "$API_EXIT" ]]; then
+    log_pass "Shutdown stops dependents before their dependencies"
+else
+    log_fail "Shutdown order" "$(cat /tmp/spm-test-deps/events.json)"
+fi
+
+cat > /tmp/spm-test-deps/cycle.toml <<'EOF_TOML'
+[[child]]
+name = "a"
+command = "sleep 1"
+depends_on = ["b"]
+
+[[child]]
+name = "b"
+command = "sleep 1"
+depends_on = ["a"]
+EOF_TOML
+OUTPUT=$($BINARY run --config /tmp/spm-test-deps/cycle.toml --headless 2>&1)
+if echo "$OUTPUT" | grep -q "depends_on forms a cycle"; then
+    log_pass "Dependency cycles are rejected"
+else
+    log_fail "Dependency cycle" "$OUTPUT"
+fi
+
+cat > /tmp/spm-test-deps/timeout.toml <<'EOF_TOML'
+[[child]]
+name = "slow"
+command = "sleep 60"
+
+[[child]]
+name = "web"
+command = "sleep 60"
+depends_on = ["slow"]
+ready_timeout_secs = 2
+EOF_TOML
+$BINARY run --config /tmp/spm-test-deps/timeout.toml --headless 2>/tmp/spm-test-deps/timeout.json &
+SPM_PID=$!
+for _ in $(seq 1 50); do
+    grep -q '"event":"failed","index":1' /tmp/spm-test-deps/timeout.json && break
+    sleep 0.1
+done
+if grep -q "\"event\":\"warning\",\"index\":1,\"message\":\"not started: dependency 'slow' not ready after 2s\"" /tmp/spm-test-deps/timeout.json \
+    && grep -q '"event":"failed","index":1' /tmp/spm-test-deps/timeout.json \
+    && ! grep -q '"event":"spawn","index":1' /tmp/spm-test-deps/timeout.json; then
+    log_pass "A child whose dependency is not ready within ready_timeout_secs is marked Failed with the reason"
+else
+    log_fail "Ready timeout" "$(cat /tmp/spm-test-deps/timeout.json)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+rm -rf /tmp/spm-test-deps
+
+header "TEST 40: Restart policies, restart windows and backoff"
+
+mkdir -p /tmp/spm-test-restart
+cat > /tmp/spm-test-restart/spm.toml <<'EOF_TOML'
+[[child]]
+name = "server"
+command = "sleep 0.5"
+restart = "always"
+backoff_base_secs = 0.2
+backoff_jitter = 0.5
+
+[[child]]
+name = "oneshot"
+command = "exit 3"
+restart = "never"
+
+[[child]]
+name = "daemon"
+command = "exec sleep 340"
+restart = "unless-stopped"
+
+[[child]]
+name = "flaky"
+command = "exit 1"
+max_restarts = 2
+restart_window_secs = 60
+backoff_base_secs = 0.1
+EOF_TOML
+$BINARY run --config /tmp/spm-test-restart/spm.toml --headless 2>/tmp/spm-test-restart/events.json &
+SPM_PID=$!
+sleep 2
+pkill -TERM -f '^sleep 340$' || true
+sleep 5
+EVENTS=/tmp/spm-test-restart/events.json
+if grep -q '"event":"restart","index":0' $EVENTS && ! grep -q '"event":"completed","index":0' $EVENTS; then
+    log_pass "restart = always brings back a child that exits 0"
+else
+    log_fail "Restart always" "$(cat $EVENTS)"
+fi
+if grep -q '"event":"failed","index":1,"cmd":"exit 3","restart_count":0' $EVENTS && ! grep -q '"event":"restart","index":1' $EVENTS; then
+    log_pass "restart = never fails without restarting"
+else
+    log_fail "Restart never" "$(cat $EVENTS)"
+fi
+if grep -q '"event":"stopped","index":2' $EVENTS && ! grep -q '"event":"restart","index":2' $EVENTS; then
+    log_pass "restart = unless-stopped leaves an externally stopped child stopped"
+else
+    log_fail "Restart unless-stopped" "$(cat $EVENTS)"
+fi
+if [[ "$(grep -c '"event":"restart","index":3' $EVENTS)" == "2" ]] && grep -q '"event":"failed","index":3' $EVENTS; then
+    log_pass "max_restarts is counted within restart_window_secs"
+else
+    log_fail "Restart window" "$(cat $EVENTS)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+$BINARY run "true" --restart always --backoff-base 0.2 --max-restarts 1 --headless 2>$EVENTS &
+SPM_PID=$!
+sleep 3.5
+if grep -q '"event":"restart","index":0[^}]*"backoff_secs":0.2' $EVENTS; then
+    log_pass "--restart and --backoff-base apply to positional commands"
+else
+    log_fail "Restart flags" "$(cat $EVENTS)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+cat > /tmp/spm-test-restart/jitter.toml <<'EOF_TOML'
+[[child]]
+command = "exit 1"
+backoff_base_secs = 3
+backoff_jitter = 0.5
+
+[[child]]
+command = "exit 1"
+backoff_base_secs = 3
+backoff_jitter = 0.5
+
+[[child]]
+command = "exit 1"
+backoff_base_secs = 3
+backoff_jitter = 0.5
+EOF_TOML
+$BINARY run --config /tmp/spm-test-restart/jitter.toml --headless 2>$EVENTS &
+SPM_PID=$!
+for _ in $(seq 1 60); do
+    [[ $(grep -c '"event":"restart"' $EVENTS) -ge 3 ]] && break
+    sleep 0.1
+done
+DELAYS=$(grep -o '"backoff_secs":[0-9.]*' $EVENTS | sort -u | wc -l)
+if [[ $(grep -c '"event":"restart"' $EVENTS) -ge 3 && $DELAYS -ge 2 ]]; then
+    log_pass "Children that crash together restart after different jittered delays"
+else
+    log_fail "Restart jitter" "$(grep '"event":"restart"' $EVENTS)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+OUTPUT=$($BINARY run "true" --backoff-jitter 2 --headless 2>&1)
+if echo "$OUTPUT" | grep -q "jitter must be between 0 and 1"; then
+    log_pass "Out-of-range backoff settings are rejected"
+else
+    log_fail "Backoff validation" "$OUTPUT"
+fi
+rm -rf /tmp/spm-test-restart
+
+header "TEST 41: Crash-loop quarantine and diagnostics"
+
+mkdir -p /tmp/spm-test-crashloop
+cat > /tmp/spm-test-crashloop/spm.toml <<'EOF_TOML'
+[[child]]
+name = "looper"
+command = "echo boom-$$; exit 7"
+backoff_base_secs = 0.1
+crash_loop = { restarts = 3, window_secs = 60, log_lines = 10 }
+EOF_TOML
+$BINARY run --config /tmp/spm-test-crashloop/spm.toml --headless 2>/tmp/spm-test-crashloop/events.json >/dev/null &
+SPM_PID=$!
+sleep 6
+EVENTS=/tmp/spm-test-crashloop/events.json
+BUNDLE="/tmp/spm-crashloop-$SPM_PID-0-looper.json"
+if grep -q '"event":"quarantined","index":0[^}]*"reason":"3 crashes in 60s, last exit code 7"' $EVENTS \
+    && [[ "$(grep -c '"event":"restart","index":0' $EVENTS)" == "2" ]] \
+    && ! grep -q '"event":"failed"' $EVENTS; then
+    log_pass "A child crashing 3 times in 60s is quarantined instead of restarted"
+else
+    log_fail "Crash-loop quarantine" "$(cat $EVENTS)"
+fi
+if [[ -f "$BUNDLE" ]] && grep -q '"exit_code":7' "$BUNDLE" && grep -q '"log":\["boom-' "$BUNDLE"; then
+    log_pass "Diagnostic bundle records exit codes and recent output"
+else
+    log_fail "Crash-loop bundle" "$(cat $BUNDLE 2>&1)"
+fi
+STATE=$($BINARY ctl --pid $SPM_PID list 2>&1)
+if kill -0 $SPM_PID 2>/dev/null && echo "$STATE" | grep -q '"state":"Quarantined","[^}]*"quarantine_reason":"3 crashes'; then
+    log_pass "Supervisor stays up and reports the quarantine reason"
+else
+    log_fail "Quarantine state" "$STATE"
+fi
+$BINARY ctl --pid $SPM_PID restart looper >/dev/null 2>&1
+sleep 2
+if [[ "$(grep -c '"event":"restart","index":0' $EVENTS)" -ge "3" ]]; then
+    log_pass "Restarting a quarantined child resumes it"
+else
+    log_fail "Quarantine resume" "$(cat $EVENTS)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+rm -f "$BUNDLE"
+
+OUTPUT=$($BINARY run --config <(printf '[[child]]\ncommand = "true"\ncrash_loop = { log_lines = 5000 }\n') --headless 2>&1)
+if echo "$OUTPUT" | grep -q "log_lines must be at most 1000"; then
+    log_pass "Out-of-range crash_loop settings are rejected"
+else
+    log_fail "Crash-loop validation" "$OUTPUT"
+fi
+
+cat > /tmp/spm-test-crashloop/killed.toml <<'EOF_TOML'
+[[child]]
+name = "server"
+command = "exec sleep 60"
+max_restarts = 10
+backoff_base_secs = 0.1
+crash_loop = { restarts = 2, window_secs = 60 }
+
+[[child]]
+name = "looper"
+command = "exit 3"
+max_restarts = 10
+backoff_base_secs = 0.1
+backoff_max_secs = 0.1
+EOF_TOML
+$BINARY run --config /tmp/spm-test-crashloop/killed.toml --headless 2>$EVENTS >/dev/null &
+SPM_PID=$!
+sleep 1
+for _ in 1 2 3; do
+    $BINARY ctl --pid $SPM_PID kill server >/dev/null 2>&1
+    sleep 2.5
+done
+if [[ "$(grep -c '"event":"restart","index":0' $EVENTS)" -ge "3" ]] \
+    && [[ "$(grep -c '"event":"restart","index":1' $EVENTS)" -ge "3" ]] \
+    && ! grep -q '"event":"quarantined"' $EVENTS; then
+    log_pass "Kills spm makes are not crashes, and detection is off unless crash_loop.restarts is set"
+else
+    log_fail "Crash-loop exclusions" "$(cat $EVENTS)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+rm -rf /tmp/spm-test-crashloop
+
+header "TEST 42: Stop signals, stop timeouts and pre-stop hooks"
+
+mkdir -p /tmp/spm-test-stop
+cat > /tmp/spm-test-stop/spm.toml <<'EOF_TOML'
+[[child]]
+name = "notebook"
+command = "trap 'echo int >> /tmp/spm-test-stop/order; exit 0' INT; while :; do sleep 0.2; done"
+stop_signal = "INT"
+pre_stop = { command = "echo pre-$SPM_CHILD_NAME >> /tmp/spm-test-stop/order" }
+
+[[child]]
+name = "stubborn"
+command = "trap '' TERM; while :; do sleep 0.2; done"
+stop_timeout_secs = 1
+
+[[child]]
+name = "drainer"
+command = "exec sleep 60"
+pre_stop = { command = "sleep 347 & sleep 30", timeout_secs = 1 }
+EOF_TOML
+$BINARY run --config /tmp/spm-test-stop/spm.toml --headless 2>/tmp/spm-test-stop/events.json &
+SPM_PID=$!
+sleep 2
+$BINARY ctl --pid $SPM_PID stop notebook >/dev/null 2>&1
+for _ in $(seq 1 30); do
+    grep -q '"event":"stopped","index":0' /tmp/spm-test-stop/events.json && break
+    sleep 0.1
+done
+if [[ "$(cat /tmp/spm-test-stop/order 2>/dev/null | tr '\n' ' ')" == "pre-notebook int " ]] \
+    && grep -q '"event":"stopped","index":0' /tmp/spm-test-stop/events.json; then
+    log_pass "stop_signal and pre_stop run in order when a child is stopped"
+else
+    log_fail "Stop signal" "$(cat /tmp/spm-test-stop/order 2>&1) $(cat /tmp/spm-test-stop/events.json)"
+fi
+$BINARY ctl --pid $SPM_PID stop drainer >/dev/null 2>&1
+sleep 2.5
+if grep -q '"event":"stopped","index":2' /tmp/spm-test-stop/events.json && ! pgrep -f "^sleep 347$" >/dev/null; then
+    log_pass "A pre-stop hook that times out is killed with everything it started"
+else
+    log_fail "Pre-stop timeout" "$(pgrep -af "^sleep 347$") $(cat /tmp/spm-test-stop/events.json)"
+    pkill -f "^sleep 347$" 2>/dev/null || true
+fi
+START=$(date +%s%N)
+kill -TERM $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+ELAPSED_MS=$(( ($(date +%s%N) - START) / 1000000 ))
+# The killed child may linger until init reaps it.
+for _ in $(seq 1 10); do
+    pgrep -f "trap '' TERM" >/dev/null || break
+    sleep 0.1
+done
+if [[ $ELAPSED_MS -lt 3500 ]] && ! pgrep -f "trap '' TERM" >/dev/null; then
+    log_pass "stop_timeout_secs shortens the grace period at shutdown (${ELAPSED_MS}ms)"
+else
+    log_fail "Stop timeout" "shutdown took ${ELAPSED_MS}ms"
+    pkill -KILL -f "trap '' TERM" 2>/dev/null || true
+fi
+
+OUTPUT=$($BINARY run "true" --stop-signal BOGUS --headless 2>&1)
+if echo "$OUTPUT" | grep -q "unknown signal 'BOGUS'"; then
+    log_pass "Unknown stop signals are rejected"
+else
+    log_fail "Stop signal validation" "$OUTPUT"
+fi
+rm -rf /tmp/spm-test-stop
+
+header "TEST 43: Background kills with visible escalation"
+
+$BINARY run "trap '' TERM; while :; do sleep 0.2; done" --stop-timeout 3 --headless 2>/tmp/spm-test-killer.json &
+SPM_PID=$!
+sleep 2
+START=$(date +%s%N)
+$BINARY ctl --pid $SPM_PID kill 0 >/dev/null 2>&1
+STATE=$($BINARY ctl --pid $SPM_PID list 2>&1)
+ELAPSED_MS=$(( ($(date +%s%N) - START) / 1000000 ))
+if [[ $ELAPSED_MS -lt 1500 ]] && echo "$STATE" | grep -q '"kill":{"stage":"signalled","signal":15,"remaining_secs":[0-9.]*}'; then
+    log_pass "Kill returns at once and the monitor reports the pending SIGTERM (${ELAPSED_MS}ms)"
+else
+    log_fail "Background kill" "${ELAPSED_MS}ms $STATE"
+fi
+sleep 5
+if grep -q '"event":"exit","index":0[^}]*"signal":9' /tmp/spm-test-killer.json \
+    && grep -q '"event":"restart","index":0' /tmp/spm-test-killer.json; then
+    log_pass "Escalates to SIGKILL after the grace period, then restarts"
+else
+    log_fail "Kill escalation" "$(cat /tmp/spm-test-killer.json)"
+fi
+if grep -q '"event":"kill_finished","index":0,"ok":true,"message":"Process group [0-9]* force-killed (SIGKILL after SIGTERM)"' /tmp/spm-test-killer.json; then
+    log_pass "The finished kill reports how it went"
+else
+    log_fail "Kill outcome" "$(cat /tmp/spm-test-killer.json)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+pkill -KILL -f "trap '' TERM; while" 2>/dev/null || true
+rm -f /tmp/spm-test-killer.json
+
+header "TEST 44: Log rotation and previous-run logs"
+
+LOG_DIR=$(mktemp -d /tmp/spm-test-logs.XXXXXX)
+timeout 20 $BINARY run "echo started; exit 3" --headless --log-dir $LOG_DIR \
+    --max-restarts 3 --backoff-base 0.2 --log-keep-runs 2 --log-timestamps >/dev/null 2>&1 || true
+CURRENT=$(ls $LOG_DIR/spm-0-*.log 2>/dev/null | grep -vE '\.[0-9TZ]+\.' | head -1)
+RUNS=$(ls $LOG_DIR | grep -cE '^spm-0-.*\.[0-9]{8}T[0-9]{6}Z\.exit-3(-[0-9]+)?\.log$')
+if [[ -n "$CURRENT" ]] && [[ $RUNS -eq 2 ]] \
+    && grep -qE '^[0-9]{4}-[0-9]{2}-[0-9]{2}T[0-9:]{8}Z started$' "$CURRENT"; then
+    log_pass "Restarts keep the last 2 runs' logs, named by exit reason, with timestamps"
+else
+    log_fail "Previous-run logs" "$(ls $LOG_DIR) / $(cat "$CURRENT" 2>/dev/null)"
+fi
+rm -rf $LOG_DIR
+
+LOG_DIR=$(mktemp -d /tmp/spm-test-logs.XXXXXX)
+$BINARY run "for i in \$(seq 1 300); do echo line-\$i-xxxxxxxxxxxxxxxxxxxx; done; sleep 30" \
+    --headless --log-dir $LOG_DIR --log-max-size 1K --log-max-files 2 >/dev/null 2>&1 &
+SPM_PID=$!
+sleep 2
+CURRENT=$(ls $LOG_DIR/spm-0-*.log | head -1)
+SIZE=$(stat -c %s "$CURRENT" 2>/dev/null || echo 0)
+if [[ -f "$CURRENT.1" ]] && [[ -f "$CURRENT.2" ]] && [[ ! -e "$CURRENT.3" ]] \
+    && [[ $SIZE -le 1024 ]] && tail -1 "$CURRENT" | grep -q "line-300-"; then
+    log_pass "Log rolls over at --log-max-size and keeps --log-max-files segments"
+else
+    log_fail "Log rotation" "$(ls -l $LOG_DIR)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+rm -rf $LOG_DIR
+
+# Under the TUI the child writes to its log itself: it outlives a killed spm.
+LOG_DIR=$(mktemp -d /tmp/spm-test-logs.XXXXXX)
+python3 - "$BINARY" "$LOG_DIR" <<'PY'
+import os, pty, select, sys, time
+pid, fd = pty.fork()
+if pid == 0:
+    os.environ["TERM"] = "xterm"
+    os.execv(sys.argv[1], [sys.argv[1], "run", "--log-dir", sys.argv[2],
+        "--log-max-size", "2K", "--log-max-files", "2",
+        "i=0; while :; do i=$((i+1)); echo tui-line-$i-xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx; echo tui-err-$i >&2; sleep 0.01; done"])
+end = time.time() + 3
+while time.time() < end:
+    if select.select([fd], [], [], 0.2)[0]:
+        try:
+            os.read(fd, 65536)
+        except OSError:
+            break
+os.kill(pid, 9)
+os.waitpid(pid, 0)
+PY
+CURRENT=$(ls $LOG_DIR/spm-0-*.log | head -1)
+BEFORE=$(tail -1 "$CURRENT" | cut -d- -f3)
+sleep 1
+AFTER=$(tail -1 "$CURRENT" | cut -d- -f3)
+if [[ -f "$CURRENT.1" ]] && [[ -f "$CURRENT.2" ]] && [[ ! -e "$CURRENT.3" ]] \
+    && pgrep -f "echo tui-line-" >/dev/null && [[ "$AFTER" -gt "$BEFORE" ]]; then
+    log_pass "TUI children write to their rotated log directly and keep running after spm is killed"
+else
+    log_fail "Direct log" "$(ls -l $LOG_DIR) before=$BEFORE after=$AFTER"
+fi
+# Rotation cuts the read part off the log, so the segments and the log
+# join up without a gap; stderr is moved into the log while spm runs.
+GAPS=$(cat "$CURRENT.2" "$CURRENT.1" "$CURRENT" | python3 -c '
+import re, sys
+text = sys.stdin.read().splitlines()[1:]
+for stream in ("line", "err"):
+    numbers = [int(m.group(1)) for l in text for m in [re.match("tui-%s-([0-9]+)" % stream, l)] if m]
+    print(len(numbers) > 100 and numbers == list(range(numbers[0], numbers[0] + len(numbers))))
+' | tr '\n' ' ')
+if grep -q "copying and truncating" "$CURRENT" "$CURRENT".*; then
+    log_skip "Lossless direct rotation" "this filesystem cannot collapse files"
+elif [[ "$GAPS" == "True True " ]] && [[ -s "$CURRENT.stderr" ]]; then
+    log_pass "Direct logs rotate without losing lines and keep the child's stderr"
+else
+    log_fail "Lossless direct rotation" "gaps=$GAPS $(ls -l $LOG_DIR)"
+fi
+pkill -f "echo tui-line-" 2>/dev/null || true
+rm -rf $LOG_DIR
+
+header "TEST 45: Kill notes in the child log and log_path in the state"
+
+LOG_DIR=$(mktemp -d /tmp/spm-test-logs.XXXXXX)
+WEB_DIR=$(mktemp -d /tmp/spm-test-web.XXXXXX)
+touch $WEB_DIR/healthz
+cat > /tmp/spm-test-logview.toml <<TOML
+[[child]]
+name = "web"
+command = "cd $WEB_DIR && exec python3 -u -m http.server 18945"
+max_restarts = 1
+health = { port = 18945, path = "/healthz", interval_secs = 1, failure_threshold = 1 }
+log = { dir = "$LOG_DIR" }
+TOML
+$BINARY run --config /tmp/spm-test-logview.toml --headless >/dev/null 2>&1 &
+SPM_PID=$!
+for _ in $(seq 1 100); do
+    STATE=$($BINARY ctl --pid $SPM_PID list 2>&1)
+    echo "$STATE" | grep -q '"health_status":"Healthy"' && break
+    sleep 0.1
+done
+if echo "$STATE" | grep -q "\"log_path\":\"$LOG_DIR/spm-0-web.log\""; then
+    log_pass "State publishes each child's log path"
+else
+    log_fail "log_path in state" "$STATE"
+fi
+rm -f $WEB_DIR/healthz
+for _ in $(seq 1 100); do
+    cat $LOG_DIR/spm-0-web*.log 2>/dev/null | grep -q '^\[spm\] killed by the health check' && break
+    sleep 0.1
+done
+if cat $LOG_DIR/spm-0-web*.log 2>/dev/null | grep -q '^\[spm\] killed by the health check (/healthz unhealthy)$'; then
+    log_pass "Health kill leaves a note in the child's log"
+else
+    log_fail "Kill note" "$(ls $LOG_DIR; cat $LOG_DIR/*.log 2>/dev/null | tail -5)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+rm -rf $LOG_DIR $WEB_DIR /tmp/spm-test-logview.toml
+
+header "TEST 46: JSON output mode for child stdout/stderr"
+
+timeout 15 $BINARY run "echo 'INFO ready'; printf 'tab\there\n'; echo '[ERROR] boom' >&2; echo '{\"level\":\"warning\",\"msg\":\"x\"}'" \
+    --headless --output-format json >/tmp/spm-test-out.json 2>/tmp/spm-test-events.json || true
+if python3 - /tmp/spm-test-out.json /tmp/spm-test-events.json <<'PY'
+import json, sys
+out = [json.loads(line) for line in open(sys.argv[1])]
+events = [json.loads(line) for line in open(sys.argv[2]) if line.startswith('{')]
+by_line = {record["line"]: record for record in out}
+assert set(by_line) == {"INFO ready", "tab\there", "[ERROR] boom", '{"level":"warning","msg":"x"}'}, by_line
+assert by_line["INFO ready"]["level"] == "info" and by_line["INFO ready"]["stream"] == "stdout"
+assert by_line["[ERROR] boom"]["level"] == "error" and by_line["[ERROR] boom"]["stream"] == "stderr"
+assert by_line['{"level":"warning","msg":"x"}']["level"] == "warn"
+assert by_line["tab\there"]["level"] is None
+assert all(record["index"] == 0 and record["pid"] > 0 and record["ts"] for record in out)
+assert events and all("event" in event for event in events)
+PY
+then
+    log_pass "Child lines are JSON records on stdout with stream and level; events stay alone on stderr"
+else
+    log_fail "JSON output" "$(cat /tmp/spm-test-out.json) // $(head -3 /tmp/spm-test-events.json)"
+fi
+
+timeout 15 $BINARY run "echo one; echo two >&2" --headless --output /tmp/spm-test-child.log \
+    >/tmp/spm-test-out.json 2>/dev/null || true
+if grep -qx "\[.*\] one" /tmp/spm-test-child.log && grep -qx "\[.*\] two" /tmp/spm-test-child.log \
+    && [[ ! -s /tmp/spm-test-out.json ]]; then
+    log_pass "--output sends both streams to a file"
+else
+    log_fail "--output file" "$(cat /tmp/spm-test-child.log)"
+fi
+rm -f /tmp/spm-test-out.json /tmp/spm-test-events.json /tmp/spm-test-child.log
+
+header "TEST 47: Exit resource accounting"
+
+timeout 20 $BINARY run "python3 -c 'import time
+data = bytearray(64 << 20)
+for i in range(0, len(data), 4096): data[i] = 1
+end = time.time() + 0.5
+while time.time() < end: pass
+time.sleep(2.5)'" --headless 2>/tmp/spm-test-usage.json >/dev/null || true
+if python3 - /tmp/spm-test-usage.json <<'PY'
+import json, sys
+exits = [e for e in map(json.loads, (l for l in open(sys.argv[1]) if l.startswith('{'))) if e["event"] == "exit"]
+usage = exits[0]["usage"]
+assert usage["user_secs"] + usage["sys_secs"] >= 0.4, usage
+assert usage["max_rss"] >= 64 << 20, usage
+assert usage["minor_faults"] >= 16000, usage
+assert usage["voluntary_switches"] >= 1 and usage["involuntary_switches"] >= 0, usage
+assert 2.9 <= usage["wall_secs"] < 10, usage
+assert usage["peak_uss"] >= 60 << 20, usage
+PY
+then
+    log_pass "Exit event carries CPU time, max RSS, faults, context switches, wall time and peak USS"
+else
+    log_fail "Exit usage" "$(grep '"exit"' /tmp/spm-test-usage.json)"
+fi
+rm -f /tmp/spm-test-usage.json
+
+$BINARY run "sleep 0.5; exit 2" --headless --max-restarts 5 --backoff-base 5 >/dev/null 2>&1 &
+SPM_PID=$!
+sleep 2
+if python3 - /tmp/spm-state-$SPM_PID.json <<'PY'
+import json, sys
+last = json.load(open(sys.argv[1]))["children"][0]["last_exit"]
+assert last["exit_code"] == 2 and last["killed_by"] is None, last
+assert 0.4 <= last["usage"]["wall_secs"] < 2 and last["usage"]["user_secs"] is not None, last
+PY
+then
+    log_pass "The state file keeps the last run's exit and usage"
+else
+    log_fail "last_exit in state" "$(cat /tmp/spm-state-$SPM_PID.json 2>/dev/null)"
+fi
+kill -INT $SPM_PID 2>/dev/null || true
+wait $SPM_PID 2>/dev/null || true
+
+header "TEST 48: Profile a command"
+
+rm -rf /tmp/spm-test-profile-rec
+SPM_RECORDINGS_DIR=/tmp/spm-test-profile-rec timeout 20 $BINARY profile --interval 0.5 --json -o /tmp/spm-test-profile.json \
+    "python3 -c 'data = bytearray(48 << 20)
+for i in range(0, len(data), 4096): data[i] = 1
+import time; time.sleep(1.5)' & sleep 0.3; echo profiled; wait; exit 4" >/tmp/spm-test-profile.out 2>&1
+status=$?
+if [ "$status" -eq 4 ] && grep -q "profiled" /tmp/spm-test-profile.out && python3 - /tmp/spm-test-profile.json <<'PY'
+import json, os, sys
+summary = json.load(open(sys.argv[1]))
+assert summary["exit_code"] == 4 and summary["signal"] is None, summary
+assert summary["peak"]["processes"] >= 2, summary["peak"]
+assert summary["peak"]["uss"] >= 40 << 20, summary["peak"]
+assert summary["peak"]["uss"] >= summary["average"]["uss"] > 0, summary
+assert summary["usage"]["max_rss"] >= 48 << 20, summary["usage"]
+assert summary["usage"]["wall_secs"] >= 1.5, summary["usage"]
+assert len(summary["samples"]) >= 3, summary["samples"]
+assert summary["recording"] and os.path.exists(summary["recording"]["path"]), summary["recording"]
+assert summary["recording"]["snapshots"] == len(summary["samples"]), summary
+PY
+then
+    log_pass "Profile reports the tree's peaks, averages, samples and rusage, keeps a recording and exits with the command's code"
+else
+    log_fail "Profile JSON" "status=$status $(cat /tmp/spm-test-profile.out /tmp/spm-test-profile.json 2>/dev/null | head -20)"
+fi
+
+SPM_RECORDINGS_DIR=/tmp/spm-test-profile-rec timeout 20 $BINARY profile --interval 0.2 sleep 30 2>/tmp/spm-test-profile.txt &
+PROFILE_PID=$!
+sleep 1
+kill -INT $PROFILE_PID
+wait $PROFILE_PID
+status=$?
+if [ "$status" -eq 130 ] && grep -q "killed by SIGINT" /tmp/spm-test-profile.txt \
+    && grep -q "Timeline" /tmp/spm-test-profile.txt && grep -q "Max RSS" /tmp/spm-test-profile.txt; then
+    log_pass "SIGINT is passed on to the profiled command and the text summary still printed"
+else
+    log_fail "Profile SIGINT" "status=$status $(head -5 /tmp/spm-test-profile.txt)"
+fi
+rm -rf /tmp/spm-test-profile.json /tmp/spm-test-profile.out /tmp/spm-test-profile.txt /tmp/spm-test-profile-rec
+
 echo ""
 echo "============================================"
 echo -e "  ${GREEN}PASS: $PASS${NC} | ${RED}FAIL: $FAIL${NC} | ${YELLOW}SKIP: $SKIP${NC}"